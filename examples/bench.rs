@@ -0,0 +1,117 @@
+//! Measures where the time goes in the download pipeline.
+//!
+//! Serves a small Docusaurus-like site from a local fixture server, crawls it
+//! with `Downloader` and prints the time spent in navigation, rendering, PDF
+//! generation and merging. Requires Chrome, just like the CLI.
+//!
+//! ```bash
+//! cargo run --release --example bench -- 20
+//! ```
+
+use anyhow::Result;
+use book2pdf::{CrawlReport, Downloader};
+use std::time::Instant;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+const DEFAULT_PAGES: usize = 10;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let pages = std::env::args()
+        .nth(1)
+        .and_then(|arg| arg.parse().ok())
+        .unwrap_or(DEFAULT_PAGES);
+
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let base_url = format!("http://{}/", listener.local_addr()?);
+    tokio::spawn(serve_fixture(listener, pages));
+
+    let out_dir = std::env::temp_dir().join("book2pdf-bench");
+    let downloader = Downloader::new(out_dir.to_string_lossy().to_string(), true, false, 30.0);
+
+    println!("Crawling {} fixture pages at {}", pages, base_url);
+    let started = Instant::now();
+    let report = downloader.run(&base_url).await?;
+    let total = started.elapsed();
+
+    print_phase_table(&report);
+    println!("{:<16} {:>12}", "wall clock", total.as_millis());
+
+    Ok(())
+}
+
+fn print_phase_table(report: &CrawlReport) {
+    let timings: Vec<_> = report.pages.iter().filter_map(|page| page.timings).collect();
+    let captured = timings.len().max(1) as f64;
+
+    let phases = [
+        ("navigation", timings.iter().map(|t| t.navigation_ms).sum::<u64>()),
+        ("rendering", timings.iter().map(|t| t.render_ms).sum()),
+        ("pdf generation", timings.iter().map(|t| t.pdf_ms).sum()),
+    ];
+
+    println!();
+    println!("{:<16} {:>12} {:>14}", "phase", "total (ms)", "per page (ms)");
+    for (phase, total) in phases {
+        println!("{:<16} {:>12} {:>14.1}", phase, total, total as f64 / captured);
+    }
+    println!("{:<16} {:>12} {:>14}", "merging", report.merge_ms.unwrap_or(0), "-");
+    println!(
+        "{:<16} {:>12}",
+        "pages",
+        format!("{}/{}", report.succeeded(), report.pages.len())
+    );
+}
+
+/// Answers every request with a fixture page sharing the same sidebar
+async fn serve_fixture(listener: TcpListener, pages: usize) {
+    while let Ok((mut stream, _)) = listener.accept().await {
+        tokio::spawn(async move {
+            let mut buffer = [0u8; 4096];
+            let Ok(read) = stream.read(&mut buffer).await else {
+                return;
+            };
+
+            let request = String::from_utf8_lossy(&buffer[..read]);
+            let path = request.split_whitespace().nth(1).unwrap_or("/");
+
+            let (status, body) = if path == "/" || path.starts_with("/docs/") {
+                ("200 OK", fixture_page(path, pages))
+            } else {
+                ("404 Not Found", String::new())
+            };
+
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            let _ = stream.write_all(response.as_bytes()).await;
+        });
+    }
+}
+
+fn fixture_page(path: &str, pages: usize) -> String {
+    let sidebar: String = (1..=pages)
+        .map(|i| format!(r#"<li><a class="menu__link" href="/docs/page-{i}">Page {i}</a></li>"#))
+        .collect();
+
+    let paragraphs = "<p>Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod \
+        tempor incididunt ut labore et dolore magna aliqua.</p><pre><code>fn main() {}</code></pre>"
+        .repeat(40);
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head><title>Fixture {path}</title></head>
+<body>
+<div id="__docusaurus">
+  <aside class="theme-doc-sidebar-container"><ul class="menu">{sidebar}</ul></aside>
+  <main><article class="theme-doc-markdown"><h1>{path}</h1>{paragraphs}</article></main>
+</div>
+</body>
+</html>"#
+    )
+}
@@ -0,0 +1,35 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+/// Writes `data` to `path` without ever exposing a partially written file.
+///
+/// The data goes to a hidden `.part` file next to `path` first and is renamed
+/// into place once complete. If the write fails, or the future is dropped
+/// because the run was cancelled, the temp file is removed again.
+pub(crate) async fn write_atomic(path: &Path, data: impl AsRef<[u8]>) -> io::Result<()> {
+    let temp = TempFile(temp_path(path));
+
+    fs::write(&temp.0, data).await?;
+    fs::rename(&temp.0, path).await?;
+
+    // Renamed into place, there is nothing left to clean up
+    std::mem::forget(temp);
+    Ok(())
+}
+
+/// `dir/.name.part`, in the same directory so the rename stays on one filesystem.
+/// The extension keeps it out of `*.pdf` directory scans.
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    path.with_file_name(format!(".{}.{}.part", name, std::process::id()))
+}
+
+/// Removes the temp file when dropped
+struct TempFile(PathBuf);
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
@@ -0,0 +1,93 @@
+use colored::*;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Consecutive failures after which the global delay is increased
+const FAILURE_THRESHOLD: u32 = 2;
+/// Consecutive successes after which an increased delay is relaxed again
+const RECOVERY_THRESHOLD: u32 = 3;
+/// Delay used as the first backoff step when no base delay is configured
+const MIN_BACKOFF_DELAY: Duration = Duration::from_millis(500);
+/// Upper bound for the global delay
+const MAX_BACKOFF_DELAY: Duration = Duration::from_secs(30);
+
+/// Global delay between page captures that adapts to how the host is responding.
+///
+/// Repeated failures (navigation errors, rate limiting, server errors) double the
+/// delay; once captures succeed again it is halved step by step back to the base.
+/// A random extra of up to `jitter` is added to each wait, so requests to a host
+/// that throttles bursts are spread out instead of arriving at a fixed rate.
+pub(crate) struct Backoff {
+    base: Duration,
+    current: Duration,
+    jitter: Duration,
+    rng: fastrand::Rng,
+    consecutive_failures: u32,
+    consecutive_successes: u32,
+    actions: Vec<String>,
+}
+
+impl Backoff {
+    /// The same `seed` gives the same sequence of jittered delays
+    pub(crate) fn new(base: Duration, jitter: Duration, seed: u64) -> Self {
+        Self {
+            base,
+            current: base,
+            jitter,
+            rng: fastrand::Rng::with_seed(seed),
+            consecutive_failures: 0,
+            consecutive_successes: 0,
+            actions: Vec::new(),
+        }
+    }
+
+    /// Delay to wait before the next capture, without jitter
+    pub(crate) fn delay(&self) -> Duration {
+        self.current
+    }
+
+    /// The delay plus a random share of the jitter, drawn anew on every call
+    pub(crate) fn next_delay(&mut self) -> Duration {
+        let jitter_ms = self.jitter.as_millis() as u64;
+        self.current + Duration::from_millis(self.rng.u64(0..=jitter_ms))
+    }
+
+    pub(crate) fn on_success(&mut self) {
+        self.consecutive_failures = 0;
+        self.consecutive_successes += 1;
+
+        if self.current > self.base && self.consecutive_successes >= RECOVERY_THRESHOLD {
+            let relaxed = (self.current / 2).max(self.base);
+            let relaxed = if relaxed < MIN_BACKOFF_DELAY { self.base } else { relaxed };
+            self.record(format!("host recovered, delay lowered from {:?} to {:?}", self.current, relaxed));
+            info!("Host recovered, lowering delay to {}", format!("{:?}", relaxed).green());
+            self.current = relaxed;
+            self.consecutive_successes = 0;
+        }
+    }
+
+    pub(crate) fn on_failure(&mut self) {
+        self.consecutive_successes = 0;
+        self.consecutive_failures += 1;
+
+        if self.consecutive_failures >= FAILURE_THRESHOLD && self.current < MAX_BACKOFF_DELAY {
+            let increased = (self.current * 2).clamp(MIN_BACKOFF_DELAY, MAX_BACKOFF_DELAY);
+            self.record(format!(
+                "{} consecutive failures, delay raised from {:?} to {:?}",
+                self.consecutive_failures, self.current, increased
+            ));
+            warn!("Host keeps failing, backing off to {}", format!("{:?}", increased).yellow());
+            self.current = increased;
+            self.consecutive_failures = 0;
+        }
+    }
+
+    /// Backoff actions taken so far, in order
+    pub(crate) fn actions(&self) -> &[String] {
+        &self.actions
+    }
+
+    fn record(&mut self, action: String) {
+        self.actions.push(action);
+    }
+}
@@ -1,19 +1,182 @@
 use anyhow::{anyhow, Result};
+use chromiumoxide::cdp::browser_protocol::emulation::{MediaFeature, SetScriptExecutionDisabledParams};
+use chromiumoxide::cdp::browser_protocol::network::{BlockedReason, EventLoadingFailed, SetBlockedUrLsParams};
 use chromiumoxide::cdp::browser_protocol::page::PrintToPdfParams;
+use chromiumoxide::error::CdpError;
+use chromiumoxide::handler::viewport::Viewport;
+use chromiumoxide::handler::REQUEST_TIMEOUT;
+use chromiumoxide::listeners::EventStream;
 use chromiumoxide::{Browser, BrowserConfig};
 use colored::*;
-use futures_util::StreamExt;
+use futures_util::{FutureExt, StreamExt};
 use scraper::{Html, Selector};
+use serde::{Deserialize, Serialize};
 use slug::slugify;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::IsTerminal;
 use std::path::{Path, PathBuf};
-use std::time::Duration;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
 use tokio::fs;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::oneshot;
 use tracing::{debug, error, info, warn};
 use url::Url;
 
-use crate::PdfMerger;
+use crate::atomic_write::write_atomic;
+use crate::backoff::Backoff;
+use crate::front_matter::markdown_to_html;
+use crate::gitbook_nav;
+use crate::platform::DocPlatform;
+use crate::local_site;
+use crate::preflight;
+use crate::pdf_outline::limit_outline_depth;
+use crate::manifest::{content_hash, Manifest, ManifestDiff, ManifestEntry, MANIFEST_FILE};
+use crate::report::{CrawlReport, PageReport, PhaseTimings, PrunedLink};
+use crate::pdf_merger::{filename_title, COVER_FILE, NAV_FILE};
+use crate::{MergeOptions, PageInsert, PageSize, PdfMerger, TitleSource};
 
+/// Upper bound on click-and-wait rounds when expanding nested navigation
+const MAX_EXPAND_ROUNDS: u32 = 10;
+
+/// Pages with less visible text than this under `--no-js` probably render client-side
+const NO_JS_MIN_TEXT_CHARS: usize = 200;
+
+/// PDF page sizes are in points, 72 to the inch
+const POINTS_PER_INCH: f64 = 72.0;
+/// Chrome lays out printed pages at 96 CSS pixels per inch
+const CSS_PIXELS_PER_INCH: f64 = 96.0;
+/// Bounds for the scale computed by `fit_width`
+const MIN_FIT_SCALE: f64 = 0.5;
+const MAX_FIT_SCALE: f64 = 1.0;
+/// Largest page side most PDF readers handle (14400 units of 1/72 inch)
+const MAX_PAGE_HEIGHT_INCHES: f64 = 200.0;
+/// Heading levels kept in a page's own outline (`h1`-`h3`)
+const PAGE_OUTLINE_DEPTH: usize = 3;
+/// Even a blank page prints to more than this; anything smaller is a failed print
+const MIN_PDF_BYTES: usize = 512;
+
+/// Light code theme for `light_code`. Token colors follow highlight.js and
+/// Prism class names; highlighters that color tokens with inline styles
+/// (Shiki, prism-react-renderer) print in the plain text color instead.
+const LIGHT_CODE_CSS: &str = r#"
+:root {
+    --book2pdf-code-background: #f6f8fa;
+    --book2pdf-code-border: #d0d7de;
+    --book2pdf-code-color: #1f2328;
+    --book2pdf-code-comment: #57606a;
+    --book2pdf-code-keyword: #a40e26;
+    --book2pdf-code-string: #0a3069;
+    --book2pdf-code-number: #0550ae;
+    --book2pdf-code-function: #6639ba;
+}
+pre, code, .hljs, .prism-code, [class*="language-"] {
+    background: var(--book2pdf-code-background) !important;
+    color: var(--book2pdf-code-color) !important;
+    text-shadow: none !important;
+}
+pre {
+    border: 1px solid var(--book2pdf-code-border) !important;
+}
+pre *, .hljs *, .prism-code * {
+    background: transparent !important;
+    color: var(--book2pdf-code-color) !important;
+    text-shadow: none !important;
+}
+pre .token.comment, pre .token.prolog, pre .token.doctype, pre .hljs-comment, pre .hljs-quote {
+    color: var(--book2pdf-code-comment) !important;
+}
+pre .token.keyword, pre .token.operator, pre .token.tag, pre .hljs-keyword, pre .hljs-selector-tag, pre .hljs-built_in {
+    color: var(--book2pdf-code-keyword) !important;
+}
+pre .token.string, pre .token.char, pre .token.attr-value, pre .hljs-string, pre .hljs-regexp {
+    color: var(--book2pdf-code-string) !important;
+}
+pre .token.number, pre .token.boolean, pre .token.constant, pre .hljs-number, pre .hljs-literal {
+    color: var(--book2pdf-code-number) !important;
+}
+pre .token.function, pre .token.class-name, pre .hljs-title, pre .hljs-function {
+    color: var(--book2pdf-code-function) !important;
+}
+"#;
+
+/// Analytics, ad and chat-widget URLs blocked unless `default_blocklist` is off;
+/// none of them add to what a page prints
+const DEFAULT_BLOCKED_URLS: &[&str] = &[
+    "*google-analytics.com/*",
+    "*googletagmanager.com/*",
+    "*googlesyndication.com/*",
+    "*doubleclick.net/*",
+    "*connect.facebook.net/*",
+    "*static.hotjar.com/*",
+    "*script.hotjar.com/*",
+    "*cdn.segment.com/*",
+    "*cdn.mxpnl.com/*",
+    "*cdn.amplitude.com/*",
+    "*cdn.heapanalytics.com/*",
+    "*clarity.ms/*",
+    "*plausible.io/js/*",
+    "*js.hs-scripts.com/*",
+    "*js.hs-analytics.net/*",
+    "*widget.intercom.io/*",
+    "*js.intercomcdn.com/*",
+    "*js.driftt.com/*",
+    "*client.crisp.chat/*",
+    "*snap.licdn.com/*",
+    "*static.ads-twitter.com/*",
+];
+
+/// Page furniture left out with `strip_edit_links`: "Edit this page" links,
+/// "Last updated" lines and "Was this helpful?" widgets
+const EDIT_LINK_SELECTORS: &[&str] = &[
+    // Docusaurus
+    ".theme-edit-this-page",
+    ".theme-last-updated",
+    // GitBook
+    "a[href*=\"/edit/\"]",
+    "[data-testid=\"page-footer\"] div[dir=\"auto\"]:has(> span[aria-label])",
+    "footer div[dir=\"auto\"]:has(> span[aria-label])",
+    "[data-testid=\"page-feedback\"]",
+    // Feedback widgets of any site, only where such widgets live, so a page
+    // about feedback keeps its content
+    "footer [class*=\"feedback\" i]",
+    "aside [class*=\"feedback\" i]",
+    "form[class*=\"feedback\" i]",
+    "[aria-label*=\"feedback\" i]",
+    "[class*=\"was-this-helpful\" i]",
+];
+
+/// Site navigation left out with `strip_nav`: sidebars, the top bar, the
+/// in-page table of contents and previous/next links
+const NAV_SELECTORS: &[&str] = &[
+    // Docusaurus
+    ".theme-doc-sidebar-container",
+    ".navbar",
+    ".theme-doc-toc-desktop",
+    ".theme-doc-toc-mobile",
+    ".pagination-nav",
+    // GitBook
+    "aside:has(nav)",
+    "nav[aria-label=\"Table of contents\"]",
+    // Any site
+    "nav[aria-label*=\"pagination\" i]",
+];
+
+/// Main content containers, whose appearance means a client-rendered page is
+/// far enough along to read its links and title
+const CONTENT_SELECTORS: &[&str] = &[
+    // GitBook
+    "main",
+    ".gitbook-root",
+    // Docusaurus
+    ".theme-doc-markdown",
+    "article",
+];
+
+/// Print scale, paper and page margins, in inches, Chrome prints each page with
 #[derive(Debug, Clone)]
 pub struct PdfOptions {
     pub scale: f64,
@@ -21,6 +184,22 @@ pub struct PdfOptions {
     pub margin_right: f64,
     pub margin_bottom: f64,
     pub margin_left: f64,
+    pub paper_size: PageSize,
+    /// Turn the paper sideways, its long edge on top
+    pub landscape: bool,
+}
+
+impl PdfOptions {
+    /// Width and height of the paper as printed, in inches
+    pub fn paper_inches(&self) -> (f64, f64) {
+        let (width, height) = self.paper_size.dimensions();
+        let (width, height) = (width as f64 / POINTS_PER_INCH, height as f64 / POINTS_PER_INCH);
+        if self.landscape {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
 }
 
 impl Default for PdfOptions {
@@ -31,42 +210,864 @@ impl Default for PdfOptions {
             margin_right: 0.0,
             margin_bottom: 0.0,
             margin_left: 0.0,
+            paper_size: PageSize::Letter,
+            landscape: false,
+        }
+    }
+}
+
+/// Which files remain in the output directory after a run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Keep {
+    /// Only the individual page PDFs; nothing is combined
+    Pages,
+    /// Only the combined PDF; the page PDFs are deleted after combining
+    #[default]
+    Combined,
+    /// The combined PDF and the individual page PDFs
+    Both,
+}
+
+impl Keep {
+    /// The policy described by the older `combine`/`preserve_pages` flags
+    pub fn from_flags(combine: bool, preserve_pages: bool) -> Self {
+        match (combine, preserve_pages) {
+            (false, _) => Keep::Pages,
+            (true, false) => Keep::Combined,
+            (true, true) => Keep::Both,
+        }
+    }
+
+    fn combines(self) -> bool {
+        self != Keep::Pages
+    }
+
+    fn keeps_pages(self) -> bool {
+        self != Keep::Combined
+    }
+}
+
+impl FromStr for Keep {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "pages" => Ok(Keep::Pages),
+            "combined" => Ok(Keep::Combined),
+            "both" => Ok(Keep::Both),
+            _ => Err(format!("unknown retention policy '{}' (expected pages, combined or both)", s)),
+        }
+    }
+}
+
+impl fmt::Display for Keep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Keep::Pages => "pages",
+            Keep::Combined => "combined",
+            Keep::Both => "both",
+        };
+        f.write_str(name)
+    }
+}
+
+/// How link paths are turned into page file names
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SlugStyle {
+    /// Lowercase, path separators and other punctuation become `-` (`/API/OAuth2` -> `api-oauth2`)
+    #[default]
+    Kebab,
+    /// Lowercase, path segments joined with `_` (`/API/OAuth2` -> `api_oauth2`)
+    Lower,
+    /// Original case, path segments joined with `_` (`/API/OAuth2` -> `API_OAuth2`)
+    Preserve,
+}
+
+impl FromStr for SlugStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "kebab" => Ok(SlugStyle::Kebab),
+            "lower" => Ok(SlugStyle::Lower),
+            "preserve" => Ok(SlugStyle::Preserve),
+            _ => Err(format!("unknown slug style '{}' (expected kebab, lower or preserve)", s)),
+        }
+    }
+}
+
+impl fmt::Display for SlugStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SlugStyle::Kebab => "kebab",
+            SlugStyle::Lower => "lower",
+            SlugStyle::Preserve => "preserve",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Size of the browser window pages are laid out in, in CSS pixels
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ViewportSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Default for ViewportSize {
+    fn default() -> Self {
+        Self { width: 1920, height: 1080 }
+    }
+}
+
+impl FromStr for ViewportSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid viewport '{}' (expected WIDTHxHEIGHT, e.g. 1280x800)", s);
+        let (width, height) = s.trim().split_once(['x', 'X']).ok_or_else(invalid)?;
+        let width: u32 = width.trim().parse().map_err(|_| invalid())?;
+        let height: u32 = height.trim().parse().map_err(|_| invalid())?;
+        if width == 0 || height == 0 {
+            return Err(format!("invalid viewport '{}' (width and height must be at least 1)", s));
+        }
+        Ok(Self { width, height })
+    }
+}
+
+impl TryFrom<String> for ViewportSize {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<ViewportSize> for String {
+    fn from(size: ViewportSize) -> Self {
+        size.to_string()
+    }
+}
+
+impl fmt::Display for ViewportSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DownloadOptions {
+    /// How many times a failed page capture is retried
+    pub retries: u32,
+    /// A page whose main document took more HTTP redirects than this fails
+    pub max_redirects: u32,
+    /// Base delay between page captures, raised automatically while the host keeps failing
+    pub delay: Duration,
+    /// Up to this much is added at random to each delay, drawn anew for every page
+    pub delay_jitter: Duration,
+    /// Seed for the jitter, so a crawl's delays can be reproduced
+    pub seed: Option<u64>,
+    /// Options applied when combining the pages
+    pub merge: MergeOptions,
+    /// Write per-page crawl statistics to this file (CSV for `.csv`, JSON otherwise)
+    pub stats_file: Option<PathBuf>,
+    /// Save each page's HTML as prepared for print to `{out_dir}/html/NN_slug.html`
+    pub html_dump: bool,
+    /// Previous run's manifest, or the output directory holding it; pages whose
+    /// content hash is unchanged are not captured again
+    pub against: Option<PathBuf>,
+    /// Name the combined PDF `*-changes.pdf`, as with `against` it only holds
+    /// the pages that were added or changed
+    pub only_changed: bool,
+    /// Reuse page PDFs already in `{out_dir}/pages` that parse, instead of capturing them again
+    pub skip_existing: bool,
+    /// Place all outputs in a subdirectory of `out_dir` named after the run's start time
+    pub timestamp_dir: bool,
+    /// Capture responsive images at their highest-resolution `srcset` candidate
+    pub hires_images: bool,
+    /// Pick each page's print scale so its content fits the paper width
+    pub fit_width: bool,
+    /// Print only the first screenful of each page on one viewport-sized PDF page.
+    /// Anything below the fold is clipped, which is the point: compact previews.
+    pub viewport_only: bool,
+    /// Print each page on one sheet as tall as its content, so nothing is split
+    /// across page breaks. Capped at `MAX_PAGE_HEIGHT_INCHES`.
+    pub single_long_page: bool,
+    /// Chrome/Chromium executable to use instead of the auto-detected one
+    pub chrome_path: Option<PathBuf>,
+    /// Chrome instances the pages are captured with. Their tabs take pages
+    /// from one queue in nav order, each the next one as soon as it is free.
+    pub browsers: usize,
+    /// Pages each browser captures at the same time, each in its own tab; the
+    /// host sees `browsers * concurrency` captures at once
+    pub concurrency: usize,
+    /// Launch Chrome with this existing profile, reusing its cookies and
+    /// signed-in sessions; it must not be open in another Chrome at the same time
+    pub user_data_dir: Option<PathBuf>,
+    /// Skip nav entries whose label contains any of these substrings (case-insensitive)
+    pub skip_link_text: Vec<String>,
+    /// Links whose path contains any of these prefixes are assets, not pages
+    pub asset_paths: Vec<String>,
+    /// Capture the URLs listed in this file (one per line, `#` comments) instead of
+    /// discovering them from the site's navigation
+    pub links_file: Option<PathBuf>,
+    /// Capture exactly these paths or URLs of the site, in this order, instead
+    /// of discovering pages from its navigation
+    pub only_urls: Vec<String>,
+    /// Collect links from sites that aren't recognized as GitBook, Docusaurus
+    /// or Mintlify instead of rejecting them
+    pub skip_detection: bool,
+    /// Check every link with a HEAD request sent from the browser before
+    /// crawling and drop those that are gone (404/410) or redirect to another
+    /// site, `concurrency` at a time
+    pub verify_links: bool,
+    /// Render the site's expanded sidebar as a page right after the cover
+    pub capture_nav: bool,
+    /// On GitBook sites, take the page order and bookmark titles from the
+    /// navigation data embedded in the page, scraping the sidebar without it
+    pub gitbook_nav_data: bool,
+    /// Start the book with a generated cover showing the site's logo and title
+    pub cover: bool,
+    /// Markdown files rendered as front matter pages (title page, copyright,
+    /// preface...), in this order, after the cover and before the nav page
+    pub front_matter: Vec<PathBuf>,
+    /// Remove elements whose visible text contains any of these substrings (case-insensitive)
+    pub remove_text: Vec<String>,
+    /// How page file names are derived from link paths
+    pub slug_style: SlugStyle,
+    /// Capture a page that isn't ready within the timeout anyway instead of failing it
+    pub capture_on_timeout: bool,
+    /// Print a page again when Chrome returns an empty or invalid PDF for it
+    pub retry_empty_pdf: bool,
+    /// Open the site in the browser window and wait for the user to sign in and
+    /// press Enter before crawling, reusing that session for every page
+    pub manual_login: bool,
+    /// Narrow the main content column to at most this many CSS pixels, centered
+    pub content_max_width: Option<u32>,
+    /// Emulate `prefers-reduced-motion: reduce` and finish CSS animations and
+    /// transitions, so pages aren't printed mid-animation
+    pub reduce_motion: bool,
+    /// Print every panel of tab widgets one after another, each under its tab's
+    /// title, instead of only the selected one
+    pub expand_tabs: bool,
+    /// Print code blocks on a light background with dark token colors, whatever
+    /// the site's code theme. The palette is a set of `--book2pdf-code-*` CSS
+    /// variables that `inject_css` can override
+    pub light_code: bool,
+    /// Remove the site header at the top of every page that repeats the one of
+    /// the first page in nav order, so the banner only opens the book
+    pub dedupe_page_headers: bool,
+    /// Style sheets (CSS text) added to every page before it is printed
+    pub inject_css: Vec<String>,
+    /// Remove "Edit this page" links, "Last updated" lines and feedback widgets
+    pub strip_edit_links: bool,
+    /// Remove the site's sidebars, top bar, in-page table of contents and
+    /// previous/next links, leaving the page content
+    pub strip_nav: bool,
+    /// Print the URL of every link to another site in parentheses after the
+    /// link's text, so a printed copy still shows where it pointed
+    pub expand_external_links: bool,
+    /// A navigation with more pages than this is only crawled once the user
+    /// confirms it on the terminal, or with `assume_yes`; an oversized nav is
+    /// usually a selector that picked up footer or pagination links
+    pub confirm_threshold: usize,
+    /// Crawl navigations above `confirm_threshold` without asking
+    pub assume_yes: bool,
+    /// Load pages with their scripts disabled. Steps that need the page's
+    /// event loop (expanding the sidebar, waiting for the DOM to settle,
+    /// swapping in larger images) don't run in this mode
+    pub no_js: bool,
+    /// URL patterns (`*` wildcards) whose requests are blocked while loading pages
+    pub block_urls: Vec<String>,
+    /// Also block a built-in list of common analytics, ad and chat-widget URLs
+    pub default_blocklist: bool,
+    /// Browser window size, which decides the responsive layout that gets printed
+    pub viewport: ViewportSize,
+    /// Device pixels per CSS pixel; above 1, raster content is captured at a
+    /// higher resolution
+    pub device_scale_factor: f64,
+    /// Print tagged PDFs, which carry the pages' structure and image alt text,
+    /// and report images that have no alt text
+    pub accessible: bool,
+    /// Give each page's PDF an outline of its own `h1`-`h3` headings, so the
+    /// per-page files kept by `Keep::Pages` or `Keep::Both` are navigable
+    pub page_outlines: bool,
+    /// Append the run's start time to the combined PDF's name, so periodic
+    /// exports into the same directory don't overwrite each other
+    pub timestamp_name: bool,
+    /// Load every prepared page into one document and print it once, instead of
+    /// printing and merging a PDF per page. Pagination, fonts and page size are
+    /// consistent throughout, but the whole book is held in memory and laid
+    /// out in one go, which is slow and memory hungry for large sites
+    pub single_print: bool,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        Self {
+            retries: 2,
+            max_redirects: 10,
+            delay: Duration::ZERO,
+            delay_jitter: Duration::ZERO,
+            seed: None,
+            merge: MergeOptions::default(),
+            stats_file: None,
+            html_dump: false,
+            against: None,
+            only_changed: false,
+            skip_existing: false,
+            timestamp_dir: false,
+            hires_images: false,
+            fit_width: false,
+            viewport_only: false,
+            single_long_page: false,
+            chrome_path: None,
+            browsers: 1,
+            concurrency: 1,
+            user_data_dir: None,
+            skip_link_text: Vec::new(),
+            asset_paths: vec!["/assets/".to_string()],
+            links_file: None,
+            only_urls: Vec::new(),
+            skip_detection: false,
+            verify_links: false,
+            capture_nav: false,
+            gitbook_nav_data: false,
+            cover: true,
+            front_matter: Vec::new(),
+            remove_text: Vec::new(),
+            slug_style: SlugStyle::default(),
+            capture_on_timeout: true,
+            retry_empty_pdf: true,
+            manual_login: false,
+            content_max_width: None,
+            reduce_motion: true,
+            expand_tabs: true,
+            light_code: false,
+            dedupe_page_headers: false,
+            strip_edit_links: false,
+            strip_nav: false,
+            expand_external_links: false,
+            confirm_threshold: 500,
+            assume_yes: false,
+            inject_css: Vec::new(),
+            no_js: false,
+            block_urls: Vec::new(),
+            default_blocklist: true,
+            viewport: ViewportSize::default(),
+            device_scale_factor: 1.0,
+            accessible: false,
+            page_outlines: false,
+            timestamp_name: false,
+            single_print: false,
+        }
+    }
+}
+
+/// Failures a caller may want to tell apart, e.g. to pick an exit code.
+///
+/// Other errors are plain `anyhow` errors; downcast to check for these.
+#[derive(Debug)]
+pub enum DownloadError {
+    /// The site isn't recognized as a supported documentation platform
+    UnsupportedSite,
+    /// Chrome could not be found or started
+    BrowserLaunch(String),
+    /// Every page of the crawl failed, nothing was captured
+    AllPagesFailed { pages: usize },
+    /// Some pages failed; not returned by the downloader itself, which keeps
+    /// going without them, but by callers that treat that as an error
+    PagesFailed { failed: usize, pages: usize },
+}
+
+impl fmt::Display for DownloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DownloadError::UnsupportedSite => write!(
+                f,
+                "Not a supported documentation website (see `book2pdf platforms`), use --skip-detection to try anyway"
+            ),
+            DownloadError::BrowserLaunch(reason) => write!(f, "{}", reason),
+            DownloadError::AllPagesFailed { pages } => write!(f, "All {} pages failed to download", pages),
+            DownloadError::PagesFailed { failed, pages } => write!(f, "{} of {} pages failed to download", failed, pages),
+        }
+    }
+}
+
+impl std::error::Error for DownloadError {}
+
+/// Returned when a page's main document came back with an error status
+#[derive(Debug)]
+struct HttpStatusError {
+    status: u16,
+    url: String,
+}
+
+impl fmt::Display for HttpStatusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Server responded with HTTP {} for {}", self.status, self.url)
+    }
+}
+
+impl std::error::Error for HttpStatusError {}
+
+/// Returned when Chrome's print "succeeded" but produced no usable PDF
+#[derive(Debug)]
+struct EmptyPdfError {
+    bytes: usize,
+}
+
+impl fmt::Display for EmptyPdfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Chrome returned an empty or invalid PDF ({} bytes)", self.bytes)
+    }
+}
+
+impl std::error::Error for EmptyPdfError {}
+
+/// Steps of a page capture that can run out of time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CapturePhase {
+    /// Loading the document, up to its load event
+    Navigation,
+    /// Waiting for the loaded page's DOM to stop changing
+    Settle,
+    /// Chrome printing the page
+    Pdf,
+}
+
+impl fmt::Display for CapturePhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CapturePhase::Navigation => "navigation",
+            CapturePhase::Settle => "settle",
+            CapturePhase::Pdf => "pdf",
+        })
+    }
+}
+
+/// Returned when a step of a page capture ran out of time, naming the step
+#[derive(Debug)]
+struct PhaseTimeoutError {
+    phase: CapturePhase,
+    timeout: Duration,
+    url: String,
+    /// Time spent in the phases up to the one that stalled
+    timings: PhaseTimings,
+}
+
+impl fmt::Display for PhaseTimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let step = match self.phase {
+            CapturePhase::Navigation => "navigation never finished",
+            CapturePhase::Settle => "the page never settled (its DOM kept changing)",
+            CapturePhase::Pdf => "PDF generation never finished",
+        };
+        write!(f, "{} within {:.1}s for {}", step, self.timeout.as_secs_f64(), self.url)
+    }
+}
+
+impl std::error::Error for PhaseTimeoutError {}
+
+/// Details about a successfully captured page
+struct PageCapture {
+    http_status: Option<u16>,
+    bytes: u64,
+    page_count: Option<usize>,
+    content_hash: Option<String>,
+    /// The content matched the previous manifest, so no PDF was written
+    unchanged: bool,
+    /// The PDF from an earlier run was reused with `skip_existing`, nothing was captured
+    reused: bool,
+    /// The page redirected to one already captured for this link, so no PDF was written
+    duplicate_of: Option<String>,
+    /// The step that wasn't done when the timeout hit, captured anyway
+    stalled: Option<CapturePhase>,
+    timings: PhaseTimings,
+    /// Images without an `alt` attribute, collected with `accessible`
+    missing_alt: Vec<String>,
+    /// The prepared page's markup, collected instead of a PDF with `single_print`
+    fragment: Option<PageFragment>,
+}
+
+/// A prepared page's markup, ready to be moved into the single-print document
+struct PageFragment {
+    /// `<style>` and stylesheet `<link>` elements of the page's head
+    styles: String,
+    /// Attributes of `<html>` and `<body>`, which themes key their styles on
+    html_attributes: String,
+    body_attributes: String,
+    body: String,
+}
+
+/// A navigation entry: where it points and what the nav calls it
+#[derive(Debug, Clone)]
+struct NavLink {
+    href: String,
+    title: String,
+    /// How many entries of the nav this one sits under, 0 at the top level
+    depth: usize,
+}
+
+/// A launched Chrome and the task driving its protocol connection
+struct Chrome {
+    browser: Browser,
+    handle: tokio::task::JoinHandle<()>,
+    /// Fires when the connection to Chrome is gone
+    connection_lost: oneshot::Receiver<()>,
+}
+
+/// What the tabs of a sharded capture did, merged back into navigation order
+struct ShardedCapture {
+    /// Outcome of every link, by its position in the nav
+    results: Vec<(usize, Result<(PathBuf, PageCapture)>)>,
+    pages: Vec<PageReport>,
+}
+
+/// Shuts down extra browser instances once their shard is done
+async fn close_all(instances: Vec<Chrome>) {
+    for mut chrome in instances {
+        chrome.browser.close().await.ok();
+        chrome.handle.abort();
+    }
+}
+
+/// Mutable state shared by the page captures of one run
+struct CrawlState {
+    /// One for the whole run, shared by every browser and tab, so failures
+    /// any of them sees slow them all down
+    backoff: Arc<Mutex<Backoff>>,
+    report: CrawlReport,
+}
+
+impl CrawlState {
+    fn backoff(&self) -> MutexGuard<'_, Backoff> {
+        self.backoff.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Page PDFs captured by [`Downloader::capture`], ready to be combined by the caller
+#[derive(Debug, Clone)]
+pub struct CapturedPages {
+    /// Where the site actually lives, after client-side redirects
+    pub base_url: String,
+    /// Cover and nav pages, in order
+    pub front_matter: Vec<PathBuf>,
+    /// Content pages in navigation order; pages unchanged since `against` are left out
+    pub pages: Vec<PathBuf>,
+    pub report: CrawlReport,
+    /// Bookmark titles by page slug from the navigation data the site ships
+    /// (GitBook), used unless `titles` or `bookmarks_from_filenames` name the
+    /// page or `title_sources` is set
+    pub nav_titles: HashMap<String, String>,
+    /// How deeply the site's navigation nests each page, by slug; empty when
+    /// its nav is flat
+    pub nav_depths: HashMap<String, usize>,
+}
+
+impl CapturedPages {
+    /// All page PDFs in book order
+    pub fn paths(&self) -> impl Iterator<Item = &PathBuf> {
+        self.front_matter.iter().chain(&self.pages)
+    }
+
+    /// A merger loaded with the captured pages, front matter first.
+    /// Pages that fail to load are logged and left out.
+    pub async fn merger(&self, mut options: MergeOptions) -> PdfMerger {
+        // Titles given by page URL apply to that page's slug, which is all the merger sees
+        for page in &self.report.pages {
+            let title = options
+                .titles
+                .get(&page.url)
+                .or_else(|| options.titles.get(page.url.trim_end_matches('/')))
+                .cloned();
+            if let Some(title) = title {
+                options.titles.insert(page.slug.clone(), title);
+            }
+        }
+        // A --title-source given on the command line names where titles come from
+        if !options.bookmarks_from_filenames && options.title_sources.is_empty() {
+            for (slug, title) in &self.nav_titles {
+                options.titles.entry(slug.clone()).or_insert_with(|| title.clone());
+            }
         }
+        if options.nav_depths.is_empty() {
+            options.nav_depths = self.nav_depths.clone();
+        }
+
+        // Which pages were captured is only known now, so a custom order that
+        // doesn't fit them is not worth failing the whole crawl over
+        let pages = options.order.arrange(&self.pages).unwrap_or_else(|e| {
+            warn!("Ignoring --order {}: {}", options.order, e);
+            self.pages.clone()
+        });
+        if options.embed_provenance {
+            let urls: HashMap<&str, &str> = self
+                .report
+                .pages
+                .iter()
+                .map(|page| (page.slug.as_str(), page.url.as_str()))
+                .collect();
+            options.source_url = Some(self.base_url.clone());
+            options.source_pages = pages
+                .iter()
+                .filter_map(|path| {
+                    let slug = filename_title(&path.file_name()?.to_string_lossy());
+                    urls.get(slug.as_str()).map(|url| url.to_string())
+                })
+                .collect();
+        }
+
+        let pages = PageInsert::apply(&options.inserts, &pages).unwrap_or_else(|e| {
+            warn!("Ignoring --insert: {}", e);
+            pages
+        });
+
+        let mut merger = PdfMerger::with_options(options);
+
+        let mut outcomes = merger.add_front_matter_pdfs(&self.front_matter).await;
+        outcomes.extend(merger.add_pdfs(&pages).await);
+        for (path, added) in self.front_matter.iter().chain(&pages).zip(outcomes) {
+            if let Err(e) = added {
+                warn!("Failed to add PDF {}: {}", path.display(), e);
+            }
+        }
+
+        merger
+    }
+}
+
+/// A same-origin URL as the href the crawl expects: path plus query
+fn path_and_query(url: &Url) -> String {
+    match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    }
+}
+
+/// `/space` for `https://org.gitbook.io/space/page`: GitBook hosts each of an
+/// organization's spaces under a path prefix of the same domain
+fn gitbook_space_root(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    if !url.host_str()?.ends_with(".gitbook.io") {
+        return None;
+    }
+    let space = url.path_segments()?.find(|segment| !segment.is_empty())?;
+    Some(format!("/{}", space))
+}
+
+/// Moves root-relative nav links under the space root when the nav leaves it out.
+///
+/// A nav that links any page with the prefix already uses full paths, and its
+/// other links (sibling spaces, the organization's home) are left alone.
+fn scope_to_space(links: Vec<NavLink>, root: &str) -> Vec<NavLink> {
+    let in_space = |href: &str| href == root || href.starts_with(&format!("{}/", root));
+    if links.iter().any(|link| in_space(&link.href)) {
+        return links;
     }
+
+    info!("Resolving nav links against the GitBook space {}", root.green());
+    links
+        .into_iter()
+        .map(|link| NavLink { href: format!("{}{}", root, link.href), ..link })
+        .collect()
+}
+
+/// The pages found in a site's navigation, and what was learned on the way
+struct Discovery {
+    /// Where the start page ended up after redirects
+    base_url: String,
+    links: Vec<NavLink>,
+    platform: Option<DocPlatform>,
+    /// Links dropped because another nav entry already pointed at the page
+    duplicate_links: usize,
+    /// The links come from navigation data the site ships, whose titles are
+    /// the ones its sidebar shows and make better bookmarks than page titles
+    titles_from_nav: bool,
+}
+
+/// Everything a crawl produced, before the pages are combined
+struct Crawl {
+    /// Where the site actually lives, after client-side redirects
+    base_url: String,
+    captured: CapturedPages,
+    manifest_pages: Vec<(ManifestEntry, Option<PathBuf>)>,
+    /// Whether the crawl was compared against a previous manifest
+    incremental: bool,
 }
 
 pub struct Downloader {
     out_dir: String,
-    combine: bool,
-    preserve_pages: bool,
-    _timeout: Duration,
+    keep: Keep,
+    timeout: Duration,
     pdf_options: PdfOptions,
+    options: DownloadOptions,
+    /// Subdirectory of `out_dir` used for this run when `timestamp_dir` is set
+    run_dir: Option<String>,
+    /// Suffix of the combined PDF's name when `timestamp_name` is set
+    name_stamp: Option<String>,
+    /// With `dedupe_page_headers`, the URL of the first page in nav order and,
+    /// once that page is captured, its header's hash, which the other pages are
+    /// compared against
+    first_page_header: Mutex<Option<(String, Option<String>)>>,
+    /// The URL each captured page ended up at, after redirects, with the URL
+    /// that was requested for it; a later page landing on one is a duplicate
+    landed_urls: Mutex<HashMap<String, String>>,
 }
 
 impl Downloader {
     pub fn new(out_dir: String, combine: bool, preserve_pages: bool, timeout_seconds: f64) -> Self {
         Self {
             out_dir,
-            combine,
-            preserve_pages,
-            _timeout: Duration::from_secs_f64(timeout_seconds),
+            keep: Keep::from_flags(combine, preserve_pages),
+            timeout: Duration::from_secs_f64(timeout_seconds),
             pdf_options: PdfOptions::default(),
+            options: DownloadOptions::default(),
+            run_dir: None,
+            name_stamp: None,
+            first_page_header: Mutex::new(None),
+            landed_urls: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_options(mut self, options: DownloadOptions) -> Self {
+        // Colons are not allowed in Windows paths
+        let started = chrono::Local::now();
+        self.run_dir = options
+            .timestamp_dir
+            .then(|| started.format("%Y-%m-%dT%H-%M-%S").to_string());
+        self.name_stamp = options
+            .timestamp_name
+            .then(|| started.format("%Y-%m-%dT%H%M%S").to_string());
+        self.options = options;
+        self
+    }
+
+    /// Sets which files remain after the run, replacing the `combine` and
+    /// `preserve_pages` flags given to `new`
+    pub fn with_keep(mut self, keep: Keep) -> Self {
+        self.keep = keep;
+        self
+    }
+
+    /// Sets the print scale and margins, replacing the defaults
+    pub fn with_pdf_options(mut self, pdf_options: PdfOptions) -> Self {
+        self.pdf_options = pdf_options;
+        self
+    }
+
+    /// Directory all outputs of this run are written to
+    fn out_dir(&self) -> PathBuf {
+        match &self.run_dir {
+            Some(run_dir) => PathBuf::from(&self.out_dir).join(run_dir),
+            None => PathBuf::from(&self.out_dir),
         }
     }
 
-    pub async fn run(&self, target_url: &str) -> Result<()> {
+    /// Crawls the site at `target_url`, then combines the pages (unless combining
+    /// is off) and writes the manifest. Returns per-page statistics of the run.
+    pub async fn run(&self, target_url: &str) -> Result<CrawlReport> {
+        let crawl = self.crawl(target_url).await?;
+        self.finish(crawl).await
+    }
+
+    /// Captures every page without combining them, leaving the merge to the caller.
+    ///
+    /// The page PDFs stay on disk regardless of the `Keep` policy; the manifest
+    /// is written without a combined PDF.
+    pub async fn capture(&self, target_url: &str) -> Result<CapturedPages> {
+        let crawl = self.crawl(target_url).await?;
+        self.write_manifest(&crawl.base_url, None, crawl.manifest_pages).await?;
+        self.write_stats(&crawl.captured.report).await?;
+        Ok(crawl.captured)
+    }
+
+    /// Captures every page and hands back a merger loaded with them, configured
+    /// with this downloader's merge options, so the caller can save it wherever
+    /// and whenever it likes.
+    pub async fn into_merger(self, target_url: &str) -> Result<(PdfMerger, CrawlReport)> {
+        let captured = self.capture(target_url).await?;
+        let merger = captured.merger(self.options.merge.clone()).await;
+        Ok((merger, captured.report))
+    }
+
+    async fn crawl(&self, target_url: &str) -> Result<Crawl> {
+        *self.first_page_header.lock().unwrap_or_else(PoisonError::into_inner) = None;
+        self.landed_urls.lock().unwrap_or_else(PoisonError::into_inner).clear();
+
+        // A local directory or HTML file is crawled through its file:// URL
+        let target_url = match local_site::start_url(target_url)? {
+            Some(url) => url,
+            None => target_url.to_string(),
+        };
+        let target_url = target_url.as_str();
+
+        let executable = preflight::chrome_executable(self.options.chrome_path.as_deref())
+            .map_err(|e| DownloadError::BrowserLaunch(e.to_string()))?;
+        if let Some(profile) = &self.options.user_data_dir {
+            preflight::check_profile_dir(profile)?;
+        }
+
+        let seeded_links = match &self.options.links_file {
+            Some(path) => Some(self.read_links_file(path, target_url).await?),
+            None if !self.options.only_urls.is_empty() => Some(self.only_urls_links(target_url)?),
+            None => None,
+        };
+
         info!("Visiting \"{}\"", target_url.green());
 
-        let config = BrowserConfig::builder()
+        if self.options.device_scale_factor > 2.0 {
+            warn!("Device scale factor {} makes images much larger, expect very large PDFs",
+                  self.options.device_scale_factor);
+        }
+
+        let chrome = self.launch_browser(&executable).await?;
+        let Chrome { mut browser, handle, connection_lost } = chrome;
+
+        let result = tokio::select! {
+            result = self.run_internal(&browser, &executable, target_url, seeded_links) => result,
+            _ = connection_lost => Err(anyhow!("Browser connection lost (Chrome crashed or was closed), aborting the crawl")),
+        };
+
+        browser.close().await.ok();
+        handle.abort();
+
+        result
+    }
+
+    /// Starts a Chrome instance configured for capturing, with its protocol
+    /// handler running in the background
+    async fn launch_browser(&self, executable: &Path) -> Result<Chrome> {
+        let mut builder = BrowserConfig::builder()
+            .chrome_executable(executable)
             .with_head()
-            .window_size(1920, 1080)  // Larger viewport for better rendering
+            .window_size(self.options.viewport.width, self.options.viewport.height)
+            .request_timeout(self.request_timeout())
+            // Every tab emulates the same size, chromiumoxide would default to 800x600
+            .viewport(Viewport {
+                width: self.options.viewport.width,
+                height: self.options.viewport.height,
+                device_scale_factor: Some(self.options.device_scale_factor),
+                ..Viewport::default()
+            });
+        if let Some(profile) = &self.options.user_data_dir {
+            info!("Using the Chrome profile in {}", profile.display().to_string().blue());
+            builder = builder.user_data_dir(profile);
+        }
+        let config = builder
             .build()
-            .map_err(|e| anyhow!("Failed to create browser config: {}", e))?;
+            .map_err(|e| DownloadError::BrowserLaunch(format!("Failed to create browser config: {}", e)))?;
 
-        let (mut browser, mut handler) = Browser::launch(config)
+        let (browser, mut handler) = Browser::launch(config)
             .await
-            .map_err(|e| anyhow!("Failed to launch browser: {}", e))?;
+            .map_err(|e| DownloadError::BrowserLaunch(format!("Failed to launch browser: {}", e)))?;
 
+        // The handler stream ends when the connection to Chrome is gone; tell the
+        // crawl so it stops instead of timing out on every remaining page
+        let (connection_lost, connection_lost_rx) = oneshot::channel::<()>();
         let handle = tokio::spawn(async move {
             while let Some(h) = handler.next().await {
                 if let Err(err) = h {
@@ -80,519 +1081,2897 @@ impl Downloader {
                     }
                 }
             }
+            let _ = connection_lost.send(());
         });
 
-        let result = self.run_internal(&browser, target_url).await;
-
-        browser.close().await.ok();
-        handle.abort();
-
-        result
+        Ok(Chrome { browser, handle, connection_lost: connection_lost_rx })
     }
 
-    async fn run_internal(&self, browser: &Browser, target_url: &str) -> Result<()> {
-        let page = browser
-            .new_page("about:blank")
-            .await
-            .map_err(|e| anyhow!("Failed to create new page: {}", e))?;
-
-        page.goto(target_url)
-            .await
-            .map_err(|e| anyhow!("Failed to navigate to {}: {}", target_url, e))?;
-
-        page.wait_for_navigation()
-            .await
-            .map_err(|e| anyhow!("Failed to wait for navigation: {}", e))?;
-
-        // Wait for the page to fully load
-        tokio::time::sleep(Duration::from_millis(3000)).await;
-
-        // Navigate to a documentation page first to ensure sidebar is loaded
-        if target_url.ends_with('/') || target_url.ends_with(".com") || target_url.ends_with(".app") {
-            // Try to find the first documentation link
-            let first_doc_link = page.evaluate(r#"
-                const links = document.querySelectorAll('a[href^="/"]');
-                for (let link of links) {
-                    const href = link.getAttribute('href');
-                    if (href && href !== '/' && !href.includes('#') && !href.includes('assets')) {
-                        return link.href;
-                    }
-                }
-                return null;
-            "#).await.ok();
+    async fn run_internal(&self, browser: &Browser, executable: &Path, target_url: &str, seeded_links: Option<Vec<NavLink>>) -> Result<Crawl> {
+        // Read before crawling, so a mistyped path fails without a wasted crawl
+        let front_matter_pages = self.read_front_matter().await?;
 
-            if let Some(result) = first_doc_link {
-                if let Ok(doc_link) = result.into_value::<String>() {
-                    info!("Navigating to documentation page to load sidebar: {}", doc_link.green());
-                    page.goto(&doc_link)
-                        .await
-                        .map_err(|e| anyhow!("Failed to navigate to doc page: {}", e))?;
-                    tokio::time::sleep(Duration::from_millis(2000)).await;
-                }
-            }
+        if self.options.manual_login {
+            self.wait_for_manual_login(browser, target_url).await?;
         }
 
-        self.expand_menu_links(&page).await?;
-
-        tokio::time::sleep(Duration::from_millis(2000)).await;
-
-        let content = page
-            .content()
-            .await
-            .map_err(|e| anyhow!("Failed to get page content: {}", e))?;
+        // Links the user listed themselves need no confirmation
+        let discovered = seeded_links.is_none();
+        let Discovery { base_url, links, platform, duplicate_links, titles_from_nav } = match seeded_links {
+            Some(links) => Discovery {
+                base_url: target_url.to_string(),
+                links,
+                platform: None,
+                duplicate_links: 0,
+                titles_from_nav: false,
+            },
+            None => self.discover_links(browser, target_url).await?,
+        };
+        // Links are resolved against the canonical location, not the URL we were given
+        let target_url = base_url.as_str();
 
-        let document = Html::parse_document(&content);
+        // Before --verify-links, which would otherwise send a HEAD request to
+        // every link of a nav the user is about to turn down
+        if discovered && links.len() > self.options.confirm_threshold && !self.options.assume_yes {
+            self.confirm_large_nav(links.len()).await?;
+        }
 
-        if !self.is_supported_documentation_site(&document) {
-            return Err(anyhow!("Not a supported documentation website (GitBook or Docusaurus)"));
+        // Without a seed, pick one and log it so the run's delays can be repeated
+        let seed = self.options.seed.unwrap_or_else(|| fastrand::u64(..));
+        if !self.options.delay_jitter.is_zero() {
+            info!("Adding up to {:?} of random delay between pages (seed {})", self.options.delay_jitter, seed);
         }
 
-        let links = self.collect_links(&document);
-        debug!("Links collected: {:?}", links);
+        let (links, pruned_links) = if self.options.verify_links && local_site::root(target_url).is_some() {
+            warn!("Ignoring --verify-links, the pages of a local site can't be checked with HEAD requests");
+            (links, Vec::new())
+        } else if self.options.verify_links {
+            self.verify_links(browser, target_url, links, seed).await?
+        } else {
+            (links, Vec::new())
+        };
 
         // Create output directory structure
-        let pages_dir = PathBuf::from(&self.out_dir).join("pages");
+        let pages_dir = self.out_dir().join("pages");
         fs::create_dir_all(&pages_dir)
             .await
             .map_err(|e| anyhow!("Failed to create pages directory: {}", e))?;
 
         let mut pdf_paths = Vec::new();
+        let mut front_matter = 0;
 
         // Create cover page with logo first
-        if let Ok(cover_path) = self.create_cover_page(browser, target_url).await {
-            pdf_paths.push(cover_path);
+        if self.options.cover {
+            if let Ok(cover_path) = self.create_cover_page(browser, target_url).await {
+                pdf_paths.push(cover_path);
+                front_matter += 1;
+            }
         }
 
-        // Use links in the order they were collected (navigation order) 
-        // Start index from 2 since cover page takes index 1
-        for (index, href) in links.iter().enumerate() {
-            if let Ok(path) = self.download_link(browser, target_url, href, index + 2).await {
-                pdf_paths.push(path);
-            }
+        for (i, (name, markdown)) in front_matter_pages.iter().enumerate() {
+            // Sorts between the cover and the nav page
+            let filename = format!("01_front_{:02}-{}.pdf", i + 1, slugify(name));
+            let path = pages_dir.join(filename);
+            self.convert_html_to_pdf(browser, &markdown_to_html(markdown, name), &path)
+                .await
+                .map_err(|e| anyhow!("Failed to render front matter page {}: {}", name, e))?;
+
+            info!("Front matter page created: {}", path.display().to_string().blue());
+            pdf_paths.push(path);
+            front_matter += 1;
         }
 
-        if self.combine && !pdf_paths.is_empty() {
-            let _combined_path = self.combine_all_pdfs(target_url, &pdf_paths).await?;
-            
-            // Delete individual pages unless preserve_pages is set
-            if !self.preserve_pages {
-                info!("Cleaning up individual page files...");
-                for pdf_path in &pdf_paths {
-                    if let Err(e) = fs::remove_file(pdf_path).await {
-                        warn!("Failed to remove {}: {}", pdf_path.display(), e);
-                    }
+        // The nav is rendered from a doc page, where the sidebar is complete
+        if let (true, Some(first)) = (self.options.capture_nav, links.first()) {
+            let nav_url = self.resolve_href(target_url, &first.href);
+            match self.capture_nav(browser, &nav_url).await {
+                Ok(Some(nav_path)) => {
+                    pdf_paths.push(nav_path);
+                    front_matter += 1;
                 }
-                
-                // Remove pages directory if empty
-                if let Ok(mut entries) = fs::read_dir(&pages_dir).await {
-                    if entries.next_entry().await?.is_none() {
-                        let _ = fs::remove_dir(&pages_dir).await;
+                Ok(None) => warn!("No navigation found on {}, skipping the nav page", nav_url),
+                Err(e) => warn!("Failed to capture the navigation page: {}", e),
+            }
+        }
+
+        let previous = match &self.options.against {
+            Some(path) if path.is_dir() => Some(Manifest::load(&path.join(MANIFEST_FILE)).await?),
+            Some(path) => Some(Manifest::load(path).await?),
+            None => None,
+        };
+        let previous_hashes = previous.as_ref().map(|manifest| manifest.hashes());
+
+        if self.options.skip_existing {
+            self.check_resumed_manifest(target_url).await;
+        }
+
+        let mut state = CrawlState {
+            backoff: Arc::new(Mutex::new(Backoff::new(self.options.delay, self.options.delay_jitter, seed))),
+            report: CrawlReport {
+                pruned_links,
+                platform: platform.map(|platform| platform.to_string()),
+                duplicate_links,
+                ..CrawlReport::default()
+            },
+        };
+        let mut diff = ManifestDiff::default();
+        let mut manifest_pages = Vec::new();
+        let mut fragments = Vec::new();
+
+        if let Some(first) = links.first().filter(|_| self.options.dedupe_page_headers) {
+            let first_url = Url::parse(&self.resolve_href(target_url, &first.href)).map(String::from).unwrap_or_default();
+            *self.first_page_header.lock().unwrap_or_else(PoisonError::into_inner) = Some((first_url, None));
+        }
+
+        // Use links in the order they were collected (navigation order)
+        let indexed: Vec<(usize, &NavLink)> = links.iter().enumerate().collect();
+        let results = if (self.options.browsers > 1 || self.options.concurrency > 1) && links.len() > 1 {
+            // The other pages' headers are compared against the first page's, so
+            // it is captured before they start rather than whenever its shard gets to it
+            let (mut results, rest) = if self.options.dedupe_page_headers {
+                let first = self.capture_links(browser, target_url, indexed[..1].iter().copied(), previous_hashes.as_ref(), &mut state).await;
+                (first, &indexed[1..])
+            } else {
+                (Vec::new(), &indexed[..])
+            };
+
+            let sharded = self
+                .capture_sharded(browser, executable, target_url, rest, previous_hashes.as_ref(), &state.backoff)
+                .await?;
+            state.report.pages.extend(sharded.pages);
+            results.extend(sharded.results);
+            results
+        } else {
+            self.capture_links(browser, target_url, indexed.iter().copied(), previous_hashes.as_ref(), &mut state).await
+        };
+
+        let reused = results
+            .iter()
+            .filter(|(_, result)| matches!(result, Ok((_, capture)) if capture.reused))
+            .count();
+        if reused > 0 {
+            info!("Reused {} of {} page PDFs already in {}", reused, results.len(), pages_dir.display());
+        }
+
+        let failed = results.iter().filter(|(_, result)| result.is_err()).count();
+
+        for (index, result) in results {
+            let href = &links[index].href;
+            let url = self.resolve_href(target_url, href);
+
+            if let Ok((path, mut capture)) = result {
+                // The page is in the book under the link that got there first
+                if capture.duplicate_of.is_some() {
+                    continue;
+                }
+                if let Some(hashes) = &previous_hashes {
+                    if capture.unchanged {
+                        diff.unchanged += 1;
+                    } else if hashes.contains_key(url.as_str()) {
+                        diff.changed += 1;
+                    } else {
+                        diff.added += 1;
+                    }
+                }
+
+                manifest_pages.push((
+                    ManifestEntry {
+                        url,
+                        slug: self.href_to_slug(href),
+                        content_hash: capture.content_hash,
+                        file: None,
+                    },
+                    (!capture.unchanged).then(|| path.clone()),
+                ));
+
+                if let Some(fragment) = capture.fragment.take() {
+                    fragments.push(fragment);
+                } else if !capture.unchanged {
+                    pdf_paths.push(path);
+                }
+            }
+        }
+
+        if let Some(first) = links.first().filter(|_| !fragments.is_empty()) {
+            let first_url = self.resolve_href(target_url, &first.href);
+            pdf_paths.push(self.print_single_document(browser, &first_url, &fragments).await?);
+        }
+
+        let backoff_actions = state.backoff().actions().to_vec();
+        if !backoff_actions.is_empty() {
+            info!("Backoff actions taken during the crawl:");
+            for action in &backoff_actions {
+                info!("  {}", action);
+            }
+        }
+
+        let timed_out: Vec<&PageReport> = state.report.pages.iter().filter(|page| page.timed_out).collect();
+        if !timed_out.is_empty() {
+            warn!("{} pages were captured after the readiness timeout and may be incomplete:", timed_out.len());
+            for page in timed_out {
+                match &page.stalled_phase {
+                    Some(phase) => warn!("  {} ({} unfinished)", page.url, phase),
+                    None => warn!("  {}", page.url),
+                }
+            }
+        }
+
+        let abandoned: Vec<&PageReport> = state
+            .report
+            .pages
+            .iter()
+            .filter(|page| !page.success && page.stalled_phase.is_some())
+            .collect();
+        if !abandoned.is_empty() {
+            warn!("{} pages kept timing out and are missing from the book:", abandoned.len());
+            for page in abandoned {
+                warn!("  {} ({} unfinished)", page.url, page.stalled_phase.as_deref().unwrap_or_default());
+            }
+        }
+
+        let empty: Vec<&PageReport> = state.report.pages.iter().filter(|page| page.empty_pdf).collect();
+        if !empty.is_empty() {
+            warn!("{} pages kept printing as empty PDFs and are missing from the book:", empty.len());
+            for page in empty {
+                warn!("  {}", page.url);
+            }
+        }
+
+        if let Some(previous) = &previous {
+            let discovered: HashSet<&str> = manifest_pages.iter().map(|(entry, _)| entry.url.as_str()).collect();
+            diff.removed = previous
+                .pages
+                .iter()
+                .filter(|entry| !discovered.contains(entry.url.as_str()))
+                .count();
+
+            info!(
+                "Compared against previous manifest: {} added, {} changed, {} unchanged, {} removed",
+                diff.added.to_string().green(),
+                diff.changed.to_string().yellow(),
+                diff.unchanged,
+                diff.removed.to_string().red()
+            );
+        }
+
+        if failed > 0 && failed == links.len() {
+            // Nothing is left to merge, so these statistics are final
+            self.write_stats(&state.report).await?;
+            return Err(DownloadError::AllPagesFailed { pages: failed }.into());
+        }
+
+        let nav_titles = if titles_from_nav {
+            links
+                .iter()
+                .filter(|link| !link.title.is_empty())
+                .map(|link| (self.href_to_slug(&link.href), link.title.clone()))
+                .collect()
+        } else {
+            HashMap::new()
+        };
+        let nav_depths = if links.iter().any(|link| link.depth > 0) {
+            links.iter().map(|link| (self.href_to_slug(&link.href), link.depth)).collect()
+        } else {
+            HashMap::new()
+        };
+
+        let pages = pdf_paths.split_off(front_matter);
+        Ok(Crawl {
+            base_url: base_url.clone(),
+            captured: CapturedPages {
+                base_url: base_url.clone(),
+                front_matter: pdf_paths,
+                pages,
+                report: state.report,
+                nav_titles,
+                nav_depths,
+            },
+            manifest_pages,
+            incremental: previous.is_some(),
+        })
+    }
+
+    /// Captures `links` one after another in `browser`, pausing between them.
+    ///
+    /// Each link comes with its position in the navigation, which numbers its
+    /// PDF and is handed back with its outcome. Outcomes and page reports are
+    /// in the order the links were taken.
+    async fn capture_links<'a>(
+        &self,
+        browser: &Browser,
+        target_url: &str,
+        links: impl IntoIterator<Item = (usize, &'a NavLink)>,
+        previous_hashes: Option<&HashMap<&str, Option<&str>>>,
+        state: &mut CrawlState,
+    ) -> Vec<(usize, Result<(PathBuf, PageCapture)>)> {
+        let mut results = Vec::new();
+        let mut fetched = 0;
+        for (index, link) in links {
+            if self.options.skip_existing {
+                if let Some(reused) = self.reuse_existing(target_url, &link.href, index + 2, state).await {
+                    results.push((index, Ok(reused)));
+                    continue;
+                }
+            }
+
+            // Reused pages send no request, so only the pages actually fetched are spaced out
+            if fetched > 0 {
+                let delay = state.backoff().next_delay();
+                if !delay.is_zero() {
+                    debug!("Waiting {:?} before the next page", delay);
+                    tokio::time::sleep(delay).await;
+                }
+            }
+
+            let url = self.resolve_href(target_url, &link.href);
+            let previous_hash = previous_hashes.and_then(|hashes| hashes.get(url.as_str()).copied().flatten());
+
+            // Start index from 2 since cover page takes index 1
+            let result = self
+                .download_link_with_retry(browser, target_url, &link.href, index + 2, previous_hash, state)
+                .await;
+            results.push((index, result));
+            fetched += 1;
+        }
+        results
+    }
+
+    /// Spreads `links` over `browsers` Chrome instances, `browser` and ones
+    /// launched for the purpose, and `concurrency` tabs in each, and captures
+    /// the shards at the same time.
+    ///
+    /// The tabs take links from one queue in nav order, so a tab that is done
+    /// with its page takes the next one instead of waiting behind another
+    /// tab's slow pages. The shards share the run's `backoff`, so rate
+    /// limiting seen by one of them raises the delay for all; each waits that
+    /// delay between its own pages. A failed page only
+    /// fails its own link; losing a browser aborts the crawl right away, with
+    /// the other shards cancelled wherever they are. Page reports and
+    /// outcomes come back in navigation order. The extra instances are closed
+    /// before returning, including on failure.
+    async fn capture_sharded(
+        &self,
+        browser: &Browser,
+        executable: &Path,
+        target_url: &str,
+        links: &[(usize, &NavLink)],
+        previous_hashes: Option<&HashMap<&str, Option<&str>>>,
+        backoff: &Arc<Mutex<Backoff>>,
+    ) -> Result<ShardedCapture> {
+        let browser_count = self.options.browsers.min(links.len());
+        let shard_count = (browser_count * self.options.concurrency).min(links.len());
+        match (browser_count, self.options.concurrency) {
+            (_, 1) => info!("Capturing {} pages with {} browsers", links.len(), browser_count),
+            (1, _) => info!("Capturing {} pages, {} at a time", links.len(), shard_count),
+            _ => info!("Capturing {} pages with {} browsers, {} at a time", links.len(), browser_count, shard_count),
+        }
+
+        let mut extra = Vec::with_capacity(browser_count - 1);
+        for _ in 1..browser_count {
+            match self.launch_browser(executable).await {
+                Ok(chrome) => extra.push(chrome),
+                Err(e) => {
+                    close_all(extra).await;
+                    return Err(e);
+                }
+            }
+        }
+
+        // Every tab takes its next link from here, in nav order
+        let queue = Mutex::new(links.iter().copied());
+        let next_link = || queue.lock().unwrap_or_else(PoisonError::into_inner).next();
+
+        let mut states: Vec<CrawlState> = (0..shard_count)
+            .map(|_| CrawlState { backoff: Arc::clone(backoff), report: CrawlReport::default() })
+            .collect();
+
+        let outcomes = {
+            // Shard n runs in browser n % browser_count
+            let mut tabs: Vec<Vec<_>> = (0..browser_count).map(|_| Vec::new()).collect();
+            for (shard, state) in states.iter_mut().enumerate() {
+                tabs[shard % browser_count].push((shard, state));
+            }
+
+            // The first instance's connection is watched by `crawl`
+            let instances = std::iter::once((browser, None))
+                .chain(extra.iter_mut().map(|chrome| (&chrome.browser, Some(&mut chrome.connection_lost))));
+
+            let browser_runs = instances
+                .zip(tabs)
+                .enumerate()
+                .map(|(index, ((browser, lost), tabs))| async move {
+                    let connection_lost = async {
+                        match lost {
+                            Some(receiver) => {
+                                let _ = receiver.await;
+                            }
+                            None => std::future::pending().await,
+                        }
+                    };
+                    let tab_runs = tabs.into_iter().map(|(shard, state)| async move {
+                        let links = std::iter::from_fn(&next_link);
+                        (shard, self.capture_links(browser, target_url, links, previous_hashes, state).await)
+                    });
+                    tokio::select! {
+                        results = futures_util::future::join_all(tab_runs) => Ok(results),
+                        _ = connection_lost => Err(anyhow!("Browser {} connection lost (Chrome crashed or was closed), aborting the crawl", index + 1)),
+                    }
+                });
+            // The first error drops the other browsers' runs instead of waiting
+            // for them to capture the rest of the queue
+            futures_util::future::try_join_all(browser_runs).await
+        };
+        close_all(extra).await;
+
+        let mut tab_results: Vec<_> = outcomes?.into_iter().flatten().collect();
+        tab_results.sort_by_key(|(shard, _)| *shard);
+
+        // A shard reports its pages in the order it took its links, which its outcomes give
+        let mut results = Vec::with_capacity(links.len());
+        let mut pages = Vec::with_capacity(links.len());
+        for ((_, shard_results), shard_state) in tab_results.into_iter().zip(states) {
+            pages.extend(shard_results.iter().map(|(index, _)| *index).zip(shard_state.report.pages));
+            results.extend(shard_results);
+        }
+        results.sort_by_key(|(index, _)| *index);
+        pages.sort_by_key(|(index, _)| *index);
+
+        Ok(ShardedCapture {
+            results,
+            pages: pages.into_iter().map(|(_, page)| page).collect(),
+        })
+    }
+
+    /// Combines the captured pages, cleans up and writes the manifest
+    async fn finish(&self, crawl: Crawl) -> Result<CrawlReport> {
+        let Crawl { base_url, mut captured, manifest_pages, incremental } = crawl;
+        let target_url = base_url.as_str();
+        let pages_dir = self.out_dir().join("pages");
+
+        let mut combined_path = None;
+        let mut checksum = None;
+        if self.keep.combines() && !captured.pages.is_empty() {
+            let merge_started = Instant::now();
+            let (path, sha256, page_count) = self.combine_all_pdfs(target_url, &captured).await?;
+            captured.report.combined_page_count = Some(page_count);
+            combined_path = Some(path);
+            checksum = sha256;
+            captured.report.merge_ms = Some(merge_started.elapsed().as_millis() as u64);
+            
+            // Delete individual pages unless they are kept as well
+            if !self.keep.keeps_pages() {
+                info!("Cleaning up individual page files...");
+                for pdf_path in captured.paths() {
+                    if let Err(e) = fs::remove_file(pdf_path).await {
+                        warn!("Failed to remove {}: {}", pdf_path.display(), e);
+                    }
+                    if self.options.merge.attach_source {
+                        // Front matter has no source to remove
+                        let _ = fs::remove_file(pdf_path.with_extension("html")).await;
+                    }
+                }
+                
+                // Remove pages directory if empty
+                if let Ok(mut entries) = fs::read_dir(&pages_dir).await {
+                    if entries.next_entry().await?.is_none() {
+                        let _ = fs::remove_dir(&pages_dir).await;
+                    }
+                }
+            }
+        } else if self.keep.combines() && incremental {
+            info!("No pages changed since the previous manifest, nothing to combine");
+        }
+
+        self.write_manifest(target_url, combined_path.as_deref(), manifest_pages).await?;
+        captured.report.output_path = Some(combined_path.clone().unwrap_or_else(|| self.out_dir()));
+        // After the merge, which fills in the combined page count and merge time
+        self.write_stats(&captured.report).await?;
+
+        match &combined_path {
+            Some(path) => info!("Done, combined PDF: {}", path.display().to_string().green()),
+            None => info!("Done, output in: {}", self.out_dir().display().to_string().green()),
+        }
+        if let Some(sha256) = checksum {
+            info!("SHA-256: {}", sha256);
+        }
+
+        let missing_alt: usize = captured.report.pages.iter().map(|page| page.missing_alt.len()).sum();
+        if missing_alt > 0 {
+            let pages = captured.report.pages.iter().filter(|page| !page.missing_alt.is_empty()).count();
+            warn!("{} images on {} pages have no alt text, screen readers can't describe them", missing_alt, pages);
+        }
+
+        Ok(captured.report)
+    }
+
+    /// Writes `report` to the `stats_file`, when one is set
+    async fn write_stats(&self, report: &CrawlReport) -> Result<()> {
+        if let Some(stats_file) = &self.options.stats_file {
+            report.write_to(stats_file).await?;
+            info!("Crawl statistics written to: {}", stats_file.display().to_string().blue());
+        }
+        Ok(())
+    }
+
+    /// Lets the user sign in through the visible browser window before the crawl.
+    ///
+    /// Every tab shares the browser's default context, so the cookies set during
+    /// the login carry over to the pages captured afterwards.
+    async fn wait_for_manual_login(&self, browser: &Browser, target_url: &str) -> Result<()> {
+        let page = browser
+            .new_page("about:blank")
+            .await
+            .map_err(|e| anyhow!("Failed to create new page: {}", e))?;
+
+        self.goto(&page, target_url).await?;
+
+        info!("{}", "Log in to the site in the browser window, then press Enter here to start the crawl".yellow());
+
+        let mut line = String::new();
+        let read = BufReader::new(tokio::io::stdin())
+            .read_line(&mut line)
+            .await
+            .map_err(|e| anyhow!("Failed to read from stdin: {}", e))?;
+        page.close().await.ok();
+
+        if read == 0 {
+            return Err(anyhow!("stdin was closed before the login was confirmed"));
+        }
+
+        info!("Resuming with the logged-in session");
+        Ok(())
+    }
+
+    /// Asks on the terminal whether to crawl a navigation of `pages` pages,
+    /// above `confirm_threshold`; without a terminal to ask on the crawl stops
+    async fn confirm_large_nav(&self, pages: usize) -> Result<()> {
+        let threshold = self.options.confirm_threshold;
+        if !std::io::stdin().is_terminal() {
+            return Err(anyhow!(
+                "Found {} pages, more than --confirm-threshold {}; pass --yes to capture them all",
+                pages, threshold
+            ));
+        }
+
+        warn!("Found {} pages, more than --confirm-threshold {}; a selector may have picked up footer or pagination links", pages, threshold);
+        info!("{}", format!("Capture all {} pages? [y/N]", pages).yellow());
+
+        let mut line = String::new();
+        BufReader::new(tokio::io::stdin())
+            .read_line(&mut line)
+            .await
+            .map_err(|e| anyhow!("Failed to read from stdin: {}", e))?;
+
+        match line.trim().to_ascii_lowercase().as_str() {
+            "y" | "yes" => Ok(()),
+            _ => Err(anyhow!("Crawl of {} pages not confirmed", pages)),
+        }
+    }
+
+    /// Finds the pages to capture from the site's expanded navigation.
+    ///
+    /// Also returns the URL the start page ended up at, which differs from
+    /// `target_url` when the site redirects to a canonical host or path.
+    async fn discover_links(&self, browser: &Browser, target_url: &str) -> Result<Discovery> {
+        let page = browser
+            .new_page("about:blank")
+            .await
+            .map_err(|e| anyhow!("Failed to create new page: {}", e))?;
+        self.disable_scripts(&page).await?;
+        let mut failed_requests = self.block_requests(&page).await?;
+
+        self.goto(&page, target_url).await?;
+
+        // Client-rendered sites (Mintlify) only have their nav once the app settled
+        if let Some(phase) = self.wait_until_ready(&page).await? {
+            warn!("{} was not ready after {:.1}s ({} unfinished), looking for links anyway", target_url, self.timeout.as_secs_f64(), phase);
+        }
+        self.log_blocked(target_url, failed_requests.as_mut());
+
+        let base_url = self.landed_url(&page, target_url).await;
+
+        // Wait for the page to fully load
+        self.wait_for_content(&page, CONTENT_SELECTORS, Duration::from_millis(3000)).await;
+
+        // Navigate to a documentation page first to ensure sidebar is loaded
+        if target_url.ends_with('/') || target_url.ends_with(".com") || target_url.ends_with(".app") {
+            // Try to find the first documentation link
+            // Asset links are skipped the way `is_asset_path` skips them
+            let asset_paths: Vec<&String> = self.options.asset_paths.iter().filter(|prefix| !prefix.is_empty()).collect();
+            let first_doc_link = page.evaluate(format!(r#"
+                (() => {{
+                    const assetPaths = {};
+                    const links = document.querySelectorAll('a[href^="/"]');
+                    for (let link of links) {{
+                        const href = link.getAttribute('href');
+                        if (href && href !== '/' && !href.includes('#') && !assetPaths.some((prefix) => href.includes(prefix))) {{
+                            return link.href;
+                        }}
+                    }}
+                    return null;
+                }})()
+            "#, serde_json::to_string(&asset_paths)?)).await.ok();
+
+            if let Some(result) = first_doc_link {
+                if let Ok(doc_link) = result.into_value::<String>() {
+                    info!("Navigating to documentation page to load sidebar: {}", doc_link.green());
+                    self.goto(&page, &doc_link).await?;
+                    self.wait_for_content(&page, CONTENT_SELECTORS, Duration::from_millis(2000)).await;
+                }
+            }
+        }
+
+        self.expand_menu_links(&page).await?;
+        self.wait_for_links_to_settle(&page, Duration::from_millis(2000)).await;
+
+        let content = page
+            .content()
+            .await
+            .map_err(|e| anyhow!("Failed to get page content: {}", e))?;
+
+        let document = Html::parse_document(&content);
+        if self.options.no_js {
+            let text = page
+                .evaluate("document.body ? document.body.innerText : ''")
+                .await
+                .ok()
+                .and_then(|result| result.into_value::<String>().ok())
+                .unwrap_or_default();
+            self.warn_if_needs_js(target_url, &text);
+        }
+
+        let platform = DocPlatform::detect(&document);
+        if self.options.skip_detection {
+            warn!("Skipping site detection, collecting links from {} as if it were a supported site", target_url);
+        } else if platform.is_none() {
+            return Err(DownloadError::UnsupportedSite.into());
+        }
+
+        // GitBook's own page tree beats scraping its sidebar, when the page has it
+        let nav_data = match platform {
+            Some(DocPlatform::GitBook | DocPlatform::GitBookLegacy) if self.options.gitbook_nav_data => {
+                gitbook_nav::nav_links(&document)
+            }
+            _ => None,
+        };
+        let titles_from_nav = nav_data.is_some();
+        let (links, duplicate_links) = match nav_data {
+            Some(links) => {
+                info!("Using the navigation data GitBook embeds in the page ({} pages)", links.len());
+                let links = links.into_iter().map(|(href, title, depth)| NavLink { href, title, depth }).collect();
+                (links, 0)
+            }
+            None => self.collect_links(&document, &base_url),
+        };
+        let mut links = self.filter_links_by_text(links);
+        if let Some(root) = gitbook_space_root(&base_url) {
+            links = scope_to_space(links, &root);
+        }
+        debug!("Links collected: {:?}", links);
+        Ok(Discovery { base_url, links, platform, duplicate_links, titles_from_nav })
+    }
+
+    /// Sends a HEAD request for each link and drops those that are gone or
+    /// redirect to another origin, returning the kept links and the dropped ones.
+    ///
+    /// The requests are sent by the browser from the site's own origin, so they
+    /// carry its cookies and a redirect elsewhere shows up as a failed request
+    /// (the response is cross-origin). Links that merely don't answer HEAD
+    /// (405, 5xx) or whose request fails for another reason (DNS, timeout,
+    /// connection reset) are kept for the crawl to deal with.
+    ///
+    /// Up to `concurrency` tabs take the links from one queue in nav order,
+    /// each waiting the `delay` and `delay_jitter` of one shared backoff
+    /// between its requests, like the captures do.
+    async fn verify_links(
+        &self,
+        browser: &Browser,
+        target_url: &str,
+        links: Vec<NavLink>,
+        seed: u64,
+    ) -> Result<(Vec<NavLink>, Vec<PrunedLink>)> {
+        let tab_count = self.options.concurrency.min(links.len()).max(1);
+        let mut pages = Vec::with_capacity(tab_count);
+        for _ in 0..tab_count {
+            let page = browser
+                .new_page("about:blank")
+                .await
+                .map_err(|e| anyhow!("Failed to create new page: {}", e))?;
+            pages.push(page);
+        }
+        for page in &pages {
+            page.execute(SetScriptExecutionDisabledParams::new(true))
+                .await
+                .map_err(|e| anyhow!("Failed to disable JavaScript: {}", e))?;
+            self.goto(page, target_url).await?;
+        }
+
+        info!("Verifying {} links before crawling", links.len());
+        let queue = Mutex::new(links.into_iter().enumerate());
+        let backoff = Mutex::new(Backoff::new(self.options.delay, self.options.delay_jitter, seed));
+
+        let tab_runs = pages.iter().map(|page| async {
+            let mut checked = Vec::new();
+            loop {
+                let Some((index, link)) = queue.lock().unwrap_or_else(PoisonError::into_inner).next() else {
+                    break checked;
+                };
+                if !checked.is_empty() {
+                    let delay = backoff.lock().unwrap_or_else(PoisonError::into_inner).next_delay();
+                    if !delay.is_zero() {
+                        tokio::time::sleep(delay).await;
+                    }
+                }
+                let url = self.resolve_href(target_url, &link.href);
+                let reason = self.head_request(page, &url).await;
+                checked.push((index, link, url, reason));
+            }
+        });
+        let mut checked: Vec<_> = futures_util::future::join_all(tab_runs).await.into_iter().flatten().collect();
+        for page in pages {
+            page.close().await.ok();
+        }
+        checked.sort_by_key(|(index, ..)| *index);
+
+        let mut kept = Vec::with_capacity(checked.len());
+        let mut pruned = Vec::new();
+        for (_, link, url, reason) in checked {
+            match reason {
+                Some(reason) => {
+                    warn!("Dropping {} ({}): {}", link.title, url, reason);
+                    pruned.push(PrunedLink { url, title: link.title, reason });
+                }
+                None => kept.push(link),
+            }
+        }
+
+        if pruned.is_empty() {
+            info!("All links are reachable");
+        } else {
+            warn!("Pruned {} of {} links that are dead or leave the site",
+                  pruned.len().to_string().yellow(), pruned.len() + kept.len());
+        }
+        Ok((kept, pruned))
+    }
+
+    /// Why `url` should be dropped, or `None` when it's fine to crawl
+    async fn head_request(&self, page: &chromiumoxide::Page, url: &str) -> Option<String> {
+        let js_code = format!(r#"
+            (async () => {{
+                const url = {};
+                try {{
+                    const response = await fetch(url, {{ method: 'HEAD', credentials: 'include', redirect: 'follow' }});
+                    return {{ status: response.status, url: response.url }};
+                }} catch (e) {{
+                    // Tell a redirect the browser won't follow across origins
+                    // apart from a network error, which fails this way too
+                    try {{
+                        const response = await fetch(url, {{ method: 'HEAD', credentials: 'include', redirect: 'manual' }});
+                        return response.type === 'opaqueredirect' ? {{ off_site_redirect: true }} : {{ error: String(e) }};
+                    }} catch (e) {{
+                        return {{ error: String(e) }};
+                    }}
+                }}
+            }})()
+        "#, serde_json::to_string(url).unwrap_or_default());
+
+        #[derive(Deserialize)]
+        struct HeadResult {
+            status: Option<u16>,
+            url: Option<String>,
+            #[serde(default)]
+            off_site_redirect: bool,
+            error: Option<String>,
+        }
+
+        let result = match page.evaluate(js_code).await.map(|result| result.into_value::<HeadResult>()) {
+            Ok(Ok(result)) => result,
+            Ok(Err(e)) => {
+                debug!("Unexpected HEAD result for {}: {}", url, e);
+                return None;
+            }
+            Err(e) => {
+                debug!("HEAD request for {} failed: {}", url, e);
+                return None;
+            }
+        };
+
+        if result.off_site_redirect {
+            return Some("redirects off the site".to_string());
+        }
+        if let Some(error) = result.error {
+            warn!("Couldn't check {} ({}), keeping it for the crawl", url, error);
+            return None;
+        }
+        let origin = |href: &str| Url::parse(href).ok().map(|url| url.origin());
+        match (result.status, result.url) {
+            (Some(status @ (404 | 410)), _) => Some(format!("HTTP {}", status)),
+            (_, Some(landed)) if origin(&landed) != origin(url) => Some(format!("redirects to {}", landed)),
+            _ => None,
+        }
+    }
+
+    /// The page's `window.location` once loaded, catching JavaScript redirects
+    /// that `goto` doesn't report
+    async fn landed_url(&self, page: &chromiumoxide::Page, target_url: &str) -> String {
+        let landed = page
+            .evaluate("window.location.href")
+            .await
+            .ok()
+            .and_then(|result| result.into_value::<String>().ok())
+            .and_then(|href| Url::parse(&href).ok())
+            .filter(|url| matches!(url.scheme(), "http" | "https"));
+
+        match (landed, Url::parse(target_url)) {
+            (Some(landed), Ok(target)) if landed.host_str() != target.host_str() || landed.path() != target.path() => {
+                info!("{} redirected to {}, resolving links against it", target_url, landed.as_str().green());
+                landed.to_string()
+            }
+            _ => target_url.to_string(),
+        }
+    }
+
+    /// Reads the ordered page list given with `links_file`.
+    ///
+    /// Same-site URLs are reduced to their path so they name their PDFs like
+    /// discovered links do; other entries are kept as they are.
+    async fn read_links_file(&self, path: &Path, target_url: &str) -> Result<Vec<NavLink>> {
+        let data = fs::read_to_string(path)
+            .await
+            .map_err(|e| anyhow!("Failed to read links file {}: {}", path.display(), e))?;
+
+        let base = Url::parse(target_url).ok();
+        let mut seen = HashSet::new();
+        let mut links = Vec::new();
+
+        for line in data.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let href = match (Url::parse(line), &base) {
+                (Ok(url), Some(base)) if url.origin() == base.origin() => path_and_query(&url),
+                _ => line.to_string(),
+            };
+
+            if seen.insert(href.clone()) {
+                links.push(NavLink { title: href.clone(), href, depth: 0 });
+            }
+        }
+
+        if links.is_empty() {
+            return Err(anyhow!("Links file {} does not list any URLs", path.display()));
+        }
+
+        info!("Using {} links from {}", links.len(), path.display());
+        Ok(links)
+    }
+
+    /// The `only_urls` list as links, in the given order. Each entry has to
+    /// resolve to a page on the same origin as `target_url`, or under the
+    /// directory of a local site.
+    fn only_urls_links(&self, target_url: &str) -> Result<Vec<NavLink>> {
+        let base = Url::parse(target_url)
+            .map_err(|e| anyhow!("Invalid URL {}: {}", target_url, e))?;
+        let mut seen = HashSet::new();
+        let mut links = Vec::new();
+
+        let local_root = local_site::root(target_url);
+
+        for entry in self.options.only_urls.iter().map(|entry| entry.trim()).filter(|entry| !entry.is_empty()) {
+            if let Some(root) = &local_root {
+                let href = local_site::nav_href(root, &base, entry)
+                    .ok_or_else(|| anyhow!("'{}' in --only-urls is not a page under {}", entry, root.path()))?;
+                if seen.insert(href.clone()) {
+                    links.push(NavLink { title: href.clone(), href, depth: 0 });
+                }
+                continue;
+            }
+
+            let url = base
+                .join(entry)
+                .map_err(|e| anyhow!("Invalid URL '{}' in --only-urls: {}", entry, e))?;
+            if url.origin() != base.origin() {
+                return Err(anyhow!("'{}' in --only-urls is not on {}", entry, base.origin().ascii_serialization()));
+            }
+
+            let href = path_and_query(&url);
+            if seen.insert(href.clone()) {
+                links.push(NavLink { title: href.clone(), href, depth: 0 });
+            }
+        }
+
+        if links.is_empty() {
+            return Err(anyhow!("--only-urls does not list any URLs"));
+        }
+
+        info!("Using {} links from --only-urls", links.len());
+        Ok(links)
+    }
+
+    /// Hash of the options that decide what a page's PDF looks like. Paths and
+    /// crawl settings are left out, so an output directory copied to another
+    /// machine hashes the same
+    fn options_hash(&self) -> String {
+        let options = &self.options;
+        let printed = format!(
+            "{:?}",
+            (
+                &self.pdf_options,
+                (options.viewport.width, options.viewport.height, options.device_scale_factor),
+                (options.hires_images, options.fit_width, options.viewport_only, options.single_long_page),
+                (options.reduce_motion, options.expand_tabs, options.light_code, options.dedupe_page_headers),
+                (&options.inject_css, &options.remove_text, options.strip_edit_links, options.strip_nav, options.expand_external_links),
+                (options.no_js, options.accessible, options.page_outlines, TitleSource::chain(&options.merge.title_sources)),
+            )
+        );
+        content_hash(&printed)
+    }
+
+    /// With `skip_existing`, warns when the manifest an earlier run left in the
+    /// output directory was written for another site or other print options,
+    /// since its pages are reused as they are
+    async fn check_resumed_manifest(&self, target_url: &str) {
+        let manifest_path = self.out_dir().join(MANIFEST_FILE);
+        if !fs::try_exists(&manifest_path).await.unwrap_or(false) {
+            return;
+        }
+        let manifest = match Manifest::load(&manifest_path).await {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                warn!("Not checking the pages being reused: {}", e);
+                return;
+            }
+        };
+
+        if manifest.base_url != target_url {
+            warn!(
+                "Reusing pages captured from {}, not {}; pass another output directory to start over",
+                manifest.base_url, target_url
+            );
+        }
+        match manifest.options_hash {
+            Some(hash) if hash != self.options_hash() => {
+                warn!("Reusing pages captured with different print options, they may not match the new pages")
+            }
+            Some(_) => debug!("Resuming the export in {}", manifest_path.display()),
+            None => debug!("{} predates option hashes, not checking the reused pages' options", manifest_path.display()),
+        }
+    }
+
+    /// Records the pages of this run next to the combined PDF so later runs can diff against it
+    async fn write_manifest(
+        &self,
+        target_url: &str,
+        combined_path: Option<&Path>,
+        pages: Vec<(ManifestEntry, Option<PathBuf>)>,
+    ) -> Result<()> {
+        let out_dir = self.out_dir();
+        let relative = |path: &Path| {
+            path.strip_prefix(&out_dir)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .replace('\\', "/")
+        };
+
+        let mut entries = Vec::with_capacity(pages.len());
+        for (mut entry, path) in pages {
+            if let Some(path) = path {
+                if fs::try_exists(&path).await.unwrap_or(false) {
+                    entry.file = Some(relative(&path));
+                }
+            }
+            entries.push(entry);
+        }
+
+        let manifest = Manifest {
+            base_url: target_url.to_string(),
+            options_hash: Some(self.options_hash()),
+            combined: combined_path.map(relative),
+            pages: entries,
+        };
+
+        let manifest_path = out_dir.join(MANIFEST_FILE);
+        manifest.save(&manifest_path).await?;
+        debug!("Manifest written to {}", manifest_path.display());
+
+        Ok(())
+    }
+
+    fn resolve_href(&self, target_url: &str, href: &str) -> String {
+        if let Some(root) = local_site::root(target_url).filter(|_| href.starts_with('/')) {
+            if let Some(url) = local_site::resolve(&root, href) {
+                return url;
+            }
+        }
+        Url::parse(target_url)
+            .and_then(|base| base.join(href))
+            .map(|url| url.to_string())
+            .unwrap_or_else(|_| href.to_string())
+    }
+
+    async fn create_cover_page(&self, browser: &Browser, target_url: &str) -> Result<PathBuf> {
+        info!("Creating cover page with website logo...");
+
+        let page = browser
+            .new_page("about:blank")
+            .await
+            .map_err(|e| anyhow!("Failed to create cover page: {}", e))?;
+        self.disable_scripts(&page).await?;
+
+        // Go to the main site to extract logo and title
+        self.goto(&page, target_url).await?;
+        self.wait_for_navigation(&page, target_url).await?;
+
+        self.wait_for_content(&page, CONTENT_SELECTORS, Duration::from_millis(2000)).await;
+
+        // Extract site title and logo
+        let site_info = page.evaluate(r#"
+            (() => {
+                // Try to find logo
+                const logoSelectors = [
+                    'img[alt*="logo" i]',
+                    'img[src*="logo" i]',
+                    'img[class*="logo" i]',
+                    '.navbar__logo img',
+                    '.navbar-brand img',
+                    'header img',
+                    '.header img'
+                ];
+                
+                let logoSrc = null;
+                for (const selector of logoSelectors) {
+                    const img = document.querySelector(selector);
+                    if (img && img.src) {
+                        logoSrc = img.src;
+                        break;
+                    }
+                }
+                
+                // Get site title
+                const title = document.title || 
+                             document.querySelector('h1')?.textContent || 
+                             document.querySelector('.navbar-brand')?.textContent ||
+                             'Documentation';
+                
+                return {
+                    title: title.trim(),
+                    logo: logoSrc,
+                    url: window.location.href
+                };
+            })()
+        "#).await.map_err(|e| anyhow!("Failed to extract site info: {}", e))?;
+
+        let site_data: serde_json::Value = site_info.into_value()
+            .map_err(|e| anyhow!("Failed to parse site info: {}", e))?;
+
+        let title = site_data["title"].as_str().unwrap_or("Documentation");
+        let logo_url = site_data["logo"].as_str();
+        let site_url = site_data["url"].as_str().unwrap_or(target_url);
+
+        // Create HTML cover page
+        let logo_html = if let Some(logo) = logo_url {
+            format!(r#"<img src="{}" alt="Logo" style="max-width: 300px; max-height: 200px; margin-bottom: 30px;">"#, logo)
+        } else {
+            String::new()
+        };
+
+        let cover_html = format!(r#"
+            <!DOCTYPE html>
+            <html>
+            <head>
+                <meta charset="UTF-8">
+                <title>Cover Page</title>
+                <style>
+                    body {{
+                        font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, sans-serif;
+                        display: flex;
+                        flex-direction: column;
+                        align-items: center;
+                        justify-content: center;
+                        height: 100vh;
+                        margin: 0;
+                        background: linear-gradient(135deg, #667eea 0%, #764ba2 100%);
+                        color: white;
+                        text-align: center;
+                    }}
+                    .container {{
+                        background: rgba(255, 255, 255, 0.1);
+                        backdrop-filter: blur(10px);
+                        border-radius: 20px;
+                        padding: 60px;
+                        box-shadow: 0 8px 32px rgba(31, 38, 135, 0.37);
+                        border: 1px solid rgba(255, 255, 255, 0.18);
+                    }}
+                    h1 {{
+                        font-size: 3em;
+                        margin: 20px 0;
+                        font-weight: 300;
+                        text-shadow: 2px 2px 4px rgba(0,0,0,0.3);
+                    }}
+                    .subtitle {{
+                        font-size: 1.2em;
+                        opacity: 0.9;
+                        margin-bottom: 20px;
+                    }}
+                    .url {{
+                        font-size: 0.9em;
+                        opacity: 0.7;
+                        font-family: monospace;
+                        background: rgba(0,0,0,0.2);
+                        padding: 10px 20px;
+                        border-radius: 10px;
+                        margin-top: 30px;
+                    }}
+                    .generated {{
+                        position: absolute;
+                        bottom: 30px;
+                        right: 30px;
+                        font-size: 0.8em;
+                        opacity: 0.6;
+                    }}
+                </style>
+            </head>
+            <body>
+                <div class="container">
+                    {}
+                    <h1>{}</h1>
+                    <div class="subtitle">Documentation Export</div>
+                    <div class="url">{}</div>
+                </div>
+                <div class="generated">Generated with book2pdf</div>
+            </body>
+            </html>
+        "#, logo_html, title, site_url);
+
+        page.close().await.ok();
+
+        // Generate PDF
+        let cover_path = self.out_dir().join("pages").join(COVER_FILE);
+
+        self.convert_html_to_pdf(browser, &cover_html, &cover_path)
+            .await
+            .map_err(|e| anyhow!("Failed to create cover PDF: {}", e))?;
+
+        info!("Cover page created: {}", cover_path.display().to_string().blue());
+        Ok(cover_path)
+    }
+
+    /// Prints a standalone HTML document to `path` with the crawl's print settings.
+    ///
+    /// The document is loaded into a blank tab, so it can't use the site's
+    /// cookies or relative URLs; images need absolute or `data:` URLs.
+    async fn convert_html_to_pdf(&self, browser: &Browser, html: &str, path: &Path) -> Result<()> {
+        let page = browser
+            .new_page("about:blank")
+            .await
+            .map_err(|e| anyhow!("Failed to create page: {}", e))?;
+
+        page.set_content(html)
+            .await
+            .map_err(|e| anyhow!("Failed to set page content: {}", e))?;
+
+        self.wait_for_images(&page).await;
+
+        let label = path.display().to_string();
+        let pdf_data = self.print(&page, &label, self.print_params()).await?;
+        page.close().await.ok();
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .await
+                .map_err(|e| anyhow!("Failed to create directory: {}", e))?;
+        }
+
+        write_atomic(path, pdf_data)
+            .await
+            .map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))
+    }
+
+    /// Contents of the `front_matter` Markdown files, named after their file stem
+    async fn read_front_matter(&self) -> Result<Vec<(String, String)>> {
+        let mut pages = Vec::with_capacity(self.options.front_matter.len());
+        for path in &self.options.front_matter {
+            let markdown = fs::read_to_string(path)
+                .await
+                .map_err(|e| anyhow!("Failed to read front matter file {}: {}", path.display(), e))?;
+            let name = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "front-matter".to_string());
+            pages.push((name, markdown));
+        }
+        Ok(pages)
+    }
+
+    /// Renders the site's own navigation tree as a front matter page.
+    ///
+    /// Everything but the nav element and its ancestors is hidden, and the
+    /// sidebar's sticky/scrolling layout is undone so the whole tree is printed.
+    /// Returns `None` when the page has no recognizable nav.
+    async fn capture_nav(&self, browser: &Browser, url: &str) -> Result<Option<PathBuf>> {
+        info!("Capturing site navigation from {}", url.green());
+
+        let page = browser
+            .new_page("about:blank")
+            .await
+            .map_err(|e| anyhow!("Failed to create nav page: {}", e))?;
+        self.disable_scripts(&page).await?;
+
+        self.goto(&page, url).await?;
+        self.wait_for_navigation(&page, url).await?;
+
+        self.wait_for_content(&page, CONTENT_SELECTORS, Duration::from_millis(2000)).await;
+
+        self.expand_menu_links(&page).await?;
+
+        let js_code = r#"
+            (() => {
+                const selectors = [
+                    '.theme-doc-sidebar-menu',                // Docusaurus
+                    'aside nav',
+                    'aside',
+                    'nav[aria-label="Table of contents"]',    // GitBook
+                    'nav',
+                ];
+
+                // The first candidate that actually lists pages
+                let nav = null;
+                for (const selector of selectors) {
+                    nav = [...document.querySelectorAll(selector)]
+                        .find((el) => el.querySelectorAll('a[href]').length >= 2);
+                    if (nav) break;
+                }
+                if (!nav) {
+                    return false;
+                }
+
+                const unclip = (el) => {
+                    el.style.setProperty('position', 'static', 'important');
+                    el.style.setProperty('height', 'auto', 'important');
+                    el.style.setProperty('max-height', 'none', 'important');
+                    el.style.setProperty('overflow', 'visible', 'important');
+                };
+
+                for (const el of nav.querySelectorAll('*:not(svg):not(svg *)')) {
+                    unclip(el);
+                }
+                for (let el = nav; el && el !== document.body; el = el.parentElement) {
+                    unclip(el);
+                    for (const sibling of el.parentElement.children) {
+                        if (sibling !== el) {
+                            sibling.style.setProperty('display', 'none', 'important');
+                        }
+                    }
+                }
+                return true;
+            })()
+        "#;
+
+        let found = page
+            .evaluate(js_code)
+            .await
+            .map_err(|e| anyhow!("Failed to isolate navigation: {}", e))?
+            .into_value::<bool>()
+            .unwrap_or(false);
+
+        if !found {
+            page.close().await.ok();
+            return Ok(None);
+        }
+
+        let pdf_data = self.print(&page, url, self.print_params()).await?;
+        page.close().await.ok();
+
+        // Sorts between the cover and the first content page
+        let nav_path = self.out_dir().join("pages").join(NAV_FILE);
+        write_atomic(&nav_path, pdf_data)
+            .await
+            .map_err(|e| anyhow!("Failed to write nav PDF: {}", e))?;
+
+        info!("Navigation page created: {}", nav_path.display().to_string().blue());
+        Ok(Some(nav_path))
+    }
+
+    async fn expand_menu_links(&self, page: &chromiumoxide::Page) -> Result<()> {
+        if self.options.no_js {
+            debug!("Scripts are disabled, leaving collapsed navigation as is");
+            return Ok(());
+        }
+
+        // Expanding a category can reveal nested expanders, so keep clicking
+        // until a round finds nothing new (or the round limit is reached)
+        let js_code = format!(r#"
+            (async () => {{
+                const selectors = [
+                    // For old GitBook format - expand TOC menu items
+                    'a[data-rnwrdesktop-fnigne="true"] > div[tabindex="0"]',
+                    // For new GitBook format - look for expandable navigation items
+                    'button[aria-expanded="false"]',
+                    'button[data-state="closed"]',
+                    '[role="button"][aria-expanded="false"]',
+                    // For Docusaurus - expand collapsible sidebar categories
+                    '.menu__list-item--collapsed > .menu__link',
+                    '.menu__link--sublist[aria-expanded="false"]',
+                    'button.menu__link--sublist',
+                    '.theme-doc-sidebar-item-category button[aria-expanded="false"]',
+                    '.menu__caret', // Docusaurus v2 caret
+                    '[class*="collapsible"] button[aria-expanded="false"]',
+                    // Also try to click on category headers directly
+                    '.menu__list-item--collapsed'
+                ].join(', ');
+
+                // Remember what was clicked so toggles aren't collapsed again
+                const clicked = new WeakSet();
+                let rounds = 0;
+
+                while (rounds < {max_rounds}) {{
+                    const pending = [...document.querySelectorAll(selectors)]
+                        .filter(element => !clicked.has(element));
+
+                    if (pending.length === 0) {{
+                        break;
+                    }}
+
+                    for (let element of pending) {{
+                        clicked.add(element);
+                        element.click();
+                    }}
+                    rounds++;
+
+                    // Wait a bit for animations and lazily rendered children
+                    await new Promise(r => setTimeout(r, 1000));
+                }}
+
+                return rounds;
+            }})()
+        "#, max_rounds = MAX_EXPAND_ROUNDS);
+
+        let rounds = page.evaluate(js_code)
+            .await
+            .map_err(|e| anyhow!("Failed to expand menu links: {}", e))?
+            .into_value::<u32>()
+            .unwrap_or(0);
+
+        if rounds >= MAX_EXPAND_ROUNDS {
+            warn!("Navigation still had collapsed items after {} expansion rounds", rounds);
+        } else {
+            debug!("Navigation fully expanded after {} rounds", rounds);
+        }
+
+        Ok(())
+    }
+
+    async fn prepare_page(&self, page: &chromiumoxide::Page) -> Result<()> {
+        let js_code = r#"
+            // Expand all expandable sections
+            const sectionsToExpand = document
+                .querySelectorAll('div[aria-controls^="expandable-body-"]');
+
+            for (let section of sectionsToExpand) {
+                section.click();
+            }
+
+            // Remove redundant/interactive elements
+            const itemSelectorsToRemove = [
+                'header + div[data-rnwrdesktop-hidden="true"]',
+                'div[aria-label^="Search"]',
+                'div[aria-label="Page actions"]',
+            ];
+            const itemsToRemove = document
+                .querySelectorAll(itemSelectorsToRemove.join(', '));
+
+            for (let item of itemsToRemove) {
+                item.remove();
+            }
+
+            // Turn relative timestamps into absolute ones
+            const lastModifiedEl = document
+                .querySelector('div[dir="auto"] > span[aria-label]');
+
+            if (lastModifiedEl) {
+                lastModifiedEl.innerText = lastModifiedEl.getAttribute('aria-label');
+            }
+        "#;
+
+        page.evaluate(js_code)
+            .await
+            .map_err(|e| anyhow!("Failed to prepare page: {}", e))?;
+
+        if !self.options.remove_text.is_empty() {
+            self.remove_elements_by_text(page).await?;
+        }
+
+        if self.options.strip_edit_links || self.options.strip_nav {
+            self.strip_page_chrome(page).await?;
+        }
+
+        if self.options.expand_external_links {
+            self.expand_external_links(page).await?;
+        }
+
+        if self.options.reduce_motion {
+            self.settle_animations(page).await?;
+        }
+
+        if self.options.expand_tabs {
+            self.expand_tabs(page).await?;
+        }
+
+        if let Some(max_width) = self.options.content_max_width {
+            self.constrain_content_width(page, max_width).await?;
+        }
+
+        if self.options.light_code || !self.options.inject_css.is_empty() {
+            self.inject_styles(page).await?;
+        }
+
+        // Waits on image load events, which never fire with scripts disabled
+        if self.options.hires_images && !self.options.no_js {
+            self.use_largest_image_sources(page).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Removes the `strip_edit_links` and `strip_nav` elements from the page
+    async fn strip_page_chrome(&self, page: &chromiumoxide::Page) -> Result<()> {
+        let mut selectors = Vec::new();
+        if self.options.strip_edit_links {
+            selectors.extend_from_slice(EDIT_LINK_SELECTORS);
+        }
+        if self.options.strip_nav {
+            selectors.extend_from_slice(NAV_SELECTORS);
+        }
+
+        let js_code = format!(r#"
+            (() => {{
+                let removed = 0;
+                for (const selector of {}) {{
+                    for (const element of document.querySelectorAll(selector)) {{
+                        element.remove();
+                        removed++;
+                    }}
+                }}
+                return removed;
+            }})()
+        "#, serde_json::to_string(&selectors)?);
+
+        let removed = page
+            .evaluate(js_code)
+            .await
+            .map_err(|e| anyhow!("Failed to strip page chrome: {}", e))?
+            .into_value::<u64>()
+            .unwrap_or(0);
+        debug!("Stripped {} edit link and navigation elements", removed);
+        Ok(())
+    }
+
+    /// Appends ` (https://...)` after each link leaving the site.
+    ///
+    /// Links without text (icons) and links whose text already is their URL
+    /// are left alone, as are links inside code blocks.
+    async fn expand_external_links(&self, page: &chromiumoxide::Page) -> Result<()> {
+        let js_code = r#"
+            (() => {
+                const bare = (url) => url.replace(/^https?:\/\//, '').replace(/\/$/, '');
+                let expanded = 0;
+                for (const link of document.querySelectorAll('a[href]')) {
+                    const url = link.href;
+                    if (!/^https?:/.test(url) || new URL(url).host === location.host || link.closest('pre')) {
+                        continue;
+                    }
+                    const text = (link.textContent || '').trim();
+                    if (!text || bare(text) === bare(url)) {
+                        continue;
+                    }
+                    const note = document.createElement('span');
+                    note.className = 'book2pdf-external-url';
+                    note.textContent = ` (${url})`;
+                    note.style.setProperty('overflow-wrap', 'anywhere');
+                    link.after(note);
+                    expanded++;
+                }
+                return expanded;
+            })()
+        "#;
+
+        let expanded = page
+            .evaluate(js_code)
+            .await
+            .map_err(|e| anyhow!("Failed to expand external links: {}", e))?
+            .into_value::<u64>()
+            .unwrap_or(0);
+        debug!("Printed the URLs of {} external links", expanded);
+        Ok(())
+    }
+
+    /// Puts animated elements in their final state before printing.
+    ///
+    /// Durations drop to zero rather than `animation: none`, which would leave
+    /// fade-ins at their invisible starting frame. Running animations and
+    /// transitions are finished; infinite ones (carousels) can't be and are stopped.
+    async fn settle_animations(&self, page: &chromiumoxide::Page) -> Result<()> {
+        let js_code = r#"
+            (() => {
+                const style = document.createElement('style');
+                style.textContent = `*, *::before, *::after {
+                    animation-duration: 0s !important;
+                    animation-delay: 0s !important;
+                    animation-iteration-count: 1 !important;
+                    transition-duration: 0s !important;
+                    transition-delay: 0s !important;
+                    scroll-behavior: auto !important;
+                }`;
+                document.head.appendChild(style);
+
+                for (const animation of document.getAnimations()) {
+                    try {
+                        animation.finish();
+                    } catch (e) {
+                        animation.cancel();
+                    }
+                }
+            })()
+        "#;
+
+        page.evaluate(js_code)
+            .await
+            .map_err(|e| anyhow!("Failed to disable animations: {}", e))?;
+        Ok(())
+    }
+
+    /// Stacks the panels of every tab widget so none is left out of the PDF.
+    ///
+    /// ARIA tab lists (Docusaurus, GitBook, ...) pair tabs with panels through
+    /// `aria-controls`, or by position when that's missing; MkDocs Material's
+    /// `.tabbed-set` is driven by radio buttons and CSS and pairs labels with
+    /// blocks by position. Each panel is shown under its tab's title and the
+    /// tab strip itself is hidden.
+    async fn expand_tabs(&self, page: &chromiumoxide::Page) -> Result<()> {
+        let js_code = r#"
+            (() => {
+                const style = document.createElement('style');
+                style.textContent = `[data-book2pdf-tabs] {
+                    display: none !important;
+                }
+                [data-book2pdf-tab-panel] {
+                    display: block !important;
+                }
+                .book2pdf-tab-title {
+                    font-weight: 600;
+                    margin: 1em 0 0.5em;
+                    padding-bottom: 0.25em;
+                    border-bottom: 1px solid currentColor;
+                }`;
+                document.head.appendChild(style);
+
+                // Shows each panel under its title, returns whether there was any
+                const stack = (pairs) => {
+                    pairs = pairs.filter(([title, panel]) => panel);
+                    for (const [title, panel] of pairs) {
+                        panel.hidden = false;
+                        panel.setAttribute('data-book2pdf-tab-panel', '');
+                        const heading = document.createElement('div');
+                        heading.className = 'book2pdf-tab-title';
+                        heading.textContent = title;
+                        panel.before(heading);
+                    }
+                    return pairs.length > 0;
+                };
+                const title = (el) => (el.innerText || el.textContent || '').trim();
+
+                let groups = 0;
+                for (const strip of document.querySelectorAll('[role="tablist"], .tabs')) {
+                    // A .tabs wrapper around the whole widget isn't the strip
+                    if (strip.querySelector('[role="tablist"], [role="tabpanel"]')) {
+                        continue;
+                    }
+                    let tabs = [...strip.querySelectorAll('[role="tab"]')];
+                    if (tabs.length === 0) {
+                        tabs = [...strip.children];
+                    }
+                    // Panels of tab widgets nested inside this one's panels belong to those
+                    const container = strip.parentElement;
+                    const siblings = [...container.querySelectorAll('[role="tabpanel"]')].filter((panel) => {
+                        const outer = panel.parentElement.closest('[role="tabpanel"]');
+                        return !outer || !container.contains(outer);
+                    });
+                    const pairs = tabs.map((tab, i) => {
+                        const id = tab.getAttribute('aria-controls');
+                        return [title(tab), (id && document.getElementById(id)) || siblings[i]];
+                    });
+                    if (stack(pairs)) {
+                        strip.setAttribute('data-book2pdf-tabs', '');
+                        groups += 1;
                     }
                 }
-            }
+
+                for (const set of document.querySelectorAll('.tabbed-set')) {
+                    const labels = [...set.querySelectorAll(':scope > .tabbed-labels > label, :scope > label')];
+                    const blocks = [...set.querySelectorAll(':scope > .tabbed-content > .tabbed-block')];
+                    // Older versions give each tab its own content element right after its label
+                    const panels = blocks.length > 0
+                        ? blocks
+                        : [...set.querySelectorAll(':scope > .tabbed-content')];
+                    if (!stack(labels.map((label, i) => [title(label), panels[i]]))) {
+                        continue;
+                    }
+                    for (const el of set.querySelectorAll(':scope > .tabbed-labels, :scope > label, :scope > input')) {
+                        el.setAttribute('data-book2pdf-tabs', '');
+                    }
+                    set.querySelector(':scope > .tabbed-content')?.setAttribute('data-book2pdf-tab-panel', '');
+                    groups += 1;
+                }
+
+                return groups;
+            })()
+        "#;
+
+        let groups = page
+            .evaluate(js_code)
+            .await
+            .map_err(|e| anyhow!("Failed to expand tabs: {}", e))?
+            .into_value::<u32>()
+            .unwrap_or(0);
+        if groups > 0 {
+            debug!("Expanded {} tab groups", groups);
         }
+        Ok(())
+    }
 
+    /// Fails the page when its main document took more HTTP redirects than
+    /// `max_redirects`. Chrome gives up on a redirect loop by itself after 20.
+    ///
+    /// Only redirects within the site's origin are counted; the browser
+    /// reports none for ones that cross origins.
+    async fn check_redirects(&self, page: &chromiumoxide::Page, url: &Url) -> Result<()> {
+        let redirects = page
+            .evaluate("performance.getEntriesByType('navigation')[0]?.redirectCount ?? 0")
+            .await
+            .ok()
+            .and_then(|result| result.into_value::<u32>().ok())
+            .unwrap_or(0);
+        if redirects > self.options.max_redirects {
+            return Err(anyhow!("{} redirected {} times, more than --max-redirects {}",
+                               url, redirects, self.options.max_redirects));
+        }
         Ok(())
     }
 
-    async fn create_cover_page(&self, browser: &Browser, target_url: &str) -> Result<PathBuf> {
-        info!("Creating cover page with website logo...");
+    /// Records the URL the page ended up at for `requested`, and returns the
+    /// URL requested for the page that got there first, if another one did.
+    async fn claim_landed_url(&self, page: &chromiumoxide::Page, requested: &Url) -> Option<String> {
+        let mut landed = page
+            .evaluate("window.location.href")
+            .await
+            .ok()
+            .and_then(|result| result.into_value::<String>().ok())
+            .and_then(|href| Url::parse(&href).ok())
+            .unwrap_or_else(|| requested.clone());
+        landed.set_fragment(None);
+        if landed != *requested {
+            debug!("Requested {}, ended up at {}", requested, landed);
+        }
 
-        let page = browser
-            .new_page("about:blank")
+        let mut landed_urls = self.landed_urls.lock().unwrap_or_else(PoisonError::into_inner);
+        match landed_urls.get(landed.as_str()) {
+            // A retry of the same link lands where its first attempt did
+            Some(first) if first != requested.as_str() => Some(first.clone()),
+            Some(_) => None,
+            None => {
+                landed_urls.insert(landed.to_string(), requested.to_string());
+                None
+            }
+        }
+    }
+
+    /// Drops the claims `requested` made with `claim_landed_url`, once it has
+    /// failed for good, so a later link landing on the same page is captured
+    fn release_landed_urls(&self, requested: &str) {
+        let Ok(requested) = Url::parse(requested) else {
+            return;
+        };
+        self.landed_urls
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|_, claimed_by| claimed_by != requested.as_str());
+    }
+
+    /// Sets `document.title` to the first title the merge's `title_sources`
+    /// find on the page, which Chrome prints as the PDF's title and the
+    /// merger turns into the page's bookmark
+    async fn set_page_title(&self, page: &chromiumoxide::Page, url: &str, pdf_path: &Path) -> Result<()> {
+        let js_code = r#"
+            (() => {
+                const text = (element) => element ? element.textContent.replace(/\s+/g, ' ').trim() : '';
+                const crumb = [
+                    'nav[aria-label*="breadcrumb" i] li:last-child',
+                    '.breadcrumbs__item:last-child',
+                    '[class*="breadcrumb"] li:last-child',
+                ].map((selector) => text(document.querySelector(selector))).find((crumb) => crumb);
+                return {
+                    h1: text(document.querySelector('h1')),
+                    title: document.title.trim(),
+                    breadcrumb: crumb || '',
+                };
+            })()
+        "#;
+
+        let found = page
+            .evaluate(js_code)
             .await
-            .map_err(|e| anyhow!("Failed to create cover page: {}", e))?;
+            .map_err(|e| anyhow!("Failed to read the page's titles: {}", e))?
+            .into_value::<HashMap<String, String>>()
+            .unwrap_or_default();
+        let slug = pdf_path
+            .file_name()
+            .map(|name| filename_title(&name.to_string_lossy()))
+            .unwrap_or_default();
 
-        // Go to the main site to extract logo and title
-        page.goto(target_url)
+        let title = TitleSource::chain(&self.options.merge.title_sources)
+            .iter()
+            .map(|source| match source {
+                TitleSource::Slug => slug.as_str(),
+                _ => found.get(&source.to_string()).map_or("", String::as_str),
+            })
+            .find(|title| !title.is_empty());
+        let Some(title) = title else {
+            return Ok(());
+        };
+
+        page.evaluate(format!("document.title = {}", serde_json::to_string(title)?))
             .await
-            .map_err(|e| anyhow!("Failed to navigate to {}: {}", target_url, e))?;
+            .map_err(|e| anyhow!("Failed to set the page title: {}", e))?;
+        debug!("Titled {} \"{}\"", url, title);
+        Ok(())
+    }
 
-        page.wait_for_navigation()
+    /// Writes the page's current HTML to the `html` directory, named like the
+    /// page's PDF at `pdf_path`, to see what was printed
+    async fn dump_html(&self, page: &chromiumoxide::Page, pdf_path: &Path) -> Result<()> {
+        let html_dir = self.out_dir().join("html");
+        fs::create_dir_all(&html_dir)
             .await
-            .map_err(|e| anyhow!("Failed to wait for navigation: {}", e))?;
+            .map_err(|e| anyhow!("Failed to create HTML dump directory: {}", e))?;
 
-        tokio::time::sleep(Duration::from_millis(2000)).await;
+        let html = page
+            .content()
+            .await
+            .map_err(|e| anyhow!("Failed to read the page's HTML: {}", e))?;
+        let dump_path = match pdf_path.file_name() {
+            Some(name) => html_dir.join(name).with_extension("html"),
+            None => return Ok(()),
+        };
+        write_atomic(&dump_path, html)
+            .await
+            .map_err(|e| anyhow!("Failed to write HTML dump to {}: {}", dump_path.display(), e))?;
+        debug!("Page HTML saved to {}", dump_path.display());
+        Ok(())
+    }
 
-        // Extract site title and logo
-        let site_info = page.evaluate(r#"
+    /// Removes the page's top header (`header` or `[role="banner"]`, the one
+    /// highest up) when it is identical to the first page's in nav order.
+    ///
+    /// Headers are compared by their text and image sources, which stay the
+    /// same across pages while classes marking the current section may not.
+    /// The first page is captured before any other, so which headers go doesn't
+    /// depend on the order concurrent captures finish in. When it has no header
+    /// or isn't captured in this run, every page keeps its own.
+    async fn dedupe_page_header(&self, page: &chromiumoxide::Page, url: &str) -> Result<()> {
+        let js_code = r#"
             (() => {
-                // Try to find logo
-                const logoSelectors = [
-                    'img[alt*="logo" i]',
-                    'img[src*="logo" i]',
-                    'img[class*="logo" i]',
-                    '.navbar__logo img',
-                    '.navbar-brand img',
-                    'header img',
-                    '.header img'
-                ];
-                
-                let logoSrc = null;
-                for (const selector of logoSelectors) {
-                    const img = document.querySelector(selector);
-                    if (img && img.src) {
-                        logoSrc = img.src;
-                        break;
-                    }
+                const header = [...document.querySelectorAll('header, [role="banner"]')]
+                    .filter((element) => element.getBoundingClientRect().height > 0)
+                    .sort((a, b) => a.getBoundingClientRect().top - b.getBoundingClientRect().top)[0];
+                if (!header) {
+                    return null;
                 }
-                
-                // Get site title
-                const title = document.title || 
-                             document.querySelector('h1')?.textContent || 
-                             document.querySelector('.navbar-brand')?.textContent ||
-                             'Documentation';
-                
-                return {
-                    title: title.trim(),
-                    logo: logoSrc,
-                    url: window.location.href
-                };
+                header.setAttribute('data-book2pdf-page-header', '');
+                const images = [...header.querySelectorAll('img')].map((img) => img.src);
+                return [header.textContent.replace(/\s+/g, ' ').trim(), ...images].join('\n');
             })()
-        "#).await.map_err(|e| anyhow!("Failed to extract site info: {}", e))?;
-
-        let site_data: serde_json::Value = site_info.into_value()
-            .map_err(|e| anyhow!("Failed to parse site info: {}", e))?;
+        "#;
 
-        let title = site_data["title"].as_str().unwrap_or("Documentation");
-        let logo_url = site_data["logo"].as_str();
-        let site_url = site_data["url"].as_str().unwrap_or(target_url);
+        let header = page
+            .evaluate(js_code)
+            .await
+            .map_err(|e| anyhow!("Failed to find the page header: {}", e))?
+            .into_value::<Option<String>>()
+            .ok()
+            .flatten();
+        let Some(header) = header else {
+            return Ok(());
+        };
 
-        // Create HTML cover page
-        let logo_html = if let Some(logo) = logo_url {
-            format!(r#"<img src="{}" alt="Logo" style="max-width: 300px; max-height: 200px; margin-bottom: 30px;">"#, logo)
-        } else {
-            String::new()
+        let hash = content_hash(&header);
+        let repeated = {
+            let mut first = self.first_page_header.lock().unwrap_or_else(PoisonError::into_inner);
+            match first.as_mut() {
+                // The first page, or a retry of it, keeps its header and sets the one to match
+                Some((first_url, first_hash)) if first_url == url => {
+                    *first_hash = Some(hash);
+                    false
+                }
+                Some((_, first_hash)) => first_hash.as_deref() == Some(hash.as_str()),
+                None => false,
+            }
         };
 
-        let cover_html = format!(r#"
-            <!DOCTYPE html>
-            <html>
-            <head>
-                <meta charset="UTF-8">
-                <title>Cover Page</title>
-                <style>
-                    body {{
-                        font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, sans-serif;
-                        display: flex;
-                        flex-direction: column;
-                        align-items: center;
-                        justify-content: center;
-                        height: 100vh;
-                        margin: 0;
-                        background: linear-gradient(135deg, #667eea 0%, #764ba2 100%);
-                        color: white;
-                        text-align: center;
-                    }}
-                    .container {{
-                        background: rgba(255, 255, 255, 0.1);
-                        backdrop-filter: blur(10px);
-                        border-radius: 20px;
-                        padding: 60px;
-                        box-shadow: 0 8px 32px rgba(31, 38, 135, 0.37);
-                        border: 1px solid rgba(255, 255, 255, 0.18);
-                    }}
-                    h1 {{
-                        font-size: 3em;
-                        margin: 20px 0;
-                        font-weight: 300;
-                        text-shadow: 2px 2px 4px rgba(0,0,0,0.3);
-                    }}
-                    .subtitle {{
-                        font-size: 1.2em;
-                        opacity: 0.9;
-                        margin-bottom: 20px;
-                    }}
-                    .url {{
-                        font-size: 0.9em;
-                        opacity: 0.7;
-                        font-family: monospace;
-                        background: rgba(0,0,0,0.2);
-                        padding: 10px 20px;
-                        border-radius: 10px;
-                        margin-top: 30px;
+        if repeated {
+            page.evaluate("document.querySelector('[data-book2pdf-page-header]')?.remove()")
+                .await
+                .map_err(|e| anyhow!("Failed to remove the page header: {}", e))?;
+            debug!("Removed the repeated page header from {}", url);
+        }
+        Ok(())
+    }
+
+    /// Adds the `light_code` theme and the `inject_css` style sheets to the
+    /// page, in that order, so the injected CSS can override the theme
+    async fn inject_styles(&self, page: &chromiumoxide::Page) -> Result<()> {
+        let mut css = Vec::new();
+        if self.options.light_code {
+            css.push(LIGHT_CODE_CSS);
+        }
+        css.extend(self.options.inject_css.iter().map(String::as_str));
+
+        let js_code = format!(r#"
+            (() => {{
+                for (const css of {}) {{
+                    const style = document.createElement('style');
+                    style.textContent = css;
+                    document.head.appendChild(style);
+                }}
+            }})()
+        "#, serde_json::to_string(&css)?);
+
+        page.evaluate(js_code)
+            .await
+            .map_err(|e| anyhow!("Failed to inject CSS: {}", e))?;
+        Ok(())
+    }
+
+    /// Caps the width of the content column and centers it, for shorter lines.
+    ///
+    /// Targets the most specific container a theme is known to use, so sidebars
+    /// and headers that wrap around it keep their layout.
+    async fn constrain_content_width(&self, page: &chromiumoxide::Page, max_width: u32) -> Result<()> {
+        let js_code = format!(r#"
+            (() => {{
+                // In order of preference; one combined selector would pick the outermost match
+                const content = ['.theme-doc-markdown', '#content-area', 'main article', 'article', 'main']
+                    .map((selector) => document.querySelector(selector))
+                    .find(Boolean);
+                if (!content) {{
+                    return false;
+                }}
+                content.setAttribute('data-book2pdf-content', '');
+                const style = document.createElement('style');
+                style.textContent = `[data-book2pdf-content] {{
+                    max-width: {max_width}px !important;
+                    margin-left: auto !important;
+                    margin-right: auto !important;
+                    box-sizing: border-box !important;
+                }}`;
+                document.head.appendChild(style);
+                return true;
+            }})()
+        "#);
+
+        let constrained = page
+            .evaluate(js_code)
+            .await
+            .map_err(|e| anyhow!("Failed to constrain content width: {}", e))?
+            .into_value::<bool>()
+            .unwrap_or(false);
+        if !constrained {
+            debug!("No content container found, --content-max-width has no effect on this page");
+        }
+        Ok(())
+    }
+
+    /// Removes notices and banners that have no stable selector but consistent
+    /// wording: the innermost element containing a `remove_text` substring goes,
+    /// together with wrappers that hold nothing else.
+    async fn remove_elements_by_text(&self, page: &chromiumoxide::Page) -> Result<()> {
+        let patterns: Vec<String> = self.options.remove_text.iter().map(|p| p.to_lowercase()).collect();
+        let js_code = format!(r#"
+            (() => {{
+                const patterns = {};
+                const keep = new Set([document.documentElement, document.body,
+                    ...document.querySelectorAll('main, article, .theme-doc-markdown')]);
+                const text = (el) => (el.innerText || '').trim().toLowerCase();
+                const matches = (el) => patterns.some((pattern) => text(el).includes(pattern));
+
+                // textContent is cheap, innerText (visible text only) needs layout
+                const candidates = [...document.body.querySelectorAll('*')].filter((el) => {{
+                    const content = el.textContent.toLowerCase();
+                    return patterns.some((pattern) => content.includes(pattern)) && matches(el);
+                }});
+                const innermost = candidates.filter((el) => ![...el.children].some(matches));
+
+                let removed = 0;
+                for (let el of innermost) {{
+                    while (el.parentElement && !keep.has(el.parentElement)
+                        && text(el.parentElement) === text(el)) {{
+                        el = el.parentElement;
                     }}
-                    .generated {{
-                        position: absolute;
-                        bottom: 30px;
-                        right: 30px;
-                        font-size: 0.8em;
-                        opacity: 0.6;
+                    if (!keep.has(el) && el.isConnected) {{
+                        el.remove();
+                        removed++;
                     }}
-                </style>
-            </head>
-            <body>
-                <div class="container">
-                    {}
-                    <h1>{}</h1>
-                    <div class="subtitle">Documentation Export</div>
-                    <div class="url">{}</div>
-                </div>
-                <div class="generated">Generated with book2pdf</div>
-            </body>
-            </html>
-        "#, logo_html, title, site_url);
+                }}
+                return removed;
+            }})()
+        "#, serde_json::to_string(&patterns)?);
+
+        let removed = page.evaluate(js_code)
+            .await
+            .map_err(|e| anyhow!("Failed to remove elements by text: {}", e))?
+            .into_value::<u32>()
+            .unwrap_or(0);
+
+        debug!("Removed {} elements by text", removed);
+        Ok(())
+    }
+
+    /// Points every responsive image at its largest `srcset` candidate so the
+    /// emulated viewport doesn't decide which resolution ends up in the PDF.
+    async fn use_largest_image_sources(&self, page: &chromiumoxide::Page) -> Result<()> {
+        let js_code = r#"
+            (async () => {
+                // Returns the candidate with the largest width (w) or density (x) descriptor
+                const largestCandidate = (srcset) => {
+                    let best = null;
+                    for (const candidate of srcset.split(/,\s+/)) {
+                        const [url, descriptor = '1x'] = candidate.trim().split(/\s+/);
+                        const value = parseFloat(descriptor) || 1;
+                        // Width descriptors always outrank density descriptors
+                        const rank = descriptor.endsWith('w') ? value * 1000 : value;
+                        if (url && (!best || rank > best.rank)) {
+                            best = { url, rank };
+                        }
+                    }
+                    return best;
+                };
+
+                const loads = [];
+                let upgraded = 0;
+
+                for (const img of document.querySelectorAll('img[srcset], picture img')) {
+                    const picture = img.closest('picture');
+                    const srcsets = [img.getAttribute('srcset') || ''];
+                    if (picture) {
+                        for (const source of picture.querySelectorAll('source[srcset]')) {
+                            srcsets.push(source.getAttribute('srcset'));
+                        }
+                    }
+
+                    const best = srcsets
+                        .filter(Boolean)
+                        .map(largestCandidate)
+                        .filter(Boolean)
+                        .reduce((a, b) => (b.rank > a.rank ? b : a), { url: null, rank: 0 });
+
+                    if (!best.url) {
+                        continue;
+                    }
+
+                    // Drop the alternatives, otherwise the browser keeps choosing among them
+                    if (picture) {
+                        picture.querySelectorAll('source').forEach(source => source.remove());
+                    }
+                    img.removeAttribute('srcset');
+                    img.removeAttribute('sizes');
+                    img.setAttribute('loading', 'eager');
+                    img.src = new URL(best.url, document.baseURI).href;
+                    upgraded++;
+
+                    loads.push(new Promise(resolve => {
+                        if (img.complete) return resolve();
+                        img.addEventListener('load', resolve, { once: true });
+                        img.addEventListener('error', resolve, { once: true });
+                    }));
+                }
+
+                // Give the larger images a bounded amount of time to arrive
+                await Promise.race([
+                    Promise.all(loads),
+                    new Promise(r => setTimeout(r, 10000)),
+                ]);
+
+                return upgraded;
+            })()
+        "#;
+
+        let upgraded = page.evaluate(js_code)
+            .await
+            .map_err(|e| anyhow!("Failed to upgrade image sources: {}", e))?
+            .into_value::<u32>()
+            .unwrap_or(0);
+
+        debug!("Switched {} images to their largest source", upgraded);
+        Ok(())
+    }
+
+    async fn download_link_with_retry(
+        &self,
+        browser: &Browser,
+        target_url: &str,
+        href: &str,
+        index: usize,
+        previous_hash: Option<&str>,
+        state: &mut CrawlState,
+    ) -> Result<(PathBuf, PageCapture)> {
+        let mut page_report = PageReport {
+            url: self.resolve_href(target_url, href),
+            slug: self.href_to_slug(href),
+            http_status: None,
+            bytes: None,
+            page_count: None,
+            duration_ms: 0,
+            timings: None,
+            attempts: 0,
+            success: false,
+            skipped: None,
+            timed_out: false,
+            stalled_phase: None,
+            empty_pdf: false,
+            missing_alt: Vec::new(),
+            error: None,
+        };
+
+        let result = loop {
+            page_report.attempts += 1;
+            let started = Instant::now();
+            let result = self.download_link(browser, target_url, href, index, previous_hash).await;
+            page_report.duration_ms = started.elapsed().as_millis() as u64;
+
+            match result {
+                Ok((path, capture)) => {
+                    state.backoff().on_success();
+                    page_report.http_status = capture.http_status;
+                    page_report.timings = Some(capture.timings);
+                    page_report.timed_out = capture.stalled.is_some();
+                    page_report.stalled_phase = capture.stalled.map(|phase| phase.to_string());
+                    page_report.empty_pdf = false;
+                    page_report.missing_alt = capture.missing_alt.clone();
+                    if capture.unchanged {
+                        page_report.skipped = Some("unchanged since previous manifest".to_string());
+                    } else if let Some(first) = &capture.duplicate_of {
+                        page_report.skipped = Some(format!("redirects to the page captured for {}", first));
+                    } else if capture.fragment.is_none() {
+                        page_report.bytes = Some(capture.bytes);
+                        page_report.page_count = capture.page_count;
+                    }
+                    page_report.success = true;
+                    page_report.error = None;
+                    break Ok((path, capture));
+                }
+                Err(e) => {
+                    state.backoff().on_failure();
+                    if let Some(http_error) = e.downcast_ref::<HttpStatusError>() {
+                        page_report.http_status = Some(http_error.status);
+                    }
+                    page_report.empty_pdf = e.is::<EmptyPdfError>();
+                    page_report.stalled_phase = None;
+                    if let Some(timeout) = e.downcast_ref::<PhaseTimeoutError>() {
+                        page_report.stalled_phase = Some(timeout.phase.to_string());
+                        page_report.timings = Some(timeout.timings);
+                    }
+                    page_report.error = Some(e.to_string());
+                    if page_report.attempts > self.options.retries {
+                        error!("Giving up on \"{}\": {}", href, e);
+                        self.release_landed_urls(&page_report.url);
+                        break Err(e);
+                    }
+                    warn!("Failed to download \"{}\" ({}), retry {}/{}", href, e, page_report.attempts, self.options.retries);
+                    let delay = state.backoff().delay().max(Duration::from_millis(500));
+                    tokio::time::sleep(delay).await;
+                }
+            }
+        };
+
+        state.report.pages.push(page_report);
+        result
+    }
+
+    async fn download_link(
+        &self,
+        browser: &Browser,
+        target_url: &str,
+        href: &str,
+        index: usize,
+        previous_hash: Option<&str>,
+    ) -> Result<(PathBuf, PageCapture)> {
+        let slug = self.href_to_slug(href);
+
+        if slug.is_empty() {
+            warn!("Empty slug, ignoring \"{}\"", href.green());
+            return Err(anyhow!("Empty slug"));
+        }
+
+        let out_path = self.page_path(index, &slug);
+
+        let url = Url::parse(&self.resolve_href(target_url, href))
+            .map_err(|e| anyhow!("Failed to join URL: {}", e))?;
+
+        let capture = self.download_page(browser, &url, &out_path, previous_hash).await?;
+
+        Ok((out_path, capture))
+    }
+
+    /// Where the PDF of the content page with this number and slug is written
+    fn page_path(&self, index: usize, slug: &str) -> PathBuf {
+        self.out_dir().join("pages").join(format!("{:02}_{}.pdf", index, slug))
+    }
+
+    /// The PDF an earlier run left for this page, for `skip_existing`, when it
+    /// is there and parses. A corrupt file is captured again and overwritten.
+    async fn reuse_existing(&self, target_url: &str, href: &str, index: usize, state: &mut CrawlState) -> Option<(PathBuf, PageCapture)> {
+        let slug = self.href_to_slug(href);
+        if slug.is_empty() {
+            return None;
+        }
+        let path = self.page_path(index, &slug);
+        let data = fs::read(&path).await.ok()?;
+
+        let doc = match lopdf::Document::load_mem(&data) {
+            Ok(doc) if is_valid_pdf(&data) => doc,
+            Ok(_) => {
+                warn!("Existing {} is empty, downloading it again", path.display());
+                return None;
+            }
+            Err(e) => {
+                warn!("Existing {} is not a valid PDF ({}), downloading it again", path.display(), e);
+                return None;
+            }
+        };
+        let page_count = doc.get_pages().len();
+        debug!("Reusing {} for \"{}\"", path.display(), href);
+
+        state.report.pages.push(PageReport {
+            url: self.resolve_href(target_url, href),
+            slug,
+            http_status: None,
+            bytes: Some(data.len() as u64),
+            page_count: Some(page_count),
+            duration_ms: 0,
+            timings: None,
+            attempts: 0,
+            success: true,
+            skipped: Some("reused existing file".to_string()),
+            timed_out: false,
+            stalled_phase: None,
+            empty_pdf: false,
+            missing_alt: Vec::new(),
+            error: None,
+        });
+
+        let capture = PageCapture {
+            http_status: None,
+            bytes: data.len() as u64,
+            page_count: Some(page_count),
+            content_hash: None,
+            unchanged: false,
+            reused: true,
+            duplicate_of: None,
+            stalled: None,
+            timings: PhaseTimings::default(),
+            missing_alt: Vec::new(),
+            fragment: None,
+        };
+        Some((path, capture))
+    }
 
-        // Set the HTML content
-        page.set_content(&cover_html).await
-            .map_err(|e| anyhow!("Failed to set cover page content: {}", e))?;
+    async fn download_page(&self, browser: &Browser, url: &Url, path: &Path, previous_hash: Option<&str>) -> Result<PageCapture> {
+        info!("Downloading \"{}\" into \"{}\"", url.to_string().green(), path.display().to_string().blue());
 
-        tokio::time::sleep(Duration::from_millis(1000)).await;
+        let mut timings = PhaseTimings::default();
+        let phase_started = Instant::now();
 
-        // Generate PDF
-        let cover_filename = "01_cover.pdf";
-        let cover_path = PathBuf::from(&self.out_dir).join("pages").join(cover_filename);
+        let page = browser
+            .new_page("about:blank")
+            .await
+            .map_err(|e| anyhow!("Failed to create new page: {}", e))?;
+        self.disable_scripts(&page).await?;
+        let mut failed_requests = self.block_requests(&page).await?;
+
+        // Set before navigating so scripts checking matchMedia on load see it too
+        if self.options.reduce_motion {
+            page.emulate_media_features(vec![MediaFeature::new("prefers-reduced-motion", "reduce")])
+                .await
+                .map_err(|e| anyhow!("Failed to emulate reduced motion: {}", e))?;
+        }
+
+        if let Err(e) = self.goto(&page, url.as_str()).await {
+            page.close().await.ok();
+            timings.navigation_ms = phase_started.elapsed().as_millis() as u64;
+            return Err(match e.downcast::<PhaseTimeoutError>() {
+                Ok(timeout) => PhaseTimeoutError { timings, ..timeout }.into(),
+                Err(e) => e,
+            });
+        }
+
+        let stalled = self.wait_until_ready(&page).await?;
+        if let (Some(phase), false) = (stalled, self.options.capture_on_timeout) {
+            page.close().await.ok();
+            timings.navigation_ms = phase_started.elapsed().as_millis() as u64;
+            return Err(PhaseTimeoutError { phase, timeout: self.timeout, url: url.to_string(), timings }.into());
+        }
+        if let Some(phase) = stalled {
+            warn!("\"{}\" was not ready after {:.1}s ({} unfinished), capturing it anyway", url, self.timeout.as_secs_f64(), phase);
+        }
+        self.log_blocked(url.as_str(), failed_requests.as_mut());
+
+        // Rate limiting and server errors still render an error page, so surface them as failures
+        let status = page
+            .evaluate("performance.getEntriesByType('navigation')[0]?.responseStatus ?? 0")
+            .await
+            .ok()
+            .and_then(|result| result.into_value::<u16>().ok())
+            .filter(|status| *status != 0);
+        if let Some(status) = status.filter(|s| *s == 429 || *s >= 500) {
+            page.close().await.ok();
+            return Err(HttpStatusError { status, url: url.to_string() }.into());
+        }
+
+        if let Err(e) = self.check_redirects(&page, url).await {
+            page.close().await.ok();
+            return Err(e);
+        }
+        if let Some(first) = self.claim_landed_url(&page, url).await {
+            page.close().await.ok();
+            debug!("{} ends up at the page already captured for {}, skipping it", url, first);
+            return Ok(PageCapture {
+                http_status: status,
+                bytes: 0,
+                page_count: None,
+                content_hash: None,
+                unchanged: false,
+                reused: false,
+                duplicate_of: Some(first),
+                stalled,
+                timings,
+                missing_alt: Vec::new(),
+                fragment: None,
+            });
+        }
+
+        timings.navigation_ms = phase_started.elapsed().as_millis() as u64;
+        let phase_started = Instant::now();
 
-        if let Some(parent) = cover_path.parent() {
+        if let Some(parent) = path.parent() {
             fs::create_dir_all(parent)
                 .await
                 .map_err(|e| anyhow!("Failed to create directory: {}", e))?;
         }
 
-        let params = PrintToPdfParams {
-            scale: Some(self.pdf_options.scale),
-            margin_top: Some(self.pdf_options.margin_top),
-            margin_right: Some(self.pdf_options.margin_right),
-            margin_bottom: Some(self.pdf_options.margin_bottom),
-            margin_left: Some(self.pdf_options.margin_left),
-            ..Default::default()
+        // Saved before `prepare_page` rearranges the DOM for print
+        let source = if self.options.merge.attach_source {
+            Some(page.content().await.map_err(|e| anyhow!("Failed to read the page's HTML: {}", e))?)
+        } else {
+            None
         };
 
-        let pdf_data = page
-            .pdf(params)
+        self.prepare_page(&page).await?;
+
+        let text = page
+            .evaluate("document.body ? document.body.innerText : ''")
             .await
-            .map_err(|e| anyhow!("Failed to generate cover PDF: {}", e))?;
+            .ok()
+            .and_then(|result| result.into_value::<String>().ok());
+        if let (true, Some(text)) = (self.options.no_js, &text) {
+            self.warn_if_needs_js(url.as_str(), text);
+        }
+        let content_hash = text.map(|text| content_hash(&text));
+
+        // Before the header goes, it may hold the breadcrumbs
+        self.set_page_title(&page, url.as_str(), path).await?;
+
+        // After hashing, so a page's hash doesn't depend on which page came first
+        if self.options.dedupe_page_headers {
+            self.dedupe_page_header(&page, url.as_str()).await?;
+        }
+
+        if self.options.html_dump {
+            self.dump_html(&page, path).await?;
+        }
+
+        let missing_alt = if self.options.accessible {
+            self.images_without_alt(&page).await
+        } else {
+            Vec::new()
+        };
+        if !missing_alt.is_empty() {
+            warn!("\"{}\" has {} images without alt text: {}", url, missing_alt.len(), missing_alt.join(", "));
+        }
+
+        if previous_hash.is_some() && previous_hash == content_hash.as_deref() {
+            debug!("Unchanged since previous manifest, skipping capture of {}", url);
+            page.close().await.ok();
+            timings.render_ms = phase_started.elapsed().as_millis() as u64;
+            return Ok(PageCapture {
+                http_status: status,
+                bytes: 0,
+                page_count: None,
+                content_hash,
+                unchanged: true,
+                reused: false,
+                duplicate_of: None,
+                stalled,
+                timings,
+                missing_alt,
+                fragment: None,
+            });
+        }
+
+        if self.options.single_print {
+            let fragment = self.page_fragment(&page).await?;
+            page.close().await.ok();
+            timings.render_ms = phase_started.elapsed().as_millis() as u64;
+            return Ok(PageCapture {
+                http_status: status,
+                bytes: 0,
+                page_count: None,
+                content_hash,
+                unchanged: false,
+                reused: false,
+                duplicate_of: None,
+                stalled,
+                timings,
+                missing_alt,
+                fragment: Some(fragment),
+            });
+        }
+
+        let mut params = self.print_params();
+        if self.options.fit_width {
+            params.scale = Some(self.fit_width_scale(&page, url).await);
+        }
+        if self.options.viewport_only {
+            // Paper the size of the viewport lays the page out exactly as on screen,
+            // and printing only the first sheet drops everything below the fold
+            let scale = params.scale.unwrap_or(self.pdf_options.scale);
+            params.paper_width = Some(self.options.viewport.width as f64 * scale / CSS_PIXELS_PER_INCH);
+            params.paper_height = Some(self.options.viewport.height as f64 * scale / CSS_PIXELS_PER_INCH);
+            params.page_ranges = Some("1".to_string());
+        }
+        if self.options.single_long_page {
+            let scale = params.scale.unwrap_or(self.pdf_options.scale);
+            params.paper_height = self.long_page_height(&page, url, scale).await;
+        }
+
+        timings.render_ms = phase_started.elapsed().as_millis() as u64;
+        let phase_started = Instant::now();
+
+        let pdf_error = |e: anyhow::Error, mut timings: PhaseTimings| match e.downcast::<PhaseTimeoutError>() {
+            Ok(timeout) => {
+                timings.pdf_ms = phase_started.elapsed().as_millis() as u64;
+                PhaseTimeoutError { timings, ..timeout }.into()
+            }
+            Err(e) => e,
+        };
+        let mut pdf_data = self.print(&page, url.as_str(), params.clone()).await.map_err(|e| pdf_error(e, timings))?;
+
+        // Under load Chrome occasionally hands back a truncated buffer; the page
+        // itself is fine, so printing it again usually works
+        if !is_valid_pdf(&pdf_data) && self.options.retry_empty_pdf {
+            warn!("Chrome returned an empty PDF ({} bytes) for \"{}\", printing it again", pdf_data.len(), url);
+            pdf_data = self.print(&page, url.as_str(), params).await.map_err(|e| pdf_error(e, timings))?;
+        }
+        if !is_valid_pdf(&pdf_data) {
+            page.close().await.ok();
+            return Err(EmptyPdfError { bytes: pdf_data.len() }.into());
+        }
+        if self.options.page_outlines {
+            pdf_data = limit_outline_depth(&pdf_data, PAGE_OUTLINE_DEPTH)
+                .map_err(|e| anyhow!("Failed to trim the outline of {}: {}", url, e))?;
+        }
+
+        timings.pdf_ms = phase_started.elapsed().as_millis() as u64;
+
+        let capture = PageCapture {
+            http_status: status,
+            bytes: pdf_data.len() as u64,
+            page_count: lopdf::Document::load_mem(&pdf_data).ok().map(|doc| doc.get_pages().len()),
+            content_hash,
+            unchanged: false,
+            reused: false,
+            duplicate_of: None,
+            stalled,
+            timings,
+            missing_alt,
+            fragment: None,
+        };
 
-        fs::write(&cover_path, pdf_data)
+        write_atomic(path, pdf_data)
             .await
-            .map_err(|e| anyhow!("Failed to write cover PDF: {}", e))?;
+            .map_err(|e| anyhow!("Failed to write PDF to {}: {}", path.display(), e))?;
 
-        info!("Cover page created: {}", cover_path.display().to_string().blue());
-        Ok(cover_path)
+        // Picked up by the merger, which attaches it to the combined PDF
+        if let Some(source) = source {
+            let source_path = path.with_extension("html");
+            write_atomic(&source_path, source)
+                .await
+                .map_err(|e| anyhow!("Failed to write HTML source to {}: {}", source_path.display(), e))?;
+        }
+
+        Ok(capture)
     }
 
-    async fn expand_menu_links(&self, page: &chromiumoxide::Page) -> Result<()> {
+    /// Takes the prepared page's markup for `single_print`.
+    ///
+    /// URLs are made absolute so they still resolve elsewhere, and scripts are
+    /// dropped: the combined document is static and must not re-render itself.
+    async fn page_fragment(&self, page: &chromiumoxide::Page) -> Result<PageFragment> {
         let js_code = r#"
-            (async () => {
-                // For old GitBook format - expand TOC menu items
-                const oldFormatElements = document
-                    .querySelectorAll('a[data-rnwrdesktop-fnigne="true"] > div[tabindex="0"]');
-
-                for (let element of oldFormatElements) {
-                    element.click();
+            (() => {
+                for (const el of document.querySelectorAll('script, noscript')) {
+                    el.remove();
                 }
-                
-                // For new GitBook format - look for expandable navigation items
-                const expandButtons = document.querySelectorAll([
-                    'button[aria-expanded="false"]',
-                    'button[data-state="closed"]',
-                    '[role="button"][aria-expanded="false"]'
-                ].join(', '));
-                
-                for (let button of expandButtons) {
-                    button.click();
+                for (const el of document.querySelectorAll('img[srcset], source[srcset]')) {
+                    if (el.currentSrc) {
+                        el.setAttribute('src', el.currentSrc);
+                    }
+                    el.removeAttribute('srcset');
                 }
-                
-                // For Docusaurus - expand collapsible sidebar categories
-                const docusaurusExpandables = document.querySelectorAll([
-                    '.menu__list-item--collapsed > .menu__link',
-                    '.menu__link--sublist[aria-expanded="false"]',
-                    'button.menu__link--sublist',
-                    '.theme-doc-sidebar-item-category button[aria-expanded="false"]',
-                    '.menu__caret', // Docusaurus v2 caret
-                    '[class*="collapsible"] button[aria-expanded="false"]'
-                ].join(', '));
-                
-                for (let item of docusaurusExpandables) {
-                    item.click();
+                for (const el of document.querySelectorAll('[src]')) {
+                    el.setAttribute('src', el.src);
                 }
-                
-                // Also try to click on category headers directly
-                const categoryHeaders = document.querySelectorAll('.menu__list-item--collapsed');
-                for (let header of categoryHeaders) {
-                    header.click();
+                for (const el of document.querySelectorAll('a[href], link[href]')) {
+                    el.setAttribute('href', el.href);
                 }
-                
-                // Wait a bit for animations
-                await new Promise(r => setTimeout(r, 1000));
-            })();
+
+                const attributes = (el) => [...el.attributes]
+                    .map((attr) => `${attr.name}="${attr.value.replace(/&/g, '&amp;').replace(/"/g, '&quot;')}"`)
+                    .join(' ');
+                return {
+                    styles: [...document.head.querySelectorAll('style, link[rel~="stylesheet"]')]
+                        .map((el) => el.outerHTML)
+                        .join('\n'),
+                    html_attributes: attributes(document.documentElement),
+                    body_attributes: document.body ? attributes(document.body) : '',
+                    body: document.body ? document.body.innerHTML : '',
+                };
+            })()
         "#;
 
-        page.evaluate(js_code)
+        let fragment: serde_json::Value = page
+            .evaluate(js_code)
             .await
-            .map_err(|e| anyhow!("Failed to expand menu links: {}", e))?;
+            .map_err(|e| anyhow!("Failed to extract page markup: {}", e))?
+            .into_value()
+            .map_err(|e| anyhow!("Failed to parse page markup: {}", e))?;
 
-        Ok(())
+        let field = |name: &str| fragment[name].as_str().unwrap_or_default().to_string();
+        Ok(PageFragment {
+            styles: field("styles"),
+            html_attributes: field("html_attributes"),
+            body_attributes: field("body_attributes"),
+            body: field("body"),
+        })
     }
 
-    async fn prepare_page(&self, page: &chromiumoxide::Page) -> Result<()> {
-        let js_code = r#"
-            // Expand all expandable sections
-            const sectionsToExpand = document
-                .querySelectorAll('div[aria-controls^="expandable-body-"]');
+    /// Prints the pages collected with `single_print` as one document, each
+    /// starting on a new sheet, and returns the PDF's path.
+    ///
+    /// The document takes the first page's styles and is loaded over that page,
+    /// so it is same-origin with the site for stylesheets, fonts and cookies.
+    async fn print_single_document(&self, browser: &Browser, first_url: &str, fragments: &[PageFragment]) -> Result<PathBuf> {
+        info!("Printing {} pages as a single document...", fragments.len());
 
-            for (let section of sectionsToExpand) {
-                section.click();
-            }
+        let page = browser
+            .new_page("about:blank")
+            .await
+            .map_err(|e| anyhow!("Failed to create page: {}", e))?;
+        // The site's scripts would otherwise re-render the page on load
+        page.execute(SetScriptExecutionDisabledParams::new(true))
+            .await
+            .map_err(|e| anyhow!("Failed to disable JavaScript: {}", e))?;
+        self.goto(&page, first_url).await?;
 
-            // Remove redundant/interactive elements
-            const itemSelectorsToRemove = [
-                'header + div[data-rnwrdesktop-hidden="true"]',
-                'div[aria-label^="Search"]',
-                'div[aria-label="Page actions"]',
-            ];
-            const itemsToRemove = document
-                .querySelectorAll(itemSelectorsToRemove.join(', '));
+        let title = page.get_title().await.ok().flatten().unwrap_or_default();
+        let bodies: Vec<&str> = fragments.iter().map(|fragment| fragment.body.as_str()).collect();
+        let html = format!(r#"<!DOCTYPE html>
+<html {}>
+<head>
+<meta charset="UTF-8">
+<title>{}</title>
+{}
+</head>
+<body {}>
+{}
+</body>
+</html>
+"#,
+            fragments[0].html_attributes,
+            title.replace('&', "&amp;").replace('<', "&lt;"),
+            fragments[0].styles,
+            fragments[0].body_attributes,
+            bodies.join("\n<div style=\"break-before: page; page-break-before: always\"></div>\n"),
+        );
 
-            for (let item of itemsToRemove) {
-                item.remove();
-            }
+        page.set_content(&html)
+            .await
+            .map_err(|e| anyhow!("Failed to load the combined document: {}", e))?;
 
-            // Turn relative timestamps into absolute ones
-            const lastModifiedEl = document
-                .querySelector('div[dir="auto"] > span[aria-label]');
+        self.wait_for_images(&page).await;
 
-            if (lastModifiedEl) {
-                lastModifiedEl.innerText = lastModifiedEl.getAttribute('aria-label');
-            }
+        let pdf_data = self.print(&page, first_url, self.print_params()).await?;
+        page.close().await.ok();
+
+        // Sorts after the front matter, where the first content page would be
+        let path = self.out_dir().join("pages").join("02_book.pdf");
+        write_atomic(&path, pdf_data)
+            .await
+            .map_err(|e| anyhow!("Failed to write PDF to {}: {}", path.display(), e))?;
+
+        info!("Single document printed: {}", path.display().to_string().blue());
+        Ok(path)
+    }
+
+    /// Sources of the printed images that have no `alt` attribute at all.
+    ///
+    /// An empty `alt`, `role="presentation"` and hidden images are deliberate
+    /// and not reported.
+    async fn images_without_alt(&self, page: &chromiumoxide::Page) -> Vec<String> {
+        let js_code = r#"
+            [...document.images]
+                .filter((img) => !img.hasAttribute('alt'))
+                .filter((img) => !['presentation', 'none'].includes(img.getAttribute('role')))
+                .filter((img) => !img.closest('[aria-hidden="true"]') && img.getClientRects().length > 0)
+                .map((img) => img.currentSrc || img.src)
         "#;
 
-        page.evaluate(js_code)
+        let mut sources = page
+            .evaluate(js_code)
             .await
-            .map_err(|e| anyhow!("Failed to prepare page: {}", e))?;
+            .ok()
+            .and_then(|result| result.into_value::<Vec<String>>().ok())
+            .unwrap_or_default();
+        sources.dedup();
+        sources
+    }
 
+    /// Turns the page's own scripts off for `--no-js`; call before navigating.
+    ///
+    /// Code run through `evaluate` still works, but timers and event listeners
+    /// it sets up never fire, which is why some steps are skipped in this mode.
+    async fn disable_scripts(&self, page: &chromiumoxide::Page) -> Result<()> {
+        if self.options.no_js {
+            page.execute(SetScriptExecutionDisabledParams::new(true))
+                .await
+                .map_err(|e| anyhow!("Failed to disable JavaScript: {}", e))?;
+        }
         Ok(())
     }
 
-    async fn download_link(&self, browser: &Browser, target_url: &str, href: &str, index: usize) -> Result<PathBuf> {
-        let slug = self.href_to_slug(href);
+    /// Has Chrome fail requests to the `block_urls` patterns, and the default
+    /// blocklist's, on `page`. Returns the page's failed requests to count the
+    /// blocked ones with [`Self::log_blocked`], or `None` if nothing is blocked.
+    async fn block_requests(&self, page: &chromiumoxide::Page) -> Result<Option<EventStream<EventLoadingFailed>>> {
+        let mut patterns = self.options.block_urls.clone();
+        if self.options.default_blocklist {
+            patterns.extend(DEFAULT_BLOCKED_URLS.iter().map(|pattern| pattern.to_string()));
+        }
+        if patterns.is_empty() {
+            return Ok(None);
+        }
 
-        if slug.is_empty() {
-            warn!("Empty slug, ignoring \"{}\"", href.green());
-            return Err(anyhow!("Empty slug"));
+        let failed = page
+            .event_listener::<EventLoadingFailed>()
+            .await
+            .map_err(|e| anyhow!("Failed to listen for failed requests: {}", e))?;
+        page.execute(SetBlockedUrLsParams::new(patterns))
+            .await
+            .map_err(|e| anyhow!("Failed to block URLs: {}", e))?;
+        Ok(Some(failed))
+    }
+
+    /// Logs how many requests of the page at `url` were blocked so far
+    fn log_blocked(&self, url: &str, failed: Option<&mut EventStream<EventLoadingFailed>>) {
+        let Some(failed) = failed else {
+            return;
+        };
+        let mut blocked = 0;
+        while let Some(Some(event)) = failed.next().now_or_never() {
+            if matches!(event.blocked_reason, Some(BlockedReason::Inspector)) {
+                blocked += 1;
+            }
+        }
+        if blocked > 0 {
+            debug!("Blocked {} requests on {}", blocked, url);
         }
+    }
 
-        let filename = format!("{:02}_{}.pdf", index, slug);
-        let out_path = PathBuf::from(&self.out_dir).join("pages").join(filename);
+    /// Waits for client-side rendering, done as soon as an element matching one
+    /// of `selectors` is on the page; only a page that never shows one waits out
+    /// all of `timeout` (capped at the page timeout). Not needed under `--no-js`.
+    async fn wait_for_content(&self, page: &chromiumoxide::Page, selectors: &[&str], timeout: Duration) {
+        if self.options.no_js {
+            return;
+        }
 
-        let url = Url::parse(target_url)?
-            .join(href)
-            .map_err(|e| anyhow!("Failed to join URL: {}", e))?;
+        let js_code = format!("document.querySelector({}) !== null", serde_json::json!(selectors.join(", ")));
+        let deadline = Instant::now() + timeout.min(self.timeout);
+        loop {
+            let found = page
+                .evaluate(js_code.as_str())
+                .await
+                .ok()
+                .and_then(|result| result.into_value::<bool>().ok())
+                .unwrap_or(false);
+            if found || Instant::now() >= deadline {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
 
-        self.download_page(browser, &url, &out_path).await?;
+    /// Waits until the number of links on the page stops changing, for
+    /// navigation that renders the entries `expand_menu_links` opened a little
+    /// later; gives up after `timeout`, capped at the page timeout
+    async fn wait_for_links_to_settle(&self, page: &chromiumoxide::Page, timeout: Duration) {
+        if self.options.no_js {
+            return;
+        }
 
-        Ok(out_path)
+        let deadline = Instant::now() + timeout.min(self.timeout);
+        let mut previous = None;
+        loop {
+            let count = page
+                .evaluate("document.querySelectorAll('a[href]').length")
+                .await
+                .ok()
+                .and_then(|result| result.into_value::<u64>().ok());
+            if count.is_none() || count == previous || Instant::now() >= deadline {
+                return;
+            }
+            previous = count;
+            tokio::time::sleep(Duration::from_millis(250)).await;
+        }
     }
 
-    async fn download_page(&self, browser: &Browser, url: &Url, path: &Path) -> Result<()> {
-        info!("Downloading \"{}\" into \"{}\"", url.to_string().green(), path.display().to_string().blue());
+    /// Waits until every image on the page has loaded or failed, at most the
+    /// page timeout. Load events don't reach evaluate with scripts off, so this
+    /// polls.
+    async fn wait_for_images(&self, page: &chromiumoxide::Page) {
+        let deadline = Instant::now() + self.timeout;
+        while Instant::now() < deadline {
+            let loaded = page
+                .evaluate("[...document.images].every((img) => img.complete)")
+                .await
+                .ok()
+                .and_then(|result| result.into_value::<bool>().ok())
+                .unwrap_or(true);
+            if loaded {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(250)).await;
+        }
+    }
 
-        let page = browser
-            .new_page("about:blank")
-            .await
-            .map_err(|e| anyhow!("Failed to create new page: {}", e))?;
+    /// Points out pages that only render with JavaScript, which come out blank under `--no-js`
+    fn warn_if_needs_js(&self, url: &str, text: &str) {
+        let chars = text.split_whitespace().map(str::len).sum::<usize>();
+        if chars < NO_JS_MIN_TEXT_CHARS {
+            warn!("\"{}\" has almost no text with JavaScript disabled; it probably needs it, try without --no-js", url);
+        }
+    }
 
-        page.goto(url.as_str())
-            .await
-            .map_err(|e| anyhow!("Failed to navigate to {}: {}", url, e))?;
+    /// How long Chrome gets to answer a single protocol command (printing a
+    /// page included): chromiumoxide's default, or longer with a longer `--timeout`
+    fn request_timeout(&self) -> Duration {
+        self.timeout.max(Duration::from_millis(REQUEST_TIMEOUT))
+    }
 
-        page.wait_for_navigation()
-            .await
-            .map_err(|e| anyhow!("Failed to wait for navigation: {}", e))?;
+    /// Navigates `page` to `url`, failing with a [`PhaseTimeoutError`] when the
+    /// load doesn't finish within the timeout
+    async fn goto(&self, page: &chromiumoxide::Page, url: &str) -> Result<()> {
+        match tokio::time::timeout(self.timeout, page.goto(url)).await {
+            Ok(Ok(_)) => Ok(()),
+            Ok(Err(CdpError::Timeout)) | Err(_) => Err(self.navigation_timeout(url)),
+            Ok(Err(e)) => Err(anyhow!("Failed to navigate to {}: {}", url, e)),
+        }
+    }
 
-        if let Some(parent) = path.parent() {
-            fs::create_dir_all(parent)
-                .await
-                .map_err(|e| anyhow!("Failed to create directory: {}", e))?;
+    /// Waits for the navigation `page` is in to finish, bounded like [`Self::goto`]
+    async fn wait_for_navigation(&self, page: &chromiumoxide::Page, url: &str) -> Result<()> {
+        match tokio::time::timeout(self.timeout, page.wait_for_navigation()).await {
+            Ok(Ok(_)) => Ok(()),
+            Ok(Err(CdpError::Timeout)) | Err(_) => Err(self.navigation_timeout(url)),
+            Ok(Err(e)) => Err(anyhow!("Failed to wait for navigation: {}", e)),
         }
+    }
 
-        self.prepare_page(&page).await?;
+    /// Prints `page` to PDF, failing with a [`PhaseTimeoutError`] when Chrome
+    /// doesn't hand the PDF back within the timeout
+    async fn print(&self, page: &chromiumoxide::Page, url: &str, params: PrintToPdfParams) -> Result<Vec<u8>> {
+        match tokio::time::timeout(self.request_timeout(), page.pdf(params)).await {
+            Ok(Ok(pdf_data)) => Ok(pdf_data),
+            Ok(Err(CdpError::Timeout)) | Err(_) => Err(PhaseTimeoutError {
+                phase: CapturePhase::Pdf,
+                timeout: self.request_timeout(),
+                url: url.to_string(),
+                timings: PhaseTimings::default(),
+            }
+            .into()),
+            Ok(Err(e)) => Err(anyhow!("Failed to generate PDF: {}", e)),
+        }
+    }
+
+    fn navigation_timeout(&self, url: &str) -> anyhow::Error {
+        PhaseTimeoutError {
+            phase: CapturePhase::Navigation,
+            timeout: self.timeout,
+            url: url.to_string(),
+            timings: PhaseTimings::default(),
+        }
+        .into()
+    }
+
+    /// Waits for the navigation to finish and the DOM to stop changing.
+    ///
+    /// Returns the phase still running when the timeout hit, if it did, e.g.
+    /// `Settle` because of a perpetual spinner or a polling widget.
+    async fn wait_until_ready(&self, page: &chromiumoxide::Page) -> Result<Option<CapturePhase>> {
+        let js_code = r#"
+            new Promise((resolve) => {
+                // Ready once the load event fired and the DOM was quiet for a moment
+                const quietMs = 500;
+                let timer;
+                const observer = new MutationObserver(() => settle());
+                const settle = () => {
+                    clearTimeout(timer);
+                    timer = setTimeout(() => {
+                        observer.disconnect();
+                        resolve(true);
+                    }, quietMs);
+                };
+                const start = () => {
+                    observer.observe(document.documentElement, {
+                        childList: true, subtree: true, attributes: true, characterData: true,
+                    });
+                    settle();
+                };
+
+                if (document.readyState === 'complete') {
+                    start();
+                } else {
+                    window.addEventListener('load', start, { once: true });
+                }
+            })
+        "#;
 
-        let params = PrintToPdfParams {
+        let navigated = AtomicBool::new(false);
+        let ready = async {
+            page.wait_for_navigation()
+                .await
+                .map_err(|e| anyhow!("Failed to wait for navigation: {}", e))?;
+            navigated.store(true, Ordering::Relaxed);
+            // Without scripts the timer would never fire, and nothing changes the DOM anyway
+            if self.options.no_js {
+                return Ok(());
+            }
+            page.evaluate(js_code)
+                .await
+                .map_err(|e| anyhow!("Failed to wait for the page to settle: {}", e))?;
+            Ok::<_, anyhow::Error>(())
+        };
+
+        match tokio::time::timeout(self.timeout, ready).await {
+            Ok(result) => result.map(|_| None),
+            Err(_) if navigated.load(Ordering::Relaxed) => Ok(Some(CapturePhase::Settle)),
+            Err(_) => Ok(Some(CapturePhase::Navigation)),
+        }
+    }
+
+    fn print_params(&self) -> PrintToPdfParams {
+        // Sideways paper is passed as its turned dimensions rather than with
+        // `landscape`, which sizes that override the paper would turn as well
+        let (paper_width, paper_height) = self.pdf_options.paper_inches();
+        PrintToPdfParams {
             scale: Some(self.pdf_options.scale),
+            paper_width: Some(paper_width),
+            paper_height: Some(paper_height),
             margin_top: Some(self.pdf_options.margin_top),
             margin_right: Some(self.pdf_options.margin_right),
             margin_bottom: Some(self.pdf_options.margin_bottom),
             margin_left: Some(self.pdf_options.margin_left),
+            // Chrome builds the outline from the structure tree, so it needs tagging too
+            generate_tagged_pdf: (self.options.accessible || self.options.page_outlines).then_some(true),
+            generate_document_outline: self.options.page_outlines.then_some(true),
             ..Default::default()
-        };
+        }
+    }
 
-        let pdf_data = page
-            .pdf(params)
+    /// Scale at which the page's content exactly fits the printable paper width
+    async fn fit_width_scale(&self, page: &chromiumoxide::Page, url: &Url) -> f64 {
+        let content_width = page
+            .evaluate(r#"
+                (() => {
+                    // Prefer the content column, the document itself spans the whole viewport
+                    const content = document.querySelector('.theme-doc-markdown, article, main') || document.documentElement;
+                    // Wide tables and code blocks usually scroll inside a wrapper, which
+                    // keeps the column narrow on screen but clips them in print
+                    const overflow = [...content.querySelectorAll('pre, table, div')]
+                        .filter((el) => el.offsetParent !== null)
+                        .reduce((widest, el) => Math.max(widest, el.scrollWidth - el.clientWidth), 0);
+                    return content.scrollWidth + overflow;
+                })()
+            "#)
             .await
-            .map_err(|e| anyhow!("Failed to generate PDF: {}", e))?;
+            .ok()
+            .and_then(|result| result.into_value::<f64>().ok())
+            .filter(|width| *width > 0.0);
 
-        fs::write(path, pdf_data)
-            .await
-            .map_err(|e| anyhow!("Failed to write PDF to {}: {}", path.display(), e))?;
+        let Some(content_width) = content_width else {
+            return self.pdf_options.scale;
+        };
 
-        Ok(())
+        let printable_inches = self.pdf_options.paper_inches().0 - self.pdf_options.margin_left - self.pdf_options.margin_right;
+        let scale = (printable_inches * CSS_PIXELS_PER_INCH / content_width).clamp(MIN_FIT_SCALE, MAX_FIT_SCALE);
+        debug!("Content of {} is {}px wide, printing at scale {:.2}", url, content_width, scale);
+        scale
     }
 
-    fn is_supported_documentation_site(&self, document: &Html) -> bool {
-        // GitBook detection
-        let old_format_selector = Selector::parse("body > .gitbook-root").unwrap();
-        if document.select(&old_format_selector).next().is_some() {
-            return true;
-        }
-
-        let new_format_selectors = [
-            "body > div.scroll-nojump",
-            "nav[role=\"navigation\"]",
-            "a[href*=\"gitbook.io\"]",
-        ];
-
-        for selector_str in &new_format_selectors {
-            if let Ok(selector) = Selector::parse(selector_str) {
-                if document.select(&selector).next().is_some() {
-                    return true;
-                }
-            }
-        }
-
-        let body_selector = Selector::parse("body").unwrap();
-        if let Some(body) = document.select(&body_selector).next() {
-            if let Some(class) = body.value().attr("class") {
-                if class.contains("theme-") {
-                    return true;
-                }
-            }
-        }
+    /// Paper height, in inches, that fits the whole page on one sheet.
+    ///
+    /// The content is measured at the width it will be printed at, since it
+    /// reflows. Returns `None` (default pagination) if it can't be measured.
+    async fn long_page_height(&self, page: &chromiumoxide::Page, url: &Url, scale: f64) -> Option<f64> {
+        let printable_inches = self.pdf_options.paper_inches().0 - self.pdf_options.margin_left - self.pdf_options.margin_right;
+        let print_width = printable_inches * CSS_PIXELS_PER_INCH / scale;
 
-        // Docusaurus detection
-        let docusaurus_selectors = [
-            "div#__docusaurus",
-            "div.docusaurus-root",
-            "nav.navbar--fixed-top",
-            "div.navbar__logo",
-            "script[src*=\"docusaurus\"]",
-        ];
+        let content_height = page
+            .evaluate(format!(r#"
+                (() => {{
+                    const root = document.documentElement;
+                    const width = root.style.width;
+                    root.style.width = '{print_width}px';
+                    const height = Math.max(root.scrollHeight, document.body ? document.body.scrollHeight : 0);
+                    root.style.width = width;
+                    return height;
+                }})()
+            "#))
+            .await
+            .ok()
+            .and_then(|result| result.into_value::<f64>().ok())
+            .filter(|height| *height > 0.0)?;
 
-        for selector_str in &docusaurus_selectors {
-            if let Ok(selector) = Selector::parse(selector_str) {
-                if document.select(&selector).next().is_some() {
-                    debug!("Detected Docusaurus site with selector: {}", selector_str);
-                    return true;
-                }
-            }
-        }
+        // A little slack keeps rounding from spilling a sliver onto a second sheet
+        let margins = self.pdf_options.margin_top + self.pdf_options.margin_bottom;
+        let height = (content_height + 16.0) * scale / CSS_PIXELS_PER_INCH + margins;
 
-        // Check for Docusaurus in script content
-        let script_selector = Selector::parse("script").unwrap();
-        for script in document.select(&script_selector) {
-            let content = script.text().collect::<String>();
-            if content.contains("docusaurus") || content.contains("__DOCUSAURUS__") {
-                debug!("Detected Docusaurus site from script content");
-                return true;
-            }
+        if height > MAX_PAGE_HEIGHT_INCHES {
+            warn!("\"{}\" is {:.0} inches long, more than PDF readers support; capping pages at {} inches",
+                  url, height, MAX_PAGE_HEIGHT_INCHES);
+            return Some(MAX_PAGE_HEIGHT_INCHES);
         }
 
-        false
+        debug!("Content is {}px tall, printing on a {:.1} inch page", content_height, height);
+        Some(height)
     }
 
-    fn collect_links(&self, document: &Html) -> Vec<String> {
+    /// Collects the nav links of the page at `page_url`, in navigation order.
+    ///
+    /// Websites link their pages root-relative (`/guide/intro`); on a local
+    /// site any relative link counts and is rewritten to that form.
+    ///
+    /// Sidebar links get the depth of their list item, relative to the
+    /// shallowest one; the navbar and links outside any nav are top-level.
+    ///
+    /// Also returns how many links were dropped because another element
+    /// already linked to the same page.
+    fn collect_links(&self, document: &Html, page_url: &str) -> (Vec<NavLink>, usize) {
         let mut links = Vec::new();
         let mut seen = HashSet::new();
+        // The selectors overlap, so the same element turns up more than once
+        let mut visited = HashSet::new();
+        let mut duplicates = 0;
+        let local = local_site::root(page_url).zip(Url::parse(page_url).ok());
         
         // Prioritize navigation order - collect from sidebar/nav first
         let nav_selectors = [
@@ -600,17 +3979,48 @@ impl Downloader {
             "aside a[href^=\"/\"]",       // Sidebar links
             ".menu a[href^=\"/\"]",       // Docusaurus menu
             ".theme-doc-sidebar-menu a[href^=\"/\"]",  // Docusaurus sidebar
+            "#sidebar-content a[href^=\"/\"]",  // Mintlify sidebar
+            "#navigation-items a[href^=\"/\"]", // Mintlify sidebar (older layout)
             "nav a[href^=\"/\"]",         // General nav links
+            "a[href^=\"/\"]",             // Fallback: any remaining internal links
         ];
+        // Everything between the navbar and the fallback nests its entries in lists
+        let sidebar_selectors = 1..nav_selectors.len() - 1;
+        
+        // Pages of a local site link each other relatively
+        let nav_selectors = nav_selectors.map(|selector| match local {
+            Some(_) => selector.replace("[href^=\"/\"]", "[href]"),
+            None => selector.to_string(),
+        });
         
         // Collect navigation links in order
-        for selector_str in &nav_selectors {
+        let mut list_depths = Vec::new();
+        for (index, selector_str) in nav_selectors.iter().enumerate() {
             if let Ok(selector) = Selector::parse(selector_str) {
                 for element in document.select(&selector) {
-                    if let Some(href) = element.value().attr("href") {
-                        if href.starts_with('/') && !href.contains('#') && !href.contains("/assets/") {
+                    let href = match (&local, element.value().attr("href")) {
+                        (Some((root, page_url)), Some(href)) => local_site::nav_href(root, page_url, href),
+                        (None, href) => href.map(str::to_string),
+                        (_, None) => None,
+                    };
+                    if let Some(href) = href.as_deref() {
+                        if href.starts_with('/') && !href.contains('#') && !self.is_asset_path(href) {
+                            let new_element = visited.insert(element.id());
                             if seen.insert(href.to_string()) {
-                                links.push(href.to_string());
+                                let list_depth = sidebar_selectors.contains(&index).then(|| {
+                                    element
+                                        .ancestors()
+                                        .filter(|node| node.value().as_element().is_some_and(|e| e.name() == "li"))
+                                        .count()
+                                });
+                                list_depths.push(list_depth);
+                                links.push(NavLink {
+                                    href: href.to_string(),
+                                    title: collapse_whitespace(&element.text().collect::<Vec<_>>().join(" ")),
+                                    depth: 0,
+                                });
+                            } else if new_element {
+                                duplicates += 1;
                             }
                         }
                     }
@@ -618,24 +4028,50 @@ impl Downloader {
             }
         }
         
-        // Fallback: collect any remaining internal links
-        let internal_selector = Selector::parse("a[href^=\"/\"]").unwrap();
-        for element in document.select(&internal_selector) {
-            if let Some(href) = element.value().attr("href") {
-                if href.starts_with('/') && !href.contains('#') && !href.contains("/assets/") {
-                    if seen.insert(href.to_string()) {
-                        links.push(href.to_string());
-                    }
-                }
+        let shallowest = list_depths.iter().flatten().min().copied().unwrap_or(0);
+        for (link, list_depth) in links.iter_mut().zip(list_depths) {
+            link.depth = list_depth.map_or(0, |depth| depth - shallowest);
+        }
+
+        debug!("Collected {} unique links in navigation order, dropped {} duplicates", links.len(), duplicates);
+        (links, duplicates)
+    }
+
+    fn is_asset_path(&self, href: &str) -> bool {
+        self.options
+            .asset_paths
+            .iter()
+            .any(|prefix| !prefix.is_empty() && href.contains(prefix.as_str()))
+    }
+
+    /// Drops nav entries whose label contains one of the `skip_link_text` substrings
+    fn filter_links_by_text(&self, links: Vec<NavLink>) -> Vec<NavLink> {
+        if self.options.skip_link_text.is_empty() {
+            return links;
+        }
+
+        let patterns: Vec<String> = self.options.skip_link_text.iter().map(|p| p.to_lowercase()).collect();
+        let (skipped, kept): (Vec<_>, Vec<_>) = links.into_iter().partition(|link| {
+            let title = link.title.to_lowercase();
+            patterns.iter().any(|pattern| title.contains(pattern))
+        });
+
+        if !skipped.is_empty() {
+            info!("Skipped {} nav entries by label:", skipped.len());
+            for link in &skipped {
+                info!("  \"{}\" ({})", link.title.yellow(), link.href);
             }
         }
-        
-        debug!("Collected {} unique links in navigation order", links.len());
-        links
+
+        kept
     }
 
     fn href_to_slug(&self, href: &str) -> String {
-        let mut slug = slugify(href);
+        let mut slug = match self.options.slug_style {
+            SlugStyle::Kebab => slugify(href),
+            SlugStyle::Lower => path_slug(&href.to_lowercase()),
+            SlugStyle::Preserve => path_slug(href),
+        };
         slug = slug.replace("/", "-").trim().to_string();
 
         if slug == "/" || slug.is_empty() {
@@ -645,26 +4081,55 @@ impl Downloader {
         }
     }
 
-    async fn combine_all_pdfs(&self, target_url: &str, pdf_paths: &[PathBuf]) -> Result<PathBuf> {
+    /// Merges the captured pages into the combined PDF, returning its path,
+    /// checksum (when computed) and page count
+    async fn combine_all_pdfs(&self, target_url: &str, captured: &CapturedPages) -> Result<(PathBuf, Option<String>, usize)> {
         info!("Combining all PDFs into a single file...");
 
         let url = Url::parse(target_url)?;
-        let domain_slug = slugify(&url.host_str().unwrap_or("gitbook").replace('.', "-"));
-        let combined_path = PathBuf::from(&self.out_dir).join(format!("{}-combined.pdf", domain_slug));
-
-        let mut merger = PdfMerger::new();
-        
-        // Use the paths in the order they were discovered/downloaded
-        for pdf_path in pdf_paths {
-            if let Err(e) = merger.add_pdf(pdf_path).await {
-                warn!("Failed to add PDF {}: {}", pdf_path.display(), e);
-            }
-        }
+        // A local site is named after its directory
+        let local_name = url
+            .to_file_path()
+            .ok()
+            .and_then(|path| path.parent()?.file_name().map(|name| name.to_string_lossy().into_owned()));
+        let name = url.host_str().map(str::to_string).or(local_name).unwrap_or_else(|| "gitbook".to_string());
+        let domain_slug = slugify(name.replace('.', "-"));
+        let kind = if self.options.only_changed { "changes" } else { "combined" };
+        let file_name = match &self.name_stamp {
+            Some(stamp) => format!("{}-{}-{}.pdf", domain_slug, kind, stamp),
+            None => format!("{}-{}.pdf", domain_slug, kind),
+        };
+        let combined_path = self.out_dir().join(file_name);
 
-        merger.save(&combined_path).await?;
+        let merger = captured.merger(self.options.merge.clone()).await;
+        let sha256 = merger.save(&combined_path).await?;
 
         info!("Combined PDF saved to: {}", combined_path.display().to_string().blue());
 
-        Ok(combined_path)
+        Ok((combined_path, sha256, merger.page_count()))
     }
-}
\ No newline at end of file
+}
+
+/// Whether `data` looks like a PDF with content rather than a failed print
+fn is_valid_pdf(data: &[u8]) -> bool {
+    data.len() >= MIN_PDF_BYTES && data.starts_with(b"%PDF-")
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Keeps the segments of a link path apart (joined with `_`) and replaces
+/// characters that don't belong in a file name with `-`
+fn path_slug(href: &str) -> String {
+    href.split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| {
+            segment
+                .chars()
+                .map(|c| if c.is_alphanumeric() || c == '-' || c == '.' { c } else { '-' })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("_")
+}
@@ -0,0 +1,112 @@
+use pulldown_cmark::{html, Event, Options, Parser, Tag, TagEnd};
+
+/// Book-like typography for pages written in Markdown, independent of the site's theme
+const STYLE: &str = r#"
+    body {
+        font-family: Georgia, 'Times New Roman', serif;
+        font-size: 12pt;
+        line-height: 1.6;
+        color: #222;
+        max-width: 42em;
+        margin: 0 auto;
+    }
+    h1 {
+        font-size: 2.4em;
+        text-align: center;
+        margin: 4em 0 0.5em;
+    }
+    h1 + p, h1 + h2 {
+        text-align: center;
+        color: #555;
+    }
+    h2, h3, h4 {
+        margin: 1.6em 0 0.6em;
+    }
+    a {
+        color: inherit;
+    }
+    code, pre {
+        font-family: 'SFMono-Regular', Consolas, 'Liberation Mono', monospace;
+        font-size: 0.9em;
+    }
+    pre {
+        background: #f5f5f5;
+        padding: 1em;
+        white-space: pre-wrap;
+    }
+    blockquote {
+        margin: 1em 2em;
+        font-style: italic;
+        color: #555;
+    }
+    table {
+        border-collapse: collapse;
+        margin: 1em auto;
+    }
+    th, td {
+        border: 1px solid #ccc;
+        padding: 0.4em 0.8em;
+    }
+    img {
+        max-width: 100%;
+    }
+    hr {
+        border: none;
+        border-top: 1px solid #ccc;
+        margin: 2em 0;
+    }
+"#;
+
+/// Renders a Markdown front matter page as a standalone HTML document.
+///
+/// The document's `<title>` is the first heading, or `fallback_title` when there
+/// is none; Chrome copies it into the PDF, where the merger uses it as the
+/// page's bookmark. Raw HTML passes through, so a page can still force breaks
+/// with `<div style="break-after: page"></div>`.
+pub(crate) fn markdown_to_html(markdown: &str, fallback_title: &str) -> String {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_SMART_PUNCTUATION;
+
+    let title = first_heading(Parser::new_ext(markdown, options))
+        .filter(|title| !title.is_empty())
+        .unwrap_or_else(|| fallback_title.to_string());
+
+    let mut body = String::new();
+    html::push_html(&mut body, Parser::new_ext(markdown, options));
+
+    format!(r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="UTF-8">
+<title>{}</title>
+<style>{}</style>
+</head>
+<body>
+{}
+</body>
+</html>
+"#, escape_html(&title), STYLE, body)
+}
+
+/// Plain text of the first heading, at any level
+fn first_heading<'a>(events: impl Iterator<Item = Event<'a>>) -> Option<String> {
+    let mut events = events.skip_while(|event| !matches!(event, Event::Start(Tag::Heading { .. })));
+    events.next()?;
+
+    let mut text = String::new();
+    for event in events {
+        match event {
+            Event::End(TagEnd::Heading(_)) => break,
+            Event::Text(part) | Event::Code(part) => text.push_str(&part),
+            Event::SoftBreak | Event::HardBreak => text.push(' '),
+            _ => {}
+        }
+    }
+    Some(text.trim().to_string())
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
@@ -0,0 +1,81 @@
+use scraper::{Html, Selector};
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// Keys under which GitBook nests a page's subpages
+const CHILD_KEYS: [&str; 2] = ["pages", "children"];
+
+/// The navigation GitBook ships as JSON with the page, as `(href, title, depth)`
+/// in reading order, when there is one.
+///
+/// Next.js builds embed their props in `<script id="__NEXT_DATA__">`. The page
+/// tree in there is the largest list of objects with a `title` and a `path`,
+/// each nesting its subpages under `pages` or `children`; groups without a path
+/// only contribute their subpages, and links to other sites are left out. A
+/// page's depth counts the pages it is nested under, so groups add none.
+/// Paths are relative to the space, so hrefs are root-relative like those
+/// scraped from the sidebar of a custom domain.
+pub(crate) fn nav_links(document: &Html) -> Option<Vec<(String, String, usize)>> {
+    let selector = Selector::parse("script#__NEXT_DATA__, script#__next_data__").ok()?;
+    let script = document.select(&selector).next()?;
+    let data: Value = serde_json::from_str(&script.text().collect::<String>()).ok()?;
+
+    let mut links = Vec::new();
+    flatten(find_page_tree(&data)?, 0, &mut links, &mut HashSet::new());
+    (!links.is_empty()).then_some(links)
+}
+
+fn is_page(value: &Value) -> bool {
+    value.get("title").is_some_and(Value::is_string)
+        && (value.get("path").is_some_and(Value::is_string) || subpages(value).is_some())
+}
+
+fn subpages(page: &Value) -> Option<&Vec<Value>> {
+    CHILD_KEYS.iter().find_map(|key| page.get(key).and_then(Value::as_array))
+}
+
+/// The array anywhere in `value` whose entries are all pages and that holds
+/// the most pages, counting subpages
+fn find_page_tree(value: &Value) -> Option<&Vec<Value>> {
+    let (own, nested): (Option<&Vec<Value>>, Vec<&Value>) = match value {
+        Value::Array(items) => {
+            let own = (!items.is_empty() && items.iter().all(is_page)).then_some(items);
+            (own, items.iter().collect())
+        }
+        Value::Object(map) => (None, map.values().collect()),
+        _ => return None,
+    };
+
+    nested
+        .into_iter()
+        .filter_map(find_page_tree)
+        .chain(own)
+        .max_by_key(|tree| count_pages(tree))
+}
+
+fn count_pages(pages: &[Value]) -> usize {
+    pages
+        .iter()
+        .map(|page| 1 + subpages(page).map_or(0, |children| count_pages(children)))
+        .sum()
+}
+
+fn flatten(pages: &[Value], depth: usize, links: &mut Vec<(String, String, usize)>, seen: &mut HashSet<String>) {
+    for page in pages {
+        let kind = page.get("kind").or_else(|| page.get("type")).and_then(Value::as_str);
+        let path = page.get("path").and_then(Value::as_str).filter(|path| !path.contains("://"));
+
+        let mut child_depth = depth;
+        if let (Some(path), false) = (path, kind == Some("link")) {
+            let href = format!("/{}", path.trim_matches('/'));
+            if seen.insert(href.clone()) {
+                let title = page.get("title").and_then(Value::as_str).unwrap_or_default();
+                links.push((href, title.trim().to_string(), depth));
+                child_depth = depth + 1;
+            }
+        }
+        if let Some(children) = subpages(page) {
+            flatten(children, child_depth, links, seen);
+        }
+    }
+}
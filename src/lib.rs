@@ -15,9 +15,43 @@
 //! ```bash
 //! book2pdf download https://docs.gitbook.com --combine
 //! ```
+//!
+//! As a library, download and assembly can be kept apart: capture the pages
+//! first, then decide how to combine them.
+//!
+//! ```no_run
+//! # async fn example() -> anyhow::Result<()> {
+//! use book2pdf::{Downloader, MergeOptions};
+//!
+//! let downloader = Downloader::new("output".to_string(), false, true, 30.0);
+//! let captured = downloader.capture("https://docs.example.com/").await?;
+//!
+//! let merger = captured.merger(MergeOptions { page_labels: true, ..Default::default() }).await;
+//! merger.save("book.pdf".as_ref()).await?;
+//! # Ok(())
+//! # }
+//! ```
 
+mod atomic_write;
+mod backoff;
 mod downloader;
+mod front_matter;
+mod gitbook_nav;
+mod local_site;
+mod manifest;
+mod pdf_compress;
 mod pdf_merger;
+mod pdf_outline;
+mod pdf_provenance;
+mod pdf_repair;
+mod pdf_splitter;
+mod platform;
+mod preflight;
+mod report;
 
-pub use downloader::Downloader;
-pub use pdf_merger::PdfMerger;
\ No newline at end of file
+pub use downloader::{CapturedPages, DownloadError, DownloadOptions, Downloader, Keep, PdfOptions, SlugStyle, ViewportSize};
+pub use manifest::{Manifest, ManifestEntry};
+pub use pdf_merger::{natural_sort_key, read_titles_file, MergeOptions, MergeOrder, MergeResult, PageInsert, PageSize, PdfMerger, TitleSource};
+pub use pdf_splitter::{PdfSplitter, Split};
+pub use platform::{DocPlatform, Marker, Maturity};
+pub use report::{CrawlReport, PageReport, PhaseTimings, PrunedLink};
\ No newline at end of file
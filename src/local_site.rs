@@ -0,0 +1,92 @@
+use anyhow::{anyhow, Result};
+use std::path::{Path, PathBuf};
+use url::Url;
+
+/// The `file://` URL to start a crawl of a locally built site at, when
+/// `target` names a directory or HTML file on disk (or is a `file://` URL).
+///
+/// A directory starts at its `index.html`, and is the root that links like
+/// `/guide/intro` are resolved against; a file's directory is the root
+/// otherwise. Returns `None` for anything else, which is crawled as a URL.
+pub(crate) fn start_url(target: &str) -> Result<Option<String>> {
+    let path = match Url::parse(target) {
+        Ok(url) if url.scheme() == "file" => url
+            .to_file_path()
+            .map_err(|_| anyhow!("'{}' is not a local file path", target))?,
+        Ok(url) if url.scheme() == "http" || url.scheme() == "https" => return Ok(None),
+        // Bare paths, including Windows ones that parse with a drive letter as scheme
+        _ if Path::new(target).exists() => PathBuf::from(target),
+        _ => return Ok(None),
+    };
+
+    let path = path
+        .canonicalize()
+        .map_err(|e| anyhow!("Failed to open local site {}: {}", path.display(), e))?;
+    let start = if path.is_dir() {
+        let index = path.join("index.html");
+        if !index.is_file() {
+            return Err(anyhow!("{} has no index.html, pass the HTML file to start from instead", path.display()));
+        }
+        index
+    } else {
+        path
+    };
+
+    Url::from_file_path(&start)
+        .map(|url| Some(url.to_string()))
+        .map_err(|_| anyhow!("Failed to turn {} into a file:// URL", start.display()))
+}
+
+/// The directory a local crawl's links are relative to, for `file://` start URLs
+pub(crate) fn root(target_url: &str) -> Option<Url> {
+    Url::parse(target_url)
+        .ok()
+        .filter(|url| url.scheme() == "file")
+        .and_then(|url| url.join("./").ok())
+}
+
+/// A link found on the local page at `page_url` as a root-relative path, the
+/// form discovered links take on websites. Links leaving the root, to other
+/// schemes or into the page itself are dropped.
+///
+/// `index.html` and the `.html` extension are left off, so page files are
+/// named like the site's own URLs; [`resolve`] finds the file again.
+pub(crate) fn nav_href(root: &Url, page_url: &Url, href: &str) -> Option<String> {
+    if href.starts_with('#') {
+        return None;
+    }
+    let mut url = if href.starts_with('/') && !href.starts_with("//") {
+        root.join(href.trim_start_matches('/')).ok()?
+    } else {
+        page_url.join(href).ok()?
+    };
+    url.set_fragment(None);
+    url.set_query(None);
+
+    let relative = url.path().strip_prefix(root.path())?;
+    let relative = relative.strip_suffix("index.html").unwrap_or(relative);
+    let relative = relative.strip_suffix(".html").unwrap_or(relative);
+    (url.scheme() == "file").then(|| format!("/{}", relative))
+}
+
+/// The file a root-relative link of a local site points at: the path itself,
+/// with `.html` added, or the `index.html` inside it, whichever exists
+pub(crate) fn resolve(root: &Url, href: &str) -> Option<String> {
+    let relative = href.trim_start_matches('/');
+    let stem = relative.trim_end_matches('/');
+
+    // Joined as URLs, which takes care of percent-encoded names
+    let mut candidates = vec![root.join(relative).ok()?];
+    if stem.is_empty() {
+        candidates.push(root.join("index.html").ok()?);
+    } else {
+        candidates.push(root.join(&format!("{}.html", stem)).ok()?);
+        candidates.push(root.join(&format!("{}/index.html", stem)).ok()?);
+    }
+
+    let found = candidates
+        .iter()
+        .find(|candidate| candidate.to_file_path().is_ok_and(|path| path.is_file()))
+        .unwrap_or(&candidates[0]);
+    Some(found.to_string())
+}
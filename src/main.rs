@@ -1,12 +1,28 @@
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::parser::ValueSource;
+use clap::{ArgAction, ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand};
 use colored::*;
-use book2pdf::{Downloader, PdfMerger};
-use std::path::PathBuf;
+use book2pdf::{natural_sort_key, read_titles_file, CrawlReport, DocPlatform, DownloadError, DownloadOptions, Downloader, Keep, Manifest, MergeOptions, MergeOrder, PageInsert, PageSize, PdfMerger, PdfOptions, PdfSplitter, SlugStyle, Split, TitleSource, ViewportSize};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
 use std::process;
-use tracing::{error, info};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn, Level};
+use tracing_subscriber::filter::Targets;
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 use tokio::fs;
+use tokio::io::AsyncReadExt;
+
+/// Beyond this Chrome's rasterized pages get too large to print reliably
+const MAX_DEVICE_SCALE_FACTOR: f64 = 4.0;
+
+/// The print scales Chrome accepts
+const MIN_PRINT_SCALE: f64 = 0.1;
+const MAX_PRINT_SCALE: f64 = 2.0;
 
 #[derive(Parser)]
 #[command(name = "book2pdf")]
@@ -20,26 +36,7 @@ struct Args {
 #[derive(Subcommand)]
 enum Commands {
     /// Download and convert documentation website to a combined PDF (default behavior)
-    Download {
-        /// URL of the website to scrape
-        url: String,
-
-        /// Output directory used to save files
-#[arg(short = 'o', long = "outDir", default_value = "output_book2pdf")]
-        out_dir: String,
-
-        /// Don't combine PDFs into a single file (by default PDFs are combined)
-        #[arg(long = "no-combine")]
-        no_combine: bool,
-
-        /// Preserve individual page PDFs (by default they are deleted after combining)
-        #[arg(short = 'p', long = "preserve-pages")]
-        preserve_pages: bool,
-
-        /// Request timeout in seconds
-        #[arg(short = 't', long = "timeout", default_value = "30.0", value_parser = parse_timeout)]
-        timeout: f64,
-    },
+    Download(Box<DownloadArgs>),
     /// Merge existing PDF files into a single document
     Merge {
         /// Directory containing PDF files to merge
@@ -49,9 +46,631 @@ enum Commands {
         /// Output file path for the merged PDF
         #[arg(short = 'o', long = "output", default_value = "merged.pdf")]
         output_file: String,
+
+        #[command(flatten)]
+        merge_args: MergeArgs,
+    },
+    /// Split a combined PDF into one file per bookmark, page range or number of pages
+    Split {
+        /// The PDF to split
+        input: PathBuf,
+
+        /// Directory the parts are written to, as NN_<title>.pdf
+        #[arg(short = 'o', long = "outDir", default_value = "split")]
+        out_dir: PathBuf,
+
+        /// How to cut the PDF: bookmarks (one file per top-level bookmark), ranges or count
+        #[arg(long = "by", value_name = "MODE", default_value_t = SplitBy::Bookmarks)]
+        by: SplitBy,
+
+        /// Page ranges for --by ranges, e.g. 1-4,5,6-20
+        #[arg(long = "ranges", value_name = "RANGES")]
+        ranges: Option<String>,
+
+        /// Pages per file for --by count
+        #[arg(long = "count", value_name = "PAGES")]
+        count: Option<usize>,
+    },
+    /// List the documentation platforms book2pdf recognizes and how it detects them
+    Platforms,
+    /// Print the JSON Schema of a JSON file book2pdf writes
+    PrintSchema {
+        /// Which file to describe: stats (--stats-file) or manifest (manifest.json)
+        #[arg(value_name = "FILE", default_value_t = SchemaKind::Stats)]
+        kind: SchemaKind,
     },
 }
 
+/// The ways `split` can cut a PDF
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SplitBy {
+    Bookmarks,
+    Ranges,
+    Count,
+}
+
+impl FromStr for SplitBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "bookmarks" => Ok(SplitBy::Bookmarks),
+            "ranges" => Ok(SplitBy::Ranges),
+            "count" => Ok(SplitBy::Count),
+            other => Err(format!("unknown split mode '{}', expected bookmarks, ranges or count", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for SplitBy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SplitBy::Bookmarks => "bookmarks",
+            SplitBy::Ranges => "ranges",
+            SplitBy::Count => "count",
+        })
+    }
+}
+
+/// `--delay`: a fixed number of milliseconds, or a `MIN-MAX` range to pick from for each page
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "RawDelay", into = "String")]
+struct DelayRange {
+    min: u64,
+    max: u64,
+}
+
+/// A delay in the JSON config: a number of milliseconds or the `--delay` syntax
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawDelay {
+    Millis(u64),
+    Text(String),
+}
+
+impl FromStr for DelayRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid delay '{}' (expected milliseconds or MIN-MAX, e.g. 500-2000)", s);
+        let (min, max) = s.trim().split_once('-').unwrap_or((s, s));
+        let min: u64 = min.trim().parse().map_err(|_| invalid())?;
+        let max: u64 = max.trim().parse().map_err(|_| invalid())?;
+        if min > max {
+            return Err(format!("invalid delay '{}' (the minimum is larger than the maximum)", s));
+        }
+        Ok(Self { min, max })
+    }
+}
+
+impl TryFrom<RawDelay> for DelayRange {
+    type Error = String;
+
+    fn try_from(raw: RawDelay) -> Result<Self, Self::Error> {
+        match raw {
+            RawDelay::Millis(millis) => Ok(Self { min: millis, max: millis }),
+            RawDelay::Text(text) => text.parse(),
+        }
+    }
+}
+
+impl From<DelayRange> for String {
+    fn from(delay: DelayRange) -> Self {
+        delay.to_string()
+    }
+}
+
+impl std::fmt::Display for DelayRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.min == self.max {
+            write!(f, "{}", self.min)
+        } else {
+            write!(f, "{}-{}", self.min, self.max)
+        }
+    }
+}
+
+/// JSON outputs whose schema `print-schema` can describe
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SchemaKind {
+    Stats,
+    Manifest,
+}
+
+impl FromStr for SchemaKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "stats" => Ok(SchemaKind::Stats),
+            "manifest" => Ok(SchemaKind::Manifest),
+            other => Err(format!("unknown schema '{}', expected stats or manifest", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for SchemaKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SchemaKind::Stats => "stats",
+            SchemaKind::Manifest => "manifest",
+        })
+    }
+}
+
+/// Options shared by every command that produces a merged PDF.
+#[derive(clap::Args, Serialize, Deserialize)]
+struct MergeArgs {
+    /// Label pages in the merged PDF: roman numerals for the cover/TOC, arabic for content
+    #[arg(long = "page-labels")]
+    page_labels: bool,
+
+    /// Repair PDFs that fail strict parsing instead of skipping them
+    #[arg(long = "lenient")]
+    lenient: bool,
+
+    /// Resize every page of the merged PDF to one paper size (a3, a4, a5, letter, legal); without
+    /// it every page keeps the size it was printed at, recorded on the page itself
+    #[arg(long = "uniform-page-size", value_name = "FORMAT")]
+    uniform_page_size: Option<PageSize>,
+
+    /// Add bookmarks: "Cover" and "Table of Contents" first, then one per page, nested like the
+    /// site's sidebar when downloading, with any bookmarks of the page's own PDF under it
+    #[arg(long = "bookmarks")]
+    bookmarks: bool,
+
+    /// Pack the merged PDF into object streams with a cross-reference stream (PDF 1.5)
+    #[arg(long = "compress")]
+    compress: bool,
+
+    /// Name page bookmarks after their files (03_getting-started.pdf is "Getting Started")
+    /// instead of the PDFs' titles, for collections of PDFs made elsewhere
+    #[arg(long = "bookmarks-from-filenames", alias = "flatten-bookmarks-from-filenames", requires = "bookmarks")]
+    #[serde(alias = "flatten_bookmarks_from_filenames")]
+    bookmarks_from_filenames: bool,
+
+    /// Open the PDF with nested bookmarks collapsed, showing only the top level (default: all expanded)
+    #[arg(long = "outline-collapsed")]
+    outline_collapsed: bool,
+
+    /// Bookmark titles to use instead of the pages' <title>: one `slug = Title` or `URL = Title` per line
+    #[arg(long = "titles", value_name = "FILE")]
+    titles: Option<PathBuf>,
+
+    /// Remove this suffix (e.g. " | My Docs") from page titles in bookmarks (repeatable)
+    #[arg(long = "strip-title-suffix", value_name = "SUFFIX")]
+    strip_title_suffix: Vec<String>,
+
+    /// Keep only the part of page titles before this separator (e.g. "|")
+    #[arg(long = "title-separator", value_name = "SEPARATOR")]
+    title_separator: Option<String>,
+
+    /// Write the merged PDF's SHA-256 to <output>.sha256 and print it
+    #[arg(long = "checksum")]
+    checksum: bool,
+
+    /// Leave out random IDs and dates (or pin them to SOURCE_DATE_EPOCH) so the same pages always give a byte-identical PDF
+    #[arg(long = "deterministic")]
+    deterministic: bool,
+
+    /// Don't parse the merged PDF again before writing it to check that it has every page
+    #[arg(long = "no-validate")]
+    no_validate: bool,
+
+    /// PDFs parsed at once when merging (default: one per CPU); the output is the same for any value
+    #[arg(long = "merge-threads", value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    merge_threads: Option<u32>,
+
+    /// Tagged PDF for screen readers: keep the pages' structure tags and image alt text
+    /// when merging (download also captures them and reports images without alt text)
+    #[arg(long = "accessible")]
+    accessible: bool,
+
+    /// Embed each page's HTML as a file attachment named after its slug, so the PDF doubles
+    /// as an archive of the source (download saves the HTML next to each page PDF)
+    #[arg(long = "attach-source")]
+    attach_source: bool,
+
+    /// Order of the content pages after the cover and TOC: natural (file names for merge,
+    /// navigation order for download), reverse, or 1-based positions such as 3,1,2 listing every page once
+    #[arg(long = "order", value_name = "ORDER", default_value_t = MergeOrder::Natural)]
+    order: MergeOrder,
+
+    /// Splice FILE in as the INDEX-th content page (1-based, counted after --order and before other
+    /// inserts; one past the last appends), e.g. 3:errata.pdf (repeatable)
+    #[arg(long = "insert", value_name = "INDEX:FILE")]
+    insert: Vec<PageInsert>,
+
+    /// Where page bookmarks and PDF titles come from, the first one a page has wins: h1, title,
+    /// breadcrumb (its last entry) or slug (merge only sees the PDF's title and the slug);
+    /// h1,title,slug when not given
+    #[arg(long = "title-source", value_name = "SOURCES", value_delimiter = ',')]
+    title_source: Vec<TitleSource>,
+
+    /// Record in the PDF's XMP metadata where it came from: the site, the export time (fixed by
+    /// --deterministic), the book2pdf version and every page URL (file names for merge)
+    #[arg(long = "embed-provenance")]
+    embed_provenance: bool,
+}
+
+impl MergeArgs {
+    async fn to_options(&self) -> Result<MergeOptions> {
+        let titles = match &self.titles {
+            Some(path) => read_titles_file(path).await?,
+            None => HashMap::new(),
+        };
+
+        for insert in &self.insert {
+            if !fs::metadata(&insert.path).await.is_ok_and(|metadata| metadata.is_file()) {
+                return Err(anyhow::anyhow!("File to insert {} does not exist", insert.path.display()));
+            }
+        }
+
+        Ok(MergeOptions {
+            page_labels: self.page_labels,
+            lenient: self.lenient,
+            uniform_page_size: self.uniform_page_size,
+            bookmarks: self.bookmarks,
+            compress: self.compress,
+            titles,
+            strip_title_suffixes: self.strip_title_suffix.clone(),
+            title_separator: self.title_separator.clone(),
+            checksum: self.checksum,
+            deterministic: self.deterministic,
+            load_threads: self.merge_threads.map(|threads| threads as usize),
+            accessible: self.accessible,
+            attach_source: self.attach_source,
+            outline_collapsed: self.outline_collapsed,
+            bookmarks_from_filenames: self.bookmarks_from_filenames,
+            skip_validation: self.no_validate,
+            order: self.order.clone(),
+            inserts: self.insert.clone(),
+            title_sources: self.title_source.clone(),
+            embed_provenance: self.embed_provenance,
+            source_url: None,
+            source_pages: Vec::new(),
+            nav_depths: HashMap::new(),
+        })
+    }
+}
+
+/// Options of the `download` subcommand.
+///
+/// The same struct is deserialized from the JSON passed with `--config-stdin`,
+/// so every field name doubles as a config key.
+#[derive(clap::Args, Serialize, Deserialize)]
+struct DownloadArgs {
+    /// URL of the website to scrape, or a local directory or HTML file of a built site (captured through file:// URLs)
+    url: String,
+
+    /// Output directory used to save files
+    #[arg(short = 'o', long = "outDir", default_value = "output_book2pdf")]
+    out_dir: String,
+
+    /// Write this run's outputs into a timestamped subdirectory of the output directory
+    #[arg(long = "timestamp-dir")]
+    timestamp_dir: bool,
+
+    /// Add the run's start time to the combined PDF's name, e.g. docs-example-com-combined-2024-06-01T120000.pdf
+    #[arg(long = "timestamp-name")]
+    timestamp_name: bool,
+
+    /// Which files to keep: the individual pages, the combined PDF, or both (default: combined)
+    #[arg(long = "keep", value_name = "WHAT", conflicts_with_all = ["no_combine", "preserve_pages"])]
+    keep: Option<Keep>,
+
+    /// Don't combine PDFs into a single file; same as `--keep pages`
+    #[arg(long = "no-combine")]
+    no_combine: bool,
+
+    /// Preserve individual page PDFs after combining; same as `--keep both`
+    #[arg(short = 'p', long = "preserve-pages")]
+    preserve_pages: bool,
+
+    /// How long each page gets to load, settle and print before it is retried or skipped, in seconds
+    #[arg(short = 't', long = "timeout", default_value = "30.0", value_parser = parse_timeout)]
+    timeout: f64,
+
+    /// Number of times a failed page is retried before it is skipped
+    #[arg(long = "retries", default_value = "2")]
+    retries: u32,
+
+    /// Fail a page whose address redirects more than this many times within the site (pages that
+    /// redirect to one already captured are skipped as duplicates either way)
+    #[arg(long = "max-redirects", value_name = "N", default_value = "10")]
+    max_redirects: u32,
+
+    /// Exit with code 5 when some pages failed, after writing the PDF of the ones that didn't
+    #[arg(long = "fail-on-error")]
+    fail_on_error: bool,
+
+    /// Delay between page captures in milliseconds, or MIN-MAX to wait a random time in that range
+    /// for each page, spreading requests to hosts that throttle bursts (raised automatically when
+    /// the host keeps failing)
+    #[arg(long = "delay", value_name = "MS", default_value = "0")]
+    delay: DelayRange,
+
+    /// Seed for the random --delay, to repeat a crawl's timing (default: random, logged)
+    #[arg(long = "seed", value_name = "N")]
+    seed: Option<u64>,
+
+    /// Write per-page crawl statistics to this file (CSV if it ends in .csv, JSON otherwise)
+    #[arg(long = "stats-file")]
+    stats_file: Option<PathBuf>,
+
+    /// Save each page's HTML, as prepared for printing, to <outDir>/html/NN_slug.html for debugging
+    #[arg(long = "html-dump")]
+    html_dump: bool,
+
+    /// Don't print the summary of the run (pages captured and failed, output, time taken) at the end
+    #[arg(long = "quiet")]
+    quiet: bool,
+
+    /// Previous run's manifest.json, or its output directory: only pages that were added or changed
+    /// since then are captured
+    #[arg(long = "against")]
+    against: Option<PathBuf>,
+
+    /// With --against, name the combined PDF <site>-changes.pdf: a changelog book of the pages
+    /// whose text differs from the previous export, or that are new
+    #[arg(long = "only-changed", requires = "against")]
+    only_changed: bool,
+
+    /// Reuse page PDFs already in the output's pages directory (kept with --preserve-pages)
+    /// instead of capturing them again; files that don't parse are downloaded again. The directory may
+    /// come from another machine, with a warning if its manifest is for another site or print options
+    #[arg(long = "skip-existing", visible_alias = "resume", conflicts_with_all = ["against", "single_print"])]
+    skip_existing: bool,
+
+    /// Print responsive images at their highest-resolution srcset candidate (larger PDFs)
+    #[arg(long = "hires-images")]
+    hires_images: bool,
+
+    /// Scale each page so its content, including tables and code blocks that scroll
+    /// sideways, fits the paper width (between 0.5 and 1.0, chosen per page)
+    #[arg(long = "fit-width", visible_alias = "auto-scale")]
+    fit_width: bool,
+
+    /// Capture only the first screenful of each page as a single fixed-size PDF page;
+    /// content below the fold is intentionally clipped
+    #[arg(long = "viewport-only", conflicts_with = "fit_width")]
+    viewport_only: bool,
+
+    /// Print each page on a single sheet as tall as its content, without page breaks
+    /// (pages longer than 200 inches are split at that height)
+    #[arg(long = "single-long-page", conflicts_with = "viewport_only")]
+    single_long_page: bool,
+
+    /// Print all pages as one document instead of merging a PDF per page, for consistent
+    /// pagination and fonts; holds the whole book in memory and prints it in one go,
+    /// so large sites take much more RAM and time
+    #[arg(long = "single-print", conflicts_with_all = ["fit_width", "viewport_only", "single_long_page", "against"])]
+    single_print: bool,
+
+    /// Give each page's PDF an outline of its own h1-h3 headings, for the files kept
+    /// with --keep pages/both; with --bookmarks they also nest under each page's bookmark
+    /// in the combined PDF
+    #[arg(long = "output-individual-bookmarks", conflicts_with = "single_print")]
+    output_individual_bookmarks: bool,
+
+    /// Path to the Chrome/Chromium executable (auto-detected by default)
+    #[arg(long = "chrome-path")]
+    chrome_path: Option<PathBuf>,
+
+    /// Launch Chrome with this existing profile directory to reuse its cookies and signed-in
+    /// sessions (SSO); close every Chrome window using the profile first
+    #[arg(long = "user-data-dir", value_name = "PATH")]
+    user_data_dir: Option<PathBuf>,
+
+    /// Capture with this many Chrome instances at once, each taking the next page as soon as it is
+    /// free, for large sites on many-core machines; pages still merge in nav order, --delay
+    /// applies per instance
+    #[arg(long = "browsers", value_name = "N", default_value_t = 1,
+          value_parser = clap::value_parser!(u32).range(1..), conflicts_with_all = ["user_data_dir", "manual_login"])]
+    browsers: u32,
+
+    /// Capture this many pages at once in each browser, each in its own tab; pages still merge in
+    /// nav order, --delay applies per tab
+    #[arg(long = "concurrency", value_name = "N", default_value_t = 1,
+          value_parser = clap::value_parser!(u32).range(1..))]
+    concurrency: u32,
+
+    /// Skip nav entries whose label contains this text (case-insensitive, repeatable)
+    #[arg(long = "skip-link-text", value_name = "SUBSTRING")]
+    skip_link_text: Vec<String>,
+
+    /// Remove elements whose visible text contains this text, e.g. cookie banners (case-insensitive, repeatable)
+    #[arg(long = "remove-text", value_name = "SUBSTRING")]
+    remove_text: Vec<String>,
+
+    /// How page file names are derived from link paths: kebab (lowercase, dashes),
+    /// lower (lowercase, path segments joined by "_") or preserve (original case)
+    #[arg(long = "slug-style", value_name = "STYLE", default_value_t = SlugStyle::Kebab)]
+    slug_style: SlugStyle,
+
+    /// Capture pages that don't settle within the timeout anyway (best effort) instead of failing them
+    #[arg(long = "capture-on-timeout", value_name = "BOOL", default_value_t = true, action = ArgAction::Set)]
+    capture_on_timeout: bool,
+
+    /// Print a page again when Chrome hands back an empty or invalid PDF for it;
+    /// pages that stay empty are left out of the book and reported
+    #[arg(long = "retry-on-empty-pdf", value_name = "BOOL", default_value_t = true, action = ArgAction::Set)]
+    retry_on_empty_pdf: bool,
+
+    /// Don't crawl links whose path contains this prefix (repeatable, replaces the default)
+    #[arg(long = "asset-path", value_name = "PREFIX", default_value = "/assets/")]
+    asset_path: Vec<String>,
+
+    /// Capture the pages listed in this file (one URL or path per line, `#` for comments)
+    /// instead of discovering them from the site's navigation
+    #[arg(long = "links-file", value_name = "PATH")]
+    links_file: Option<PathBuf>,
+
+    /// Capture exactly these pages, in this order, instead of discovering them: comma-separated
+    /// paths or same-origin URLs (repeatable, also as --url)
+    #[arg(long = "only-urls", visible_alias = "url", value_name = "PATHS", value_delimiter = ',', conflicts_with = "links_file")]
+    only_urls: Vec<String>,
+
+    /// Crawl sites that aren't recognized as GitBook, Docusaurus or Mintlify
+    /// (custom themes, newer versions) instead of stopping
+    #[arg(long = "skip-detection")]
+    skip_detection: bool,
+
+    /// Send a HEAD request to every page from the browser, with the site's cookies, before
+    /// crawling and drop the ones that are gone (404/410) or redirect to another site;
+    /// --concurrency requests at a time, each tab waiting --delay between them
+    #[arg(long = "verify-links")]
+    verify_links: bool,
+
+    /// Add the site's expanded sidebar as a page right after the cover
+    #[arg(long = "capture-nav")]
+    capture_nav: bool,
+
+    /// On GitBook sites, take the page order and the combined PDF's bookmark titles from the
+    /// navigation data GitBook embeds in the page instead of its sidebar (which is scraped when
+    /// the data is missing); --titles, --bookmarks-from-filenames and --title-source win
+    #[arg(long = "gitbook-nav-data", alias = "combine-hyperlink-toc-from-gitbook-nav")]
+    gitbook_nav_data: bool,
+
+    /// Start the book with a generated cover page showing the site's logo and title
+    #[arg(long = "cover", value_name = "BOOL", default_value_t = true, action = ArgAction::Set)]
+    cover: bool,
+
+    /// Render this Markdown file (title page, copyright notice, preface...) as a front matter
+    /// page after the cover (repeatable, in order; bookmarked by its first heading)
+    #[arg(long = "front-matter", value_name = "FILE.md")]
+    front_matter: Vec<PathBuf>,
+
+    /// Open the site in the browser window and wait for you to log in (SSO, 2FA...)
+    /// and press Enter before crawling with that session
+    #[arg(long = "manual-login", conflicts_with = "config_stdin")]
+    manual_login: bool,
+
+    /// Limit the main content column to this width in CSS pixels, centered, for shorter lines
+    #[arg(long = "content-max-width", value_name = "PX", value_parser = clap::value_parser!(u32).range(1..))]
+    content_max_width: Option<u32>,
+
+    /// Emulate prefers-reduced-motion and finish CSS animations before printing, so nothing is caught mid-fade
+    #[arg(long = "reduce-motion", value_name = "BOOL", default_value_t = true, action = ArgAction::Set)]
+    reduce_motion: bool,
+
+    /// Print every panel of tab widgets (code samples per language, per-OS steps) under its tab title
+    #[arg(long = "expand-tabs", value_name = "BOOL", default_value_t = true, action = ArgAction::Set)]
+    expand_tabs: bool,
+
+    /// Print code blocks on a light background with dark, high-contrast token colors instead of
+    /// the site's dark code theme (colors are --book2pdf-code-* CSS variables, see --inject-css)
+    #[arg(long = "light-code")]
+    light_code: bool,
+
+    /// Remove the site header (title, top nav) from every page whose header is identical to the
+    /// first page's, so the banner isn't repeated at the start of every section
+    #[arg(long = "dedupe-page-headers", alias = "strip-duplicate-headers-across-pages")]
+    dedupe_page_headers: bool,
+
+    /// Remove "Edit this page" links, "Last updated" lines and "Was this helpful?" widgets
+    #[arg(long = "strip-edit-links")]
+    strip_edit_links: bool,
+
+    /// Remove the site's sidebars, top bar, on-page table of contents and previous/next links
+    #[arg(long = "strip-nav")]
+    strip_nav: bool,
+
+    /// Preset for clean output: --strip-edit-links and --strip-nav (pages already print with
+    /// the site's print styles and, by default, reduced motion)
+    #[arg(long = "clean")]
+    clean: bool,
+
+    /// Print the URL of each link to another site in parentheses after its text, for printed copies
+    #[arg(long = "expand-external-links")]
+    expand_external_links: bool,
+
+    /// Ask before crawling a discovered navigation with more pages than this (often a selector
+    /// that matched footer or pagination links); without a terminal the run stops unless --yes
+    #[arg(long = "confirm-threshold", value_name = "N", default_value_t = 500, value_parser = clap::value_parser!(u32).range(1..))]
+    confirm_threshold: u32,
+
+    /// Crawl navigations above --confirm-threshold without asking
+    #[arg(short = 'y', long = "yes")]
+    yes: bool,
+
+    /// Add this CSS file to every page before printing (repeatable, in order)
+    #[arg(long = "inject-css", value_name = "FILE.css")]
+    inject_css: Vec<PathBuf>,
+
+    /// Load pages with JavaScript disabled: faster and more reproducible for static sites
+    /// that don't need it (warns when a page looks client-rendered)
+    #[arg(long = "no-js", conflicts_with = "hires_images")]
+    no_js: bool,
+
+    /// Block requests to URLs matching this pattern (`*` wildcards) while loading pages, e.g.
+    /// slow third-party widgets that delay rendering (repeatable, added to the default blocklist)
+    #[arg(long = "block", value_name = "PATTERN")]
+    block: Vec<String>,
+
+    /// Block common analytics, ad and chat-widget scripts while loading pages
+    #[arg(long = "default-blocklist", value_name = "BOOL", default_value_t = true, action = ArgAction::Set)]
+    default_blocklist: bool,
+
+    /// Browser window size as WIDTHxHEIGHT; narrower sizes get a site's mobile layout,
+    /// wider ones keep sidebars that collapse below a breakpoint expanded
+    #[arg(long = "viewport", value_name = "WIDTHxHEIGHT", default_value_t = ViewportSize::default())]
+    viewport: ViewportSize,
+
+    /// Device pixels per CSS pixel: 2 or 3 capture images and canvas diagrams at higher
+    /// resolution, at the cost of much larger PDFs
+    #[arg(long = "device-scale-factor", value_name = "N", default_value = "1", value_parser = parse_device_scale_factor)]
+    device_scale_factor: f64,
+
+    /// Print scale of the page content, from 0.1 to 2; --fit-width picks its own
+    #[arg(long = "scale", value_name = "N", default_value = "0.75", value_parser = parse_scale)]
+    scale: f64,
+
+    /// Blank space above the content of every PDF page, in inches
+    #[arg(long = "margin-top", value_name = "INCHES", default_value = "0", value_parser = parse_margin)]
+    margin_top: f64,
+
+    /// Blank space right of the content of every PDF page, in inches
+    #[arg(long = "margin-right", value_name = "INCHES", default_value = "0", value_parser = parse_margin)]
+    margin_right: f64,
+
+    /// Blank space below the content of every PDF page, in inches
+    #[arg(long = "margin-bottom", value_name = "INCHES", default_value = "0", value_parser = parse_margin)]
+    margin_bottom: f64,
+
+    /// Blank space left of the content of every PDF page, in inches
+    #[arg(long = "margin-left", value_name = "INCHES", default_value = "0", value_parser = parse_margin)]
+    margin_left: f64,
+
+    /// Paper every page is printed on: a3, a4, a5, letter or legal
+    #[arg(long = "paper-size", value_name = "FORMAT", default_value_t = PageSize::Letter)]
+    paper_size: PageSize,
+
+    /// Print on paper turned sideways, its long edge on top
+    #[arg(long = "landscape")]
+    landscape: bool,
+
+    #[command(flatten)]
+    #[serde(flatten)]
+    merge_args: MergeArgs,
+
+    /// Read options as a JSON object from stdin (flags given on the command line take precedence)
+    #[arg(long = "config-stdin")]
+    #[serde(skip)]
+    config_stdin: bool,
+
+    /// Log every Chrome DevTools protocol message to --trace-cdp-file, for debugging
+    /// blank pages and hung navigations
+    #[arg(long = "trace-cdp")]
+    #[serde(skip)]
+    trace_cdp: bool,
+
+    /// File the --trace-cdp log is written to (overwritten)
+    #[arg(long = "trace-cdp-file", value_name = "PATH", default_value = "cdp-trace.log")]
+    #[serde(skip)]
+    trace_cdp_file: PathBuf,
+}
+
 fn parse_timeout(s: &str) -> Result<f64, String> {
     let value = s.parse::<f64>().map_err(|_| "Not a number.")?;
     if value < 0.0 {
@@ -60,7 +679,151 @@ fn parse_timeout(s: &str) -> Result<f64, String> {
     Ok(value)
 }
 
-async fn merge_pdfs(input_dir: &str, output_file: &str) -> Result<()> {
+fn parse_device_scale_factor(s: &str) -> Result<f64, String> {
+    let value = s.parse::<f64>().map_err(|_| "Not a number.")?;
+    if !(value > 0.0 && value <= MAX_DEVICE_SCALE_FACTOR) {
+        return Err(format!("Must be greater than 0 and at most {}.", MAX_DEVICE_SCALE_FACTOR));
+    }
+    Ok(value)
+}
+
+fn parse_scale(s: &str) -> Result<f64, String> {
+    let value = s.parse::<f64>().map_err(|_| "Not a number.")?;
+    if !(MIN_PRINT_SCALE..=MAX_PRINT_SCALE).contains(&value) {
+        return Err(format!("Must be between {} and {}.", MIN_PRINT_SCALE, MAX_PRINT_SCALE));
+    }
+    Ok(value)
+}
+
+fn parse_margin(s: &str) -> Result<f64, String> {
+    let value = s.parse::<f64>().map_err(|_| "Not a number.")?;
+    if value < 0.0 {
+        return Err("Must be zero or positive number.".to_string());
+    }
+    Ok(value)
+}
+
+/// Opposite margins have to leave some of the paper for the content
+fn check_margins(pdf_options: &PdfOptions) -> Result<()> {
+    let (width, height) = pdf_options.paper_inches();
+    let paper = format!("{}{}", pdf_options.paper_size, if pdf_options.landscape { " landscape" } else { "" });
+    if pdf_options.margin_left + pdf_options.margin_right >= width {
+        return Err(anyhow::anyhow!("Left and right margins leave no room for content on {} paper ({:.2} inches wide)", paper, width));
+    }
+    if pdf_options.margin_top + pdf_options.margin_bottom >= height {
+        return Err(anyhow::anyhow!("Top and bottom margins leave no room for content on {} paper ({:.2} inches tall)", paper, height));
+    }
+    Ok(())
+}
+
+/// Overlays the JSON config read from stdin onto the parsed CLI arguments.
+///
+/// Config values only replace arguments that were not given explicitly on the command line.
+async fn apply_stdin_config(args: DownloadArgs, matches: &ArgMatches) -> Result<DownloadArgs> {
+    let mut input = String::new();
+    tokio::io::stdin()
+        .read_to_string(&mut input)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to read config from stdin: {}", e))?;
+
+    let config: serde_json::Value = serde_json::from_str(&input)
+        .map_err(|e| anyhow::anyhow!("Malformed JSON config on stdin: {}", e))?;
+    let serde_json::Value::Object(config) = config else {
+        return Err(anyhow::anyhow!("Malformed JSON config on stdin: expected an object"));
+    };
+
+    let mut merged = serde_json::to_value(&args)?;
+    let fields = merged.as_object_mut().expect("DownloadArgs serializes to an object");
+
+    for (key, value) in config {
+        if !fields.contains_key(&key) {
+            return Err(anyhow::anyhow!("Unknown option '{}' in JSON config on stdin", key));
+        }
+        if matches.value_source(&key) != Some(ValueSource::CommandLine) {
+            fields.insert(key, value);
+        }
+    }
+
+    let mut merged: DownloadArgs = serde_json::from_value(merged)
+        .map_err(|e| anyhow::anyhow!("Invalid JSON config on stdin: {}", e))?;
+    // Logging is set up before stdin is read, so these are command line only
+    merged.config_stdin = args.config_stdin;
+    merged.trace_cdp = args.trace_cdp;
+    merged.trace_cdp_file = args.trace_cdp_file;
+
+    // Values from the config skip clap's value parsers, so validate them here
+    parse_timeout(&merged.timeout.to_string())
+        .map_err(|e| anyhow::anyhow!("Invalid 'timeout' in JSON config on stdin: {}", e))?;
+    parse_device_scale_factor(&merged.device_scale_factor.to_string())
+        .map_err(|e| anyhow::anyhow!("Invalid 'device_scale_factor' in JSON config on stdin: {}", e))?;
+    parse_scale(&merged.scale.to_string())
+        .map_err(|e| anyhow::anyhow!("Invalid 'scale' in JSON config on stdin: {}", e))?;
+    for (name, margin) in [
+        ("margin_top", merged.margin_top),
+        ("margin_right", merged.margin_right),
+        ("margin_bottom", merged.margin_bottom),
+        ("margin_left", merged.margin_left),
+    ] {
+        parse_margin(&margin.to_string())
+            .map_err(|e| anyhow::anyhow!("Invalid '{}' in JSON config on stdin: {}", name, e))?;
+    }
+    if merged.viewport_only && merged.fit_width {
+        return Err(anyhow::anyhow!("'viewport_only' cannot be combined with 'fit_width'"));
+    }
+    if merged.keep.is_some() && (merged.no_combine || merged.preserve_pages) {
+        return Err(anyhow::anyhow!("'keep' cannot be combined with 'no_combine' or 'preserve_pages'"));
+    }
+    if merged.viewport_only && merged.single_long_page {
+        return Err(anyhow::anyhow!("'viewport_only' cannot be combined with 'single_long_page'"));
+    }
+    if merged.links_file.is_some() && !merged.only_urls.is_empty() {
+        return Err(anyhow::anyhow!("'only_urls' cannot be combined with 'links_file'"));
+    }
+    if merged.single_print && (merged.fit_width || merged.viewport_only || merged.single_long_page || merged.against.is_some()) {
+        return Err(anyhow::anyhow!("'single_print' cannot be combined with 'fit_width', 'viewport_only', 'single_long_page' or 'against'"));
+    }
+    if merged.only_changed && merged.against.is_none() {
+        return Err(anyhow::anyhow!("'only_changed' needs 'against'"));
+    }
+    if merged.skip_existing && (merged.against.is_some() || merged.single_print) {
+        return Err(anyhow::anyhow!("'skip_existing' cannot be combined with 'against' or 'single_print'"));
+    }
+    if merged.single_print && merged.output_individual_bookmarks {
+        return Err(anyhow::anyhow!("'single_print' cannot be combined with 'output_individual_bookmarks'"));
+    }
+    if merged.no_js && merged.hires_images {
+        return Err(anyhow::anyhow!("'no_js' cannot be combined with 'hires_images'"));
+    }
+    if merged.merge_args.bookmarks_from_filenames && !merged.merge_args.bookmarks {
+        return Err(anyhow::anyhow!("'bookmarks_from_filenames' needs 'bookmarks'"));
+    }
+    if merged.browsers == 0 {
+        return Err(anyhow::anyhow!("Invalid 'browsers' in JSON config on stdin: must be at least 1"));
+    }
+    if merged.concurrency == 0 {
+        return Err(anyhow::anyhow!("Invalid 'concurrency' in JSON config on stdin: must be at least 1"));
+    }
+    if merged.browsers > 1 && merged.user_data_dir.is_some() {
+        return Err(anyhow::anyhow!("'browsers' cannot be combined with 'user_data_dir': Chrome opens a profile in one instance only"));
+    }
+    if merged.browsers > 1 && merged.manual_login {
+        return Err(anyhow::anyhow!("'browsers' cannot be combined with 'manual_login': only the first browser would be logged in"));
+    }
+    if merged.merge_args.merge_threads == Some(0) {
+        return Err(anyhow::anyhow!("Invalid 'merge_threads' in JSON config on stdin: must be at least 1"));
+    }
+    if merged.content_max_width == Some(0) {
+        return Err(anyhow::anyhow!("Invalid 'content_max_width' in JSON config on stdin: must be at least 1"));
+    }
+    if merged.manual_login {
+        return Err(anyhow::anyhow!("'manual_login' waits for Enter on stdin and cannot be combined with --config-stdin"));
+    }
+
+    Ok(merged)
+}
+
+async fn merge_pdfs(input_dir: &str, output_file: &str, merge_args: &MergeArgs) -> Result<()> {
+    let options = merge_args.to_options().await?;
     let input_path = PathBuf::from(input_dir);
     
     if !input_path.exists() {
@@ -85,61 +848,342 @@ async fn merge_pdfs(input_dir: &str, output_file: &str) -> Result<()> {
         return Err(anyhow::anyhow!("No PDF files found in '{}'", input_dir));
     }
     
-    // Sort by filename to maintain order (especially numbered files)
-    pdf_files.sort();
+    // Numbered files in numeric order, so 100_x.pdf comes after 11_x.pdf; any
+    // unnumbered ones after them, by name
+    pdf_files.sort_by_cached_key(|path| natural_sort_key(path));
     
+    // Numbered like --order counts them, after the front matter
+    let front_matter = PdfMerger::front_matter_count(&pdf_files);
     info!("Found {} PDF files to merge:", pdf_files.len());
     for (i, path) in pdf_files.iter().enumerate() {
-        info!("  {}: {}", i + 1, path.file_name().unwrap().to_string_lossy().blue());
+        let name = path.file_name().unwrap().to_string_lossy().blue();
+        if i < front_matter {
+            info!("  front matter: {}", name);
+        } else {
+            info!("  {}: {}", i + 1 - front_matter, name);
+        }
     }
     
-    let mut merger = PdfMerger::new();
-    
-    for pdf_path in &pdf_files {
-        info!("Adding: {}", pdf_path.display());
-        if let Err(e) = merger.add_pdf(pdf_path).await {
-            error!("Failed to add PDF {}: {}", pdf_path.display(), e);
+    let result = PdfMerger::merge_files(&pdf_files, Path::new(output_file), options)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to merge '{}': {}", input_dir, e))?;
+
+    if result.skipped.is_empty() {
+        info!("Successfully merged {} PDFs into: {}", 
+              result.merged.len(), 
+              result.output.display().to_string().green());
+    } else {
+        warn!("Merged {} of {} PDFs into: {}",
+              result.merged.len(),
+              pdf_files.len(),
+              result.output.display().to_string().yellow());
+        warn!("Skipped {} files:", result.skipped.len());
+        for (path, e) in &result.skipped {
+            warn!("  {}: {}", path.display(), e);
         }
     }
-    
-    let output_path = PathBuf::from(output_file);
-    merger.save(&output_path).await?;
-    
-    info!("Successfully merged {} PDFs into: {}", 
-          pdf_files.len(), 
-          output_path.display().to_string().green());
+    if let Some(sha256) = &result.sha256 {
+        info!("SHA-256: {}", sha256);
+    }
     
     Ok(())
 }
 
-#[tokio::main]
-async fn main() {
-    // Set up logging with chromiumoxide errors suppressed
+async fn split_pdf(input: &Path, out_dir: &Path, by: SplitBy, ranges: Option<&str>, count: Option<usize>) -> Result<()> {
+    let split = match (by, ranges, count) {
+        (SplitBy::Bookmarks, None, None) => Split::Bookmarks,
+        (SplitBy::Ranges, Some(ranges), None) => Split::Ranges(parse_page_ranges(ranges)?),
+        (SplitBy::Count, None, Some(count)) => Split::Count(count),
+        (SplitBy::Ranges, None, _) => return Err(anyhow::anyhow!("--by ranges needs --ranges, e.g. --ranges 1-4,5-20")),
+        (SplitBy::Count, _, None) => return Err(anyhow::anyhow!("--by count needs --count <PAGES>")),
+        _ => return Err(anyhow::anyhow!("--ranges only works with --by ranges and --count with --by count")),
+    };
+
+    info!("Splitting {} by {}", input.display().to_string().green(), by);
+    let written = PdfSplitter::split_file(input, out_dir, &split)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to split '{}': {}", input.display(), e))?;
+
+    info!("{}", format!("Split {} into {} files in {}", input.display(), written.len(), out_dir.display()).green());
+    Ok(())
+}
+
+/// Parses `1-4,5,6-20` into inclusive page ranges
+fn parse_page_ranges(ranges: &str) -> Result<Vec<RangeInclusive<usize>>> {
+    ranges
+        .split(',')
+        .map(|range| {
+            let range = range.trim();
+            let (start, end) = range.split_once('-').unwrap_or((range, range));
+            match (start.trim().parse(), end.trim().parse()) {
+                (Ok(start), Ok(end)) => Ok(start..=end),
+                _ => Err(anyhow::anyhow!("Invalid page range '{}', expected e.g. 3 or 1-4", range)),
+            }
+        })
+        .collect()
+}
+
+/// Prints the JSON Schema of a JSON output to stdout, for validating it downstream
+fn print_schema(kind: SchemaKind) -> Result<()> {
+    let schema = match kind {
+        SchemaKind::Stats => schemars::schema_for!(CrawlReport),
+        SchemaKind::Manifest => schemars::schema_for!(Manifest),
+    };
+    println!("{}", serde_json::to_string_pretty(&schema)?);
+    Ok(())
+}
+
+fn print_platforms() {
+    for platform in DocPlatform::ALL {
+        println!("{} ({})", platform.to_string().bold(), platform.maturity());
+        for marker in platform.markers() {
+            println!("  {}", marker);
+        }
+    }
+    println!();
+    println!("A site is detected by any one of its platform's markers. Use --skip-detection to crawl other sites anyway.");
+}
+
+async fn run_download(mut args: DownloadArgs, matches: &ArgMatches) -> Result<()> {
+    let started = Instant::now();
+    if args.config_stdin {
+        args = apply_stdin_config(args, matches).await?;
+    }
+
+    let mut inject_css = Vec::new();
+    for path in &args.inject_css {
+        let css = fs::read_to_string(path)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to read CSS file {}: {}", path.display(), e))?;
+        inject_css.push(css);
+    }
+
+    let options = DownloadOptions {
+        retries: args.retries,
+        max_redirects: args.max_redirects,
+        delay: Duration::from_millis(args.delay.min),
+        delay_jitter: Duration::from_millis(args.delay.max - args.delay.min),
+        seed: args.seed,
+        merge: args.merge_args.to_options().await?,
+        stats_file: args.stats_file,
+        html_dump: args.html_dump,
+        against: args.against,
+        only_changed: args.only_changed,
+        skip_existing: args.skip_existing,
+        timestamp_dir: args.timestamp_dir,
+        timestamp_name: args.timestamp_name,
+        hires_images: args.hires_images,
+        fit_width: args.fit_width,
+        viewport_only: args.viewport_only,
+        single_long_page: args.single_long_page,
+        single_print: args.single_print,
+        page_outlines: args.output_individual_bookmarks,
+        chrome_path: args.chrome_path,
+        user_data_dir: args.user_data_dir,
+        browsers: args.browsers as usize,
+        concurrency: args.concurrency as usize,
+        skip_link_text: args.skip_link_text,
+        asset_paths: args.asset_path,
+        links_file: args.links_file,
+        only_urls: args.only_urls,
+        skip_detection: args.skip_detection,
+        verify_links: args.verify_links,
+        capture_nav: args.capture_nav,
+        gitbook_nav_data: args.gitbook_nav_data,
+        cover: args.cover,
+        front_matter: args.front_matter,
+        remove_text: args.remove_text,
+        slug_style: args.slug_style,
+        capture_on_timeout: args.capture_on_timeout,
+        retry_empty_pdf: args.retry_on_empty_pdf,
+        manual_login: args.manual_login,
+        content_max_width: args.content_max_width,
+        reduce_motion: args.reduce_motion,
+        expand_tabs: args.expand_tabs,
+        light_code: args.light_code,
+        dedupe_page_headers: args.dedupe_page_headers,
+        strip_edit_links: args.strip_edit_links || args.clean,
+        strip_nav: args.strip_nav || args.clean,
+        expand_external_links: args.expand_external_links,
+        confirm_threshold: args.confirm_threshold as usize,
+        assume_yes: args.yes,
+        inject_css,
+        no_js: args.no_js,
+        block_urls: args.block,
+        default_blocklist: args.default_blocklist,
+        viewport: args.viewport,
+        device_scale_factor: args.device_scale_factor,
+        accessible: args.merge_args.accessible,
+    };
+    let pdf_options = PdfOptions {
+        scale: args.scale,
+        margin_top: args.margin_top,
+        margin_right: args.margin_right,
+        margin_bottom: args.margin_bottom,
+        margin_left: args.margin_left,
+        paper_size: args.paper_size,
+        landscape: args.landscape,
+    };
+    check_margins(&pdf_options)?;
+    // --no-combine and --preserve-pages are the older spelling of --keep
+    let mut downloader = Downloader::new(args.out_dir, !args.no_combine, args.preserve_pages, args.timeout)
+        .with_options(options)
+        .with_pdf_options(pdf_options);
+    if let Some(keep) = args.keep {
+        downloader = downloader.with_keep(keep);
+    }
+    let report = match downloader.run(&args.url).await {
+        Ok(report) => report,
+        Err(e) => {
+            if !args.quiet {
+                print_failure_summary(&e, started.elapsed());
+            }
+            return Err(e);
+        }
+    };
+    if !args.quiet {
+        print_summary(&report, started.elapsed());
+    }
+
+    let failed = report.failed();
+    if args.fail_on_error && failed > 0 {
+        return Err(DownloadError::PagesFailed { failed, pages: report.pages.len() }.into());
+    }
+    Ok(())
+}
+
+/// Prints the end-of-run summary to stderr, where the log goes too
+fn print_summary(report: &CrawlReport, elapsed: Duration) {
+    let skipped = report.skipped();
+    let captured = report.succeeded() - skipped;
+    let failed = report.failed();
+
+    eprintln!();
+    eprintln!("{}", "Summary".bold());
+    eprintln!("  Platform:      {}", report.platform.as_deref().unwrap_or("not detected"));
+    eprintln!(
+        "  Pages:         {} attempted, {} captured, {} skipped, {}",
+        report.pages.len(),
+        captured.to_string().green(),
+        skipped,
+        match failed {
+            0 => "0 failed".normal(),
+            failed => format!("{} failed", failed).red(),
+        }
+    );
+    if !report.pruned_links.is_empty() {
+        eprintln!("  Links dropped: {} (--verify-links)", report.pruned_links.len());
+    }
+    eprintln!("  Duplicates:    {} nav links dropped", report.duplicate_links);
+    if let Some(page_count) = report.combined_page_count {
+        eprintln!("  Combined PDF:  {} pages", page_count);
+    }
+    if let Some(path) = &report.output_path {
+        eprintln!("  Output:        {}", path.display().to_string().blue());
+    }
+    eprintln!("  Elapsed:       {:.1}s", elapsed.as_secs_f64());
+}
+
+/// The end-of-run summary of a run that stopped with `error`, which is logged
+/// right after it
+fn print_failure_summary(error: &anyhow::Error, elapsed: Duration) {
+    eprintln!();
+    eprintln!("{}", "Summary".bold());
+    if let Some(DownloadError::AllPagesFailed { pages }) = error.downcast_ref::<DownloadError>() {
+        eprintln!("  Pages:         {} attempted, 0 captured, {}", pages, format!("{} failed", pages).red());
+    }
+    eprintln!("  Result:        {}", "failed, see the error below".red());
+    eprintln!("  Elapsed:       {:.1}s", elapsed.as_secs_f64());
+}
+
+/// The exit code for an error, so scripts can tell failure classes apart
+/// (documented in the README)
+fn exit_code(error: &anyhow::Error) -> i32 {
+    match error.downcast_ref::<DownloadError>() {
+        Some(DownloadError::UnsupportedSite) => 2,
+        Some(DownloadError::BrowserLaunch(_)) => 3,
+        Some(DownloadError::AllPagesFailed { .. }) => 4,
+        Some(DownloadError::PagesFailed { .. }) => 5,
+        None => 1,
+    }
+}
+
+/// Sets up logging to stderr with chromiumoxide's noisy targets suppressed.
+///
+/// With `cdp_trace`, those targets are logged at trace level, which includes
+/// every protocol message sent and received, to that file instead.
+fn init_logging(cdp_trace: Option<&Path>) -> Result<()> {
     let filter = EnvFilter::from_default_env()
         .add_directive("chromiumoxide::conn=off".parse().unwrap())
         .add_directive("chromiumoxide::handler=off".parse().unwrap())
         .add_directive("book2pdf=info".parse().unwrap());
-    
+
+    let trace_layer = match cdp_trace {
+        Some(path) => {
+            let file = std::fs::File::create(path)
+                .map_err(|e| anyhow::anyhow!("Failed to create CDP trace file {}: {}", path.display(), e))?;
+            let targets = Targets::new()
+                .with_target("chromiumoxide::conn", Level::TRACE)
+                .with_target("chromiumoxide::handler", Level::TRACE);
+            Some(fmt::layer().with_writer(Mutex::new(file)).with_ansi(false).with_filter(targets))
+        }
+        None => None,
+    };
+
     tracing_subscriber::registry()
-        .with(fmt::layer())
-        .with(filter)
+        .with(fmt::layer().with_filter(filter))
+        .with(trace_layer)
         .init();
+    Ok(())
+}
 
-    let args = Args::parse();
+/// Prints clap's usage error (or `--help`/`--version` output) and exits.
+///
+/// clap itself exits with 2 on usage errors, which is taken by unsupported sites.
+fn exit_usage(e: clap::Error) -> ! {
+    e.print().ok();
+    process::exit(if e.use_stderr() { 1 } else { 0 });
+}
+
+#[tokio::main]
+async fn main() {
+    let matches = Args::command().try_get_matches().unwrap_or_else(|e| exit_usage(e));
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|e| exit_usage(e));
+
+    let cdp_trace = match &args.command {
+        Commands::Download(download_args) if download_args.trace_cdp => Some(download_args.trace_cdp_file.as_path()),
+        _ => None,
+    };
+    if let Err(e) = init_logging(cdp_trace) {
+        eprintln!("{}", format!("Error: {}", e).red());
+        process::exit(1);
+    }
+    if let Some(path) = cdp_trace {
+        info!("Logging Chrome DevTools protocol traffic to {}", path.display());
+    }
 
     let result = match args.command {
-        Commands::Download { url, out_dir, no_combine, preserve_pages, timeout } => {
-            let combine = !no_combine; // Invert the logic: combine by default
-            let downloader = Downloader::new(out_dir, combine, preserve_pages, timeout);
-            downloader.run(&url).await
+        Commands::Download(download_args) => {
+            let download_matches = matches
+                .subcommand_matches("download")
+                .expect("download subcommand was matched");
+            run_download(*download_args, download_matches).await
+        }
+        Commands::Merge { input_dir, output_file, merge_args } => {
+            merge_pdfs(&input_dir, &output_file, &merge_args).await
         }
-        Commands::Merge { input_dir, output_file } => {
-            merge_pdfs(&input_dir, &output_file).await
+        Commands::Split { input, out_dir, by, ranges, count } => {
+            split_pdf(&input, &out_dir, by, ranges.as_deref(), count).await
+        }
+        Commands::PrintSchema { kind } => print_schema(kind),
+        Commands::Platforms => {
+            print_platforms();
+            Ok(())
         }
     };
 
     if let Err(e) = result {
         error!("{}", format!("Error: {}", e).red());
-        process::exit(1);
+        process::exit(exit_code(&e));
     }
 }
\ No newline at end of file
@@ -0,0 +1,75 @@
+use anyhow::{anyhow, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::Path;
+use tokio::fs;
+
+use crate::atomic_write::write_atomic;
+
+/// File name of the manifest written next to the combined PDF
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// A page that was part of a run
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ManifestEntry {
+    pub url: String,
+    pub slug: String,
+    /// SHA-256 of the page's rendered text, used to detect changes between runs
+    pub content_hash: Option<String>,
+    /// Page PDF relative to the output directory, if it was kept
+    pub file: Option<String>,
+}
+
+/// Sidecar describing which pages make up an export
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Manifest {
+    pub base_url: String,
+    /// Hash of the options that change how pages print, so a run reusing the
+    /// pages can tell whether they were captured the same way
+    #[serde(default)]
+    pub options_hash: Option<String>,
+    /// Combined PDF relative to the output directory
+    pub combined: Option<String>,
+    pub pages: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    pub async fn load(path: &Path) -> Result<Self> {
+        let data = fs::read_to_string(path)
+            .await
+            .map_err(|e| anyhow!("Failed to read manifest {}: {}", path.display(), e))?;
+
+        serde_json::from_str(&data)
+            .map_err(|e| anyhow!("Failed to parse manifest {}: {}", path.display(), e))
+    }
+
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        let data = serde_json::to_string_pretty(self)?;
+        write_atomic(path, data)
+            .await
+            .map_err(|e| anyhow!("Failed to write manifest {}: {}", path.display(), e))
+    }
+
+    /// Content hashes keyed by page URL
+    pub fn hashes(&self) -> HashMap<&str, Option<&str>> {
+        self.pages
+            .iter()
+            .map(|entry| (entry.url.as_str(), entry.content_hash.as_deref()))
+            .collect()
+    }
+}
+
+/// How the current crawl differs from a previous manifest
+#[derive(Debug, Clone, Default)]
+pub struct ManifestDiff {
+    pub added: usize,
+    pub removed: usize,
+    pub changed: usize,
+    pub unchanged: usize,
+}
+
+pub fn content_hash(text: &str) -> String {
+    format!("{:x}", Sha256::digest(text.as_bytes()))
+}
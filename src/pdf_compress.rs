@@ -0,0 +1,214 @@
+use anyhow::{anyhow, Result};
+use lopdf::{dictionary, Dictionary, Document, Object, Stream, StringFormat};
+use std::io::Write;
+
+/// Objects packed into each object stream
+const OBJECTS_PER_STREAM: usize = 100;
+
+/// Trailer keys that describe the xref section itself and must not be copied
+const XREF_KEYS: [&[u8]; 9] = [
+    b"Size", b"Prev", b"XRefStm", b"Type", b"W", b"Index", b"Filter", b"DecodeParms", b"Length",
+];
+
+/// Entry of the cross-reference stream, indexed by object number
+#[derive(Clone, Copy)]
+enum XrefEntry {
+    Free,
+    Direct { offset: usize, generation: u16 },
+    Packed { container: u32, index: usize },
+}
+
+/// Serializes a document using PDF 1.5 object streams and a cross-reference stream.
+///
+/// lopdf only writes classic xref tables, so this drops unused objects, deflates
+/// the remaining streams and writes the file itself: every non-stream object goes
+/// into a compressed object stream, streams are written directly, and a deflated
+/// cross-reference stream replaces the xref table and trailer.
+///
+/// Empty streams are kept: an empty form is a valid annotation appearance, and
+/// deleting it would leave link annotations with a dangling `/AP`.
+pub(crate) fn save_compressed(doc: &mut Document) -> Result<Vec<u8>> {
+    doc.prune_objects();
+    doc.renumber_objects();
+    doc.compress();
+
+    let mut direct = Vec::new();
+    let mut packed = Vec::new();
+    for (&(id, generation), object) in &doc.objects {
+        let is_container = object
+            .type_name()
+            .is_ok_and(|name| ["ObjStm", "XRef", "Linearized"].contains(&name));
+        match object {
+            _ if is_container => {}
+            // Streams and objects with a generation can't live in an object stream
+            Object::Stream(_) => direct.push((id, generation, object)),
+            _ if generation != 0 => direct.push((id, generation, object)),
+            _ => packed.push((id, object)),
+        }
+    }
+
+    let mut next_id = doc.max_id + 1;
+    let mut containers = Vec::new();
+    let mut xref = vec![XrefEntry::Free; next_id as usize];
+
+    for chunk in packed.chunks(OBJECTS_PER_STREAM) {
+        let container = next_id;
+        next_id += 1;
+
+        let mut offsets = Vec::new();
+        let mut body = Vec::new();
+        for (index, (id, object)) in chunk.iter().enumerate() {
+            offsets.extend_from_slice(format!("{} {} ", id, body.len()).as_bytes());
+            write_object(&mut body, object)?;
+            body.push(b'\n');
+            xref[*id as usize] = XrefEntry::Packed { container, index };
+        }
+
+        let first = offsets.len();
+        offsets.extend_from_slice(&body);
+        let mut stream = Stream::new(
+            dictionary! {
+                "Type" => "ObjStm",
+                "N" => chunk.len() as i64,
+                "First" => first as i64,
+            },
+            offsets,
+        );
+        stream
+            .compress()
+            .map_err(|e| anyhow!("Failed to compress object stream: {}", e))?;
+        containers.push((container, Object::Stream(stream)));
+    }
+
+    let version = if doc.version.as_str() < "1.5" { "1.5" } else { doc.version.as_str() };
+    let mut data = Vec::new();
+    writeln!(data, "%PDF-{}", version)?;
+    data.extend_from_slice(b"%\xE2\xE3\xCF\xD3\n");
+
+    xref.resize(next_id as usize + 1, XrefEntry::Free);
+    let objects = direct
+        .into_iter()
+        .chain(containers.iter().map(|(id, object)| (*id, 0, object)));
+    for (id, generation, object) in objects {
+        xref[id as usize] = XrefEntry::Direct { offset: data.len(), generation };
+        writeln!(data, "{} {} obj", id, generation)?;
+        write_object(&mut data, object)?;
+        data.extend_from_slice(b"\nendobj\n");
+    }
+
+    // The cross-reference stream lists itself as the last object
+    let xref_id = next_id;
+    let xref_offset = data.len();
+    xref[xref_id as usize] = XrefEntry::Direct { offset: xref_offset, generation: 0 };
+
+    let mut entries = Vec::with_capacity(xref.len() * 7);
+    for entry in &xref {
+        let (kind, field2, field3) = match *entry {
+            XrefEntry::Free => (0u8, 0u32, 0xFFFFu16),
+            XrefEntry::Direct { offset, generation } => (1, offset as u32, generation),
+            XrefEntry::Packed { container, index } => (2, container, index as u16),
+        };
+        entries.push(kind);
+        entries.extend_from_slice(&field2.to_be_bytes());
+        entries.extend_from_slice(&field3.to_be_bytes());
+    }
+
+    let mut xref_dict = dictionary! {
+        "Type" => "XRef",
+        "Size" => xref.len() as i64,
+        "W" => vec![1.into(), 4.into(), 2.into()],
+    };
+    for (key, value) in doc.trailer.iter() {
+        if !XREF_KEYS.contains(&key.as_slice()) {
+            xref_dict.set(key.clone(), value.clone());
+        }
+    }
+    let mut xref_stream = Stream::new(xref_dict, entries);
+    xref_stream
+        .compress()
+        .map_err(|e| anyhow!("Failed to compress cross-reference stream: {}", e))?;
+
+    writeln!(data, "{} 0 obj", xref_id)?;
+    write_object(&mut data, &Object::Stream(xref_stream))?;
+    write!(data, "\nendobj\nstartxref\n{}\n%%EOF\n", xref_offset)?;
+
+    Ok(data)
+}
+
+fn write_object(out: &mut Vec<u8>, object: &Object) -> Result<()> {
+    match object {
+        Object::Null => out.extend_from_slice(b"null"),
+        Object::Boolean(value) => write!(out, "{}", value)?,
+        Object::Integer(value) => write!(out, "{}", value)?,
+        Object::Real(value) => write!(out, "{}", value)?,
+        Object::Name(name) => write_name(out, name),
+        Object::String(text, format) => write_string(out, text, format),
+        Object::Array(array) => {
+            out.push(b'[');
+            for (i, item) in array.iter().enumerate() {
+                if i > 0 {
+                    out.push(b' ');
+                }
+                write_object(out, item)?;
+            }
+            out.push(b']');
+        }
+        Object::Dictionary(dict) => write_dictionary(out, dict)?,
+        Object::Stream(stream) => {
+            let mut dict = stream.dict.clone();
+            dict.set("Length", stream.content.len() as i64);
+            write_dictionary(out, &dict)?;
+            out.extend_from_slice(b"\nstream\n");
+            out.extend_from_slice(&stream.content);
+            out.extend_from_slice(b"\nendstream");
+        }
+        Object::Reference((id, generation)) => write!(out, "{} {} R", id, generation)?,
+    }
+    Ok(())
+}
+
+fn write_dictionary(out: &mut Vec<u8>, dict: &Dictionary) -> Result<()> {
+    out.extend_from_slice(b"<<");
+    for (key, value) in dict.iter() {
+        write_name(out, key);
+        out.push(b' ');
+        write_object(out, value)?;
+    }
+    out.extend_from_slice(b">>");
+    Ok(())
+}
+
+fn write_name(out: &mut Vec<u8>, name: &[u8]) {
+    out.push(b'/');
+    for &byte in name {
+        // Whitespace, delimiters and anything outside printable ASCII need # escapes
+        if b"()<>[]{}/%#".contains(&byte) || !(33..=126).contains(&byte) {
+            out.extend_from_slice(format!("#{:02X}", byte).as_bytes());
+        } else {
+            out.push(byte);
+        }
+    }
+}
+
+fn write_string(out: &mut Vec<u8>, text: &[u8], format: &StringFormat) {
+    match format {
+        StringFormat::Literal => {
+            out.push(b'(');
+            for &byte in text {
+                match byte {
+                    b'(' | b')' | b'\\' => out.extend_from_slice(&[b'\\', byte]),
+                    b'\r' => out.extend_from_slice(b"\\r"),
+                    _ => out.push(byte),
+                }
+            }
+            out.push(b')');
+        }
+        StringFormat::Hexadecimal => {
+            out.push(b'<');
+            for &byte in text {
+                out.extend_from_slice(format!("{:02X}", byte).as_bytes());
+            }
+            out.push(b'>');
+        }
+    }
+}
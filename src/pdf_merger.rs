@@ -1,60 +1,604 @@
 use anyhow::{anyhow, Result};
-use lopdf::{Document, Object};
-use std::path::Path;
+use futures_util::{stream, StreamExt};
+use lopdf::{dictionary, Document, Object, ObjectId, Stream, StringFormat};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use tokio::fs;
-use tracing::{debug, info};
+use tracing::{debug, error, info, warn};
+
+use crate::atomic_write::write_atomic;
+use crate::pdf_compress;
+use crate::pdf_outline::{self, OutlineNode};
+use crate::pdf_provenance::{self, Provenance};
+use crate::pdf_repair;
+
+#[derive(Debug, Clone, Default)]
+pub struct MergeOptions {
+    /// Write a `/PageLabels` tree: roman numerals for front matter, arabic for content
+    pub page_labels: bool,
+    /// Try to repair PDFs that fail strict parsing instead of rejecting them
+    pub lenient: bool,
+    /// Resize every page to this paper size, scaling and centering its content
+    pub uniform_page_size: Option<PageSize>,
+    /// Write an outline with one bookmark per merged document, led by the cover and TOC;
+    /// a content document's own outline is nested under its bookmark
+    pub bookmarks: bool,
+    /// Write object streams and a cross-reference stream (PDF 1.5) to shrink the output
+    pub compress: bool,
+    /// Bookmark titles keyed by page slug (or page URL when merging a crawl),
+    /// used instead of the page's `<title>`
+    pub titles: HashMap<String, String>,
+    /// Suffixes such as " | My Docs" removed from the pages' `<title>` in bookmarks
+    pub strip_title_suffixes: Vec<String>,
+    /// Keep only the part of a page's `<title>` before this separator
+    pub title_separator: Option<String>,
+    /// Write a `<output>.sha256` sidecar with the merged PDF's checksum
+    pub checksum: bool,
+    /// Drop creation/modification dates, XMP metadata and the file ID, so the
+    /// same pages always produce a byte-identical PDF. `embed_provenance`
+    /// still writes its XMP, dated `SOURCE_DATE_EPOCH` or not at all
+    pub deterministic: bool,
+    /// Documents parsed at once by `add_pdfs`, one per CPU when unset. Only
+    /// loading runs in parallel; the merge itself keeps the given order
+    pub load_threads: Option<usize>,
+    /// Combine the documents' structure trees (tagged PDF) so screen readers
+    /// keep the reading order and image descriptions of every page
+    pub accessible: bool,
+    /// Embed the `.html` file next to each document (the downloader writes one
+    /// per page with this option) as an attachment named after its slug
+    pub attach_source: bool,
+    /// Open bookmarks that have nested ones closed, so readers first see only
+    /// the top level
+    pub outline_collapsed: bool,
+    /// Name content bookmarks after their file (`03_getting-started.pdf` becomes
+    /// "Getting Started") instead of the PDF's title, for PDFs from elsewhere
+    /// whose titles are missing or meaningless
+    pub bookmarks_from_filenames: bool,
+    /// Don't parse the merged PDF before writing it to check that it has every page
+    pub skip_validation: bool,
+    /// Order of the content documents in `merge_files` and when combining a crawl
+    pub order: MergeOrder,
+    /// Extra PDFs spliced in among the content documents once they are in `order`
+    pub inserts: Vec<PageInsert>,
+    /// Where content bookmarks take their titles from, first available wins;
+    /// [`TitleSource::DEFAULT_CHAIN`] when empty. Explicit `titles` and
+    /// `bookmarks_from_filenames` still come first
+    pub title_sources: Vec<TitleSource>,
+    /// Write XMP metadata recording where the pages came from (`source_url`,
+    /// `source_pages`), when they were merged and by which book2pdf version
+    pub embed_provenance: bool,
+    /// Site the pages were captured from, for `embed_provenance`
+    pub source_url: Option<String>,
+    /// URLs of the content pages in book order, for `embed_provenance`; the
+    /// content documents' file names are listed when empty
+    pub source_pages: Vec<String>,
+    /// How deeply the site's navigation nests each content page, by slug; with
+    /// `bookmarks` a page's bookmark goes under the closest earlier one that
+    /// sits less deep
+    pub nav_depths: HashMap<String, usize>,
+}
+
+/// Page attributes a page can inherit from its ancestors in the page tree
+const INHERITABLE_ATTRIBUTES: [&[u8]; 4] = [b"MediaBox", b"CropBox", b"Resources", b"Rotate"];
+
+/// Guards against cyclic `/Parent` chains in malformed files
+const MAX_TREE_DEPTH: usize = 32;
+
+/// File names the downloader gives the cover and nav pages, which the merge
+/// recognizes as front matter
+pub(crate) const COVER_FILE: &str = "01_cover.pdf";
+pub(crate) const NAV_FILE: &str = "01_nav.pdf";
+
+/// Paper sizes accepted by `--paper-size` and `--uniform-page-size`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PageSize {
+    A3,
+    A4,
+    A5,
+    Letter,
+    Legal,
+}
+
+impl PageSize {
+    /// Width and height in PDF points
+    pub fn dimensions(self) -> (f32, f32) {
+        match self {
+            PageSize::A3 => (841.89, 1190.55),
+            PageSize::A4 => (595.28, 841.89),
+            PageSize::A5 => (419.53, 595.28),
+            PageSize::Letter => (612.0, 792.0),
+            PageSize::Legal => (612.0, 1008.0),
+        }
+    }
+}
+
+impl FromStr for PageSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "a3" => Ok(PageSize::A3),
+            "a4" => Ok(PageSize::A4),
+            "a5" => Ok(PageSize::A5),
+            "letter" => Ok(PageSize::Letter),
+            "legal" => Ok(PageSize::Legal),
+            _ => Err(format!("unknown page size '{}' (expected a3, a4, a5, letter or legal)", s)),
+        }
+    }
+}
+
+impl fmt::Display for PageSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            PageSize::A3 => "a3",
+            PageSize::A4 => "a4",
+            PageSize::A5 => "a5",
+            PageSize::Letter => "letter",
+            PageSize::Legal => "legal",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A place a page's title can be taken from, for `--title-source`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TitleSource {
+    /// The page's first `<h1>`
+    H1,
+    /// The page's `<title>`
+    Title,
+    /// The last entry of the page's breadcrumb trail
+    Breadcrumb,
+    /// The page's slug, from its file name
+    Slug,
+}
+
+impl TitleSource {
+    pub const DEFAULT_CHAIN: [TitleSource; 3] = [TitleSource::H1, TitleSource::Title, TitleSource::Slug];
+
+    /// `sources`, or the default chain when it's empty
+    pub fn chain(sources: &[TitleSource]) -> &[TitleSource] {
+        if sources.is_empty() {
+            &Self::DEFAULT_CHAIN
+        } else {
+            sources
+        }
+    }
+}
+
+impl FromStr for TitleSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "h1" => Ok(TitleSource::H1),
+            "title" => Ok(TitleSource::Title),
+            "breadcrumb" => Ok(TitleSource::Breadcrumb),
+            "slug" => Ok(TitleSource::Slug),
+            _ => Err(format!("unknown title source '{}' (expected h1, title, breadcrumb or slug)", s)),
+        }
+    }
+}
+
+impl fmt::Display for TitleSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TitleSource::H1 => "h1",
+            TitleSource::Title => "title",
+            TitleSource::Breadcrumb => "breadcrumb",
+            TitleSource::Slug => "slug",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Order in which content documents are merged; front matter always comes first
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum MergeOrder {
+    /// As given: file name order for `merge`, navigation order for `download`
+    #[default]
+    Natural,
+    /// Back to front
+    Reverse,
+    /// 1-based positions in the natural order, each listed exactly once
+    Custom(Vec<usize>),
+}
+
+impl MergeOrder {
+    /// Reorders `paths`, checking that a custom order lists each of them exactly once
+    pub fn arrange(&self, paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+        match self {
+            MergeOrder::Natural => Ok(paths.to_vec()),
+            MergeOrder::Reverse => Ok(paths.iter().rev().cloned().collect()),
+            MergeOrder::Custom(order) => {
+                // `Custom` can be built without going through `from_str`, so
+                // its positions are checked again here
+                let mut listed = HashSet::with_capacity(order.len());
+                for &index in order {
+                    if index == 0 {
+                        return Err(anyhow!("Custom order refers to file 0, file numbers start at 1"));
+                    }
+                    if index > paths.len() {
+                        return Err(anyhow!("Custom order refers to file {} but there are only {}", index, paths.len()));
+                    }
+                    if !listed.insert(index) {
+                        return Err(anyhow!("Custom order lists file {} twice, each must be listed once", index));
+                    }
+                }
+                // Positions are unique and in range, so only missing ones are left to catch
+                if order.len() < paths.len() {
+                    return Err(anyhow!("Custom order lists only {} of the {} files to merge, each must be listed once",
+                                       order.len(), paths.len()));
+                }
+                Ok(order.iter().map(|&index| paths[index - 1].clone()).collect())
+            }
+        }
+    }
+}
+
+impl FromStr for MergeOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "natural" => return Ok(MergeOrder::Natural),
+            "reverse" => return Ok(MergeOrder::Reverse),
+            _ => {}
+        }
+
+        let invalid = || format!("invalid order '{}' (expected natural, reverse or comma-separated file numbers such as 3,1,2)", s);
+        let mut order = Vec::new();
+        for part in s.split(',') {
+            let index: usize = part.trim().parse().map_err(|_| invalid())?;
+            if index == 0 {
+                return Err(format!("invalid order '{}' (file numbers start at 1)", s));
+            }
+            if order.contains(&index) {
+                return Err(format!("invalid order '{}' (file {} is listed twice)", s, index));
+            }
+            order.push(index);
+        }
+        Ok(MergeOrder::Custom(order))
+    }
+}
+
+impl TryFrom<String> for MergeOrder {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<MergeOrder> for String {
+    fn from(order: MergeOrder) -> Self {
+        order.to_string()
+    }
+}
+
+impl fmt::Display for MergeOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MergeOrder::Natural => f.write_str("natural"),
+            MergeOrder::Reverse => f.write_str("reverse"),
+            MergeOrder::Custom(order) => {
+                let order: Vec<String> = order.iter().map(|index| index.to_string()).collect();
+                f.write_str(&order.join(","))
+            }
+        }
+    }
+}
+
+/// A PDF to splice in among the content documents, given as `INDEX:FILE`.
+///
+/// The index is a 1-based position among the content documents before any
+/// insertion: `3:errata.pdf` puts the file where the third document was,
+/// one past the last appends it. Front matter is not counted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct PageInsert {
+    pub position: usize,
+    pub path: PathBuf,
+}
+
+impl PageInsert {
+    /// Splices `inserts` into `paths`; files inserted at the same position
+    /// keep the order they are given in
+    pub fn apply(inserts: &[PageInsert], paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+        if let Some(insert) = inserts.iter().find(|insert| insert.position == 0) {
+            return Err(anyhow!("Cannot insert {} at position 0, positions start at 1", insert.path.display()));
+        }
+        if let Some(insert) = inserts.iter().find(|insert| insert.position > paths.len() + 1) {
+            return Err(anyhow!("Cannot insert {} at position {}, there are only {} files (use {} to append)",
+                               insert.path.display(), insert.position, paths.len(), paths.len() + 1));
+        }
+
+        let mut spliced = Vec::with_capacity(paths.len() + inserts.len());
+        for position in 1..=paths.len() + 1 {
+            spliced.extend(
+                inserts
+                    .iter()
+                    .filter(|insert| insert.position == position)
+                    .map(|insert| insert.path.clone()),
+            );
+            if let Some(path) = paths.get(position - 1) {
+                spliced.push(path.clone());
+            }
+        }
+        Ok(spliced)
+    }
+}
+
+impl FromStr for PageInsert {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid insert '{}' (expected INDEX:FILE such as 3:errata.pdf)", s);
+        let (position, path) = s.split_once(':').ok_or_else(invalid)?;
+        let position: usize = position.trim().parse().map_err(|_| invalid())?;
+        if position == 0 {
+            return Err(format!("invalid insert '{}' (positions start at 1)", s));
+        }
+        if path.is_empty() {
+            return Err(invalid());
+        }
+        Ok(PageInsert { position, path: PathBuf::from(path) })
+    }
+}
+
+impl TryFrom<String> for PageInsert {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<PageInsert> for String {
+    fn from(insert: PageInsert) -> Self {
+        insert.to_string()
+    }
+}
+
+impl fmt::Display for PageInsert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.position, self.path.display())
+    }
+}
+
+/// Outcome of [`PdfMerger::merge_files`]
+#[derive(Debug, Clone)]
+pub struct MergeResult {
+    pub output: PathBuf,
+    /// Files that made it into the output, in order
+    pub merged: Vec<PathBuf>,
+    /// Files that could not be loaded and were left out, with the reason
+    pub skipped: Vec<(PathBuf, String)>,
+    /// Pages in the merged PDF
+    pub page_count: usize,
+    /// SHA-256 of the output, when `checksum` is set
+    pub sha256: Option<String>,
+    /// Files that could only be loaded after lenient repair
+    pub lenient_loads: Vec<String>,
+}
 
 pub struct PdfMerger {
     documents: Vec<(String, Document)>,
+    /// Number of leading documents that are front matter (cover, TOC)
+    front_matter: usize,
+    options: MergeOptions,
+    /// Files that only loaded after repair
+    lenient_loads: Vec<String>,
+    /// Attachment names and contents of the documents' HTML sources
+    sources: Vec<(String, Vec<u8>)>,
 }
 
 impl PdfMerger {
     pub fn new() -> Self {
+        Self::with_options(MergeOptions::default())
+    }
+
+    pub fn with_options(options: MergeOptions) -> Self {
         Self {
             documents: Vec::new(),
+            front_matter: 0,
+            options,
+            lenient_loads: Vec::new(),
+            sources: Vec::new(),
         }
     }
 
+    /// Merges `paths` into `output` in one call.
+    ///
+    /// Leading `01_cover.pdf`, `01_front_*.pdf` and `01_nav.pdf` files, as written
+    /// by the downloader, are treated as front matter and kept in front; the
+    /// rest are merged in `options.order`, with `options.inserts` spliced in.
+    /// Files that fail to load are skipped and reported in the result; it is
+    /// an error if none of them load.
+    ///
+    /// ```no_run
+    /// # async fn example() -> anyhow::Result<()> {
+    /// use book2pdf::{MergeOptions, PdfMerger};
+    /// use std::path::{Path, PathBuf};
+    ///
+    /// let pages = vec![PathBuf::from("pages/01_cover.pdf"), PathBuf::from("pages/02_intro.pdf")];
+    /// let options = MergeOptions { page_labels: true, ..Default::default() };
+    ///
+    /// let result = PdfMerger::merge_files(&pages, Path::new("book.pdf"), options).await?;
+    /// println!("{} pages, {} files skipped", result.page_count, result.skipped.len());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn merge_files(paths: &[PathBuf], output: &Path, options: MergeOptions) -> Result<MergeResult> {
+        let front_matter = Self::front_matter_count(paths);
+        let mut ordered = paths[..front_matter].to_vec();
+        let content = options.order.arrange(&paths[front_matter..])?;
+        ordered.extend(PageInsert::apply(&options.inserts, &content)?);
+        let paths = ordered.as_slice();
+
+        let mut merger = Self::with_options(options);
+        let mut outcomes = merger.add_front_matter_pdfs(&paths[..front_matter]).await;
+        outcomes.extend(merger.add_pdfs(&paths[front_matter..]).await);
+
+        let mut merged = Vec::new();
+        let mut skipped = Vec::new();
+        for (path, added) in paths.iter().zip(outcomes) {
+            match added {
+                Ok(()) => merged.push(path.clone()),
+                Err(e) => {
+                    error!("Failed to add PDF {}: {}", path.display(), e);
+                    skipped.push((path.clone(), e.to_string()));
+                }
+            }
+        }
+
+        if merged.is_empty() {
+            return Err(anyhow!("None of the {} PDF files could be added", paths.len()));
+        }
+
+        let sha256 = merger.save(output).await?;
+
+        Ok(MergeResult {
+            output: output.to_path_buf(),
+            merged,
+            skipped,
+            page_count: merger.page_count(),
+            sha256,
+            lenient_loads: merger.lenient_loads().to_vec(),
+        })
+    }
+
+    /// How many of `paths` `merge_files` treats as front matter, which `order` leaves in front
+    pub fn front_matter_count(paths: &[PathBuf]) -> usize {
+        paths.iter().take_while(|path| is_front_matter_file(path)).count()
+    }
+
+    /// Adds a front matter document (cover, TOC). These must be added before any content.
+    pub async fn add_front_matter_pdf(&mut self, path: &Path) -> Result<()> {
+        self.check_front_matter_order(path)?;
+        self.add_pdf(path).await?;
+        self.front_matter += 1;
+        Ok(())
+    }
+
     pub async fn add_pdf(&mut self, path: &Path) -> Result<()> {
-        let data = fs::read(path)
-            .await
-            .map_err(|e| anyhow!("Failed to read PDF file {}: {}", path.display(), e))?;
+        let loaded = load_pdf(path.to_path_buf(), self.options.lenient, self.options.attach_source).await?;
+        self.push(loaded);
+        Ok(())
+    }
 
-        let document = Document::load_mem(&data)
-            .map_err(|e| anyhow!("Failed to parse PDF file {}: {}", path.display(), e))?;
+    /// Like [`add_front_matter_pdf`](Self::add_front_matter_pdf) for several files,
+    /// with the outcome for each path
+    pub async fn add_front_matter_pdfs(&mut self, paths: &[PathBuf]) -> Vec<Result<()>> {
+        let mut outcomes = Vec::with_capacity(paths.len());
+        for (path, loaded) in paths.iter().zip(self.load_all(paths).await) {
+            let added = self.check_front_matter_order(path).and(loaded).map(|loaded| {
+                self.push(loaded);
+                self.front_matter += 1;
+            });
+            outcomes.push(added);
+        }
+        outcomes
+    }
 
-        let filename = path.file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or("unknown.pdf")
-            .to_string();
+    /// Adds documents in the given order, parsing up to `load_threads` of them
+    /// in parallel. Returns the outcome for each path; failed ones are left out.
+    pub async fn add_pdfs(&mut self, paths: &[PathBuf]) -> Vec<Result<()>> {
+        let mut outcomes = Vec::with_capacity(paths.len());
+        for loaded in self.load_all(paths).await {
+            outcomes.push(loaded.map(|loaded| self.push(loaded)));
+        }
+        outcomes
+    }
 
-        debug!("Loaded PDF with {} pages from {}", document.get_pages().len(), path.display());
-        self.documents.push((filename, document));
+    async fn load_all(&self, paths: &[PathBuf]) -> Vec<Result<LoadedPdf>> {
+        let threads = self
+            .options
+            .load_threads
+            .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()))
+            .max(1);
+        let lenient = self.options.lenient;
+        let attach_source = self.options.attach_source;
 
+        // `buffered` yields in input order however the loads finish
+        stream::iter(paths.iter().cloned())
+            .map(|path| {
+                info!("Adding: {}", path.display());
+                load_pdf(path, lenient, attach_source)
+            })
+            .buffered(threads)
+            .collect()
+            .await
+    }
+
+    fn check_front_matter_order(&self, path: &Path) -> Result<()> {
+        if self.documents.len() > self.front_matter {
+            return Err(anyhow!("Front matter {} must be added before content PDFs", path.display()));
+        }
         Ok(())
     }
 
-    pub async fn save(&self, output_path: &Path) -> Result<()> {
+    fn push(&mut self, loaded: LoadedPdf) {
+        if loaded.repaired {
+            self.lenient_loads.push(loaded.filename.clone());
+        }
+        if let Some(source) = loaded.source {
+            self.sources.push((format!("{}.html", filename_title(&loaded.filename)), source));
+        }
+        self.documents.push((loaded.filename, loaded.document));
+    }
+
+    /// Total pages across the added documents
+    pub fn page_count(&self) -> usize {
+        self.documents.iter().map(|(_, document)| document.get_pages().len()).sum()
+    }
+
+    /// Files that could only be loaded after lenient repair
+    pub fn lenient_loads(&self) -> &[String] {
+        &self.lenient_loads
+    }
+
+    /// Writes the merged PDF, returning its SHA-256 when `checksum` is set
+    pub async fn save(&self, output_path: &Path) -> Result<Option<String>> {
         if self.documents.is_empty() {
             return Err(anyhow!("No PDFs added to merge"));
         }
 
-        if self.documents.len() == 1 {
-            // If only one document, just copy it
-            let data = fs::read(&output_path).await.unwrap_or_default();
-            fs::write(output_path, data).await?;
-            return Ok(());
+        if !self.lenient_loads.is_empty() {
+            warn!("{} of {} PDFs were loaded in lenient mode: {}",
+                  self.lenient_loads.len(), self.documents.len(), self.lenient_loads.join(", "));
         }
 
         info!("Starting PDF merge process with {} documents", self.documents.len());
 
-        // Use the first document as the base
+        // Use the first document as the base; a single document goes through the
+        // same steps, so it gets its outline, metadata and checksum too
         let mut merged_doc = self.documents[0].1.clone();
         let mut all_page_ids = Vec::new();
+        // First page and own bookmarks of every document, for the outline
+        let mut section_starts = Vec::with_capacity(self.documents.len());
+        let mut section_outlines = Vec::with_capacity(self.documents.len());
         
+        let mut tagged = Vec::new();
+        if let Some(root) = struct_tree_root(&merged_doc) {
+            tagged.push(TaggedPart { root, objects: 0..=merged_doc.max_id });
+        }
+
         // Collect page IDs from the first document
         let first_pages = merged_doc.get_pages();
         debug!("First document has {} pages", first_pages.len());
+        section_starts.push(first_pages.values().next().copied());
+        if self.options.bookmarks {
+            section_outlines.push(pdf_outline::read_outline(&merged_doc));
+        }
         for (_, page_id) in first_pages {
             all_page_ids.push(page_id);
         }
@@ -69,11 +613,20 @@ impl PdfMerger {
             let mut doc_copy = document.clone();
             
             // Renumber objects to avoid conflicts
-            doc_copy.renumber_objects_with(max_id + 1);
+            let first_id = max_id + 1;
+            doc_copy.renumber_objects_with(first_id);
             max_id = doc_copy.max_id;
+            if let Some(root) = struct_tree_root(&doc_copy) {
+                tagged.push(TaggedPart { root, objects: first_id..=max_id });
+            }
             
             // Get pages from this document
             let pages = doc_copy.get_pages();
+            section_starts.push(pages.values().next().copied());
+            if self.options.bookmarks {
+                // Read after renumbering, so it points at the pages' new IDs
+                section_outlines.push(pdf_outline::read_outline(&doc_copy));
+            }
             
             // Copy all objects from this document
             for (obj_id, obj) in doc_copy.objects.iter() {
@@ -90,22 +643,20 @@ impl PdfMerger {
 
         // Update the Pages object to reference all pages
         if let Ok(catalog) = merged_doc.catalog() {
-            if let Ok(pages_ref) = catalog.get(b"Pages") {
-                if let Object::Reference(pages_id) = pages_ref {
-                    if let Ok(pages_obj) = merged_doc.get_object_mut(*pages_id) {
-                        if let Object::Dictionary(ref mut pages_dict) = pages_obj {
-                            // Update the Kids array with all page references
-                            pages_dict.set("Kids", Object::Array(
-                                all_page_ids.into_iter().map(Object::Reference).collect()
-                            ));
-                            
-                            // Update the Count
-                            if let Ok(Object::Array(ref kids)) = pages_dict.get(b"Kids") {
-                                let kids_len = kids.len();
-                                pages_dict.set("Count", Object::Integer(kids_len as i64));
-                                debug!("Updated Pages object with {} kids", kids_len);
-                            }
-                        }
+            if let Ok(Object::Reference(pages_id)) = catalog.get(b"Pages") {
+                let pages_id = *pages_id;
+                flatten_page_tree(&mut merged_doc, pages_id, &all_page_ids);
+                if let Ok(Object::Dictionary(ref mut pages_dict)) = merged_doc.get_object_mut(pages_id) {
+                    // Update the Kids array with all page references
+                    pages_dict.set("Kids", Object::Array(
+                        all_page_ids.into_iter().map(Object::Reference).collect()
+                    ));
+
+                    // Update the Count
+                    if let Ok(Object::Array(ref kids)) = pages_dict.get(b"Kids") {
+                        let kids_len = kids.len();
+                        pages_dict.set("Count", Object::Integer(kids_len as i64));
+                        debug!("Updated Pages object with {} kids", kids_len);
                     }
                 }
             }
@@ -114,15 +665,35 @@ impl PdfMerger {
         // Update max_id and renumber if needed
         merged_doc.max_id = max_id;
 
+        if self.options.accessible {
+            if tagged.len() < self.documents.len() {
+                warn!("{} of {} PDFs have no structure tags, their pages are left out of the reading order",
+                      self.documents.len() - tagged.len(), self.documents.len());
+            }
+            merge_structure_trees(&mut merged_doc, &tagged);
+        }
+
+        if self.options.page_labels {
+            self.add_page_labels(&mut merged_doc)?;
+        }
+
+        if self.options.bookmarks {
+            self.add_outline(&mut merged_doc, &section_starts, section_outlines)?;
+        }
+
+        if let Some(size) = self.options.uniform_page_size {
+            normalize_page_sizes(&mut merged_doc, size)?;
+        }
+
+        if self.options.attach_source {
+            self.add_source_attachments(&mut merged_doc)?;
+        }
+
         let final_page_count = if let Ok(catalog) = merged_doc.catalog() {
-            if let Ok(pages_ref) = catalog.get(b"Pages") {
-                if let Object::Reference(pages_id) = pages_ref {
-                    if let Ok(pages_obj) = merged_doc.get_object(*pages_id) {
-                        if let Object::Dictionary(ref pages_dict) = pages_obj {
-                            if let Ok(Object::Integer(count)) = pages_dict.get(b"Count") {
-                                *count
-                            } else { 0 }
-                        } else { 0 }
+            if let Ok(Object::Reference(pages_id)) = catalog.get(b"Pages") {
+                if let Ok(Object::Dictionary(ref pages_dict)) = merged_doc.get_object(*pages_id) {
+                    if let Ok(Object::Integer(count)) = pages_dict.get(b"Count") {
+                        *count
                     } else { 0 }
                 } else { 0 }
             } else { 0 }
@@ -130,24 +701,1402 @@ impl PdfMerger {
 
         info!("Finalizing merged PDF with {} total pages", final_page_count);
 
+        if self.options.deterministic {
+            make_deterministic(&mut merged_doc);
+        }
+
+        // After the deterministic cleanup, which drops every XMP packet
+        if self.options.embed_provenance {
+            self.add_provenance(&mut merged_doc)?;
+        }
+
         // Save the merged document
         let mut data = Vec::new();
         merged_doc
             .save_to(&mut data)
             .map_err(|e| anyhow!("Failed to serialize merged PDF: {}", e))?;
 
-        fs::write(output_path, data)
+        if self.options.compress {
+            let compressed = pdf_compress::save_compressed(&mut merged_doc)?;
+            info!("Compressed merged PDF from {} to {} bytes ({:.1}% smaller)",
+                  data.len(), compressed.len(),
+                  100.0 * (1.0 - compressed.len() as f64 / data.len() as f64));
+            data = compressed;
+        }
+
+        let sha256 = self.options.checksum.then(|| format!("{:x}", Sha256::digest(&data)));
+
+        // Before the write, so a bad merge fails without replacing the output
+        self.validate_output(output_path, &data)?;
+        write_atomic(output_path, data)
             .await
             .map_err(|e| anyhow!("Failed to write merged PDF to {}: {}", output_path.display(), e))?;
 
+        if let Some(sha256) = &sha256 {
+            write_checksum_file(output_path, sha256).await?;
+        }
+
         info!("Successfully merged {} PDFs into {}", 
               self.documents.len(), output_path.display());
+        Ok(sha256)
+    }
+
+    /// Checks the serialized merge `data` parses with all the source pages, so
+    /// a bad merge fails the run instead of going unnoticed
+    fn validate_output(&self, output_path: &Path, data: &[u8]) -> Result<()> {
+        if self.options.skip_validation {
+            return Ok(());
+        }
+
+        let document = Document::load_mem(data)
+            .map_err(|e| anyhow!("Merged PDF {} is corrupt, it doesn't parse: {}", output_path.display(), e))?;
+        let pages = document.get_pages();
+        let expected = self.page_count();
+        let found = pages.len();
+        if found != expected {
+            return Err(anyhow!("Merged PDF {} has {} pages but its sources have {}",
+                               output_path.display(), found, expected));
+        }
+
+        // Pages keep their own sizes, so each has to say what it is
+        if let Some(number) = pages.iter().find_map(|(number, &id)| page_box(&document, id).is_none().then_some(number)) {
+            return Err(anyhow!("Merged PDF {} has no valid MediaBox on page {}", output_path.display(), number));
+        }
+
+        debug!("Validated {}: {} pages", output_path.display(), found);
+        Ok(())
+    }
+
+    /// Applies `title_separator` and `strip_title_suffixes` to a scraped title,
+    /// keeping the original if nothing would be left
+    fn clean_title(&self, title: String) -> String {
+        let mut cleaned = title.as_str();
+
+        if let Some(separator) = self.options.title_separator.as_deref().filter(|s| !s.is_empty()) {
+            if let Some((before, _)) = cleaned.split_once(separator) {
+                cleaned = before;
+            }
+        }
+
+        // Suffixes can be stacked ("Intro - Guide | Docs"), so strip until none matches
+        loop {
+            let trimmed = cleaned.trim_end();
+            let stripped = self
+                .options
+                .strip_title_suffixes
+                .iter()
+                .filter(|suffix| !suffix.is_empty())
+                .find_map(|suffix| trimmed.strip_suffix(suffix.as_str()));
+            match stripped {
+                Some(rest) => cleaned = rest,
+                None => break,
+            }
+        }
+
+        match cleaned.trim() {
+            "" => title,
+            cleaned => cleaned.to_string(),
+        }
+    }
+
+    /// Writes a `/PageLabels` number tree into the catalog so readers number the
+    /// front matter "i, ii, iii" and restart the content at "1".
+    fn add_page_labels(&self, merged_doc: &mut Document) -> Result<()> {
+        let front_matter_pages: usize = self.documents[..self.front_matter]
+            .iter()
+            .map(|(_, document)| document.get_pages().len())
+            .sum();
+
+        let mut nums = Vec::new();
+        if front_matter_pages > 0 {
+            nums.push(Object::Integer(0));
+            nums.push(Object::Dictionary(dictionary! { "S" => Object::Name(b"r".to_vec()) }));
+        }
+        nums.push(Object::Integer(front_matter_pages as i64));
+        nums.push(Object::Dictionary(dictionary! { "S" => Object::Name(b"D".to_vec()) }));
+
+        let labels_id = merged_doc.add_object(dictionary! { "Nums" => nums });
+        merged_doc
+            .catalog_mut()
+            .map_err(|e| anyhow!("Failed to access PDF catalog: {}", e))?
+            .set("PageLabels", Object::Reference(labels_id));
+
+        debug!("Added page labels: {} front matter pages", front_matter_pages);
         Ok(())
     }
+
+    /// Records the export's site, content pages and time as XMP metadata.
+    ///
+    /// A standalone merge has no page URLs, so it lists the content file names.
+    fn add_provenance(&self, merged_doc: &mut Document) -> Result<()> {
+        let file_names: Vec<String>;
+        let sources = if self.options.source_pages.is_empty() {
+            file_names = self.documents[self.front_matter..].iter().map(|(name, _)| name.clone()).collect();
+            &file_names
+        } else {
+            &self.options.source_pages
+        };
+
+        let exported_at = if self.options.deterministic {
+            source_date_epoch()
+        } else {
+            Some(chrono::Utc::now())
+        };
+
+        pdf_provenance::embed(
+            merged_doc,
+            &Provenance {
+                source_url: self.options.source_url.as_deref(),
+                sources,
+                exported_at,
+            },
+        )
+    }
+
+    /// Embeds the documents' HTML sources as files listed in the catalog's
+    /// `/EmbeddedFiles` name tree, which readers show as attachments.
+    fn add_source_attachments(&self, merged_doc: &mut Document) -> Result<()> {
+        if self.sources.is_empty() {
+            warn!("No .html sources found next to the PDFs, nothing to attach");
+            return Ok(());
+        }
+
+        // Name tree keys must be sorted and unique
+        let mut sources: Vec<&(String, Vec<u8>)> = self.sources.iter().collect();
+        sources.sort_by(|a, b| a.0.cmp(&b.0));
+        sources.dedup_by(|a, b| a.0 == b.0);
+
+        let mut names = Vec::with_capacity(sources.len() * 2);
+        for (name, data) in &sources {
+            let mut file = Stream::new(
+                dictionary! {
+                    "Type" => "EmbeddedFile",
+                    "Subtype" => "text/html",
+                    "Params" => dictionary! { "Size" => data.len() as i64 },
+                },
+                data.clone(),
+            );
+            file.compress()
+                .map_err(|e| anyhow!("Failed to compress attachment {}: {}", name, e))?;
+            let file_id = merged_doc.add_object(file);
+
+            let spec_id = merged_doc.add_object(dictionary! {
+                "Type" => "Filespec",
+                "F" => text_string(name),
+                "UF" => text_string(name),
+                "EF" => dictionary! { "F" => file_id },
+                "AFRelationship" => "Source",
+            });
+            names.push(text_string(name));
+            names.push(spec_id.into());
+        }
+        let tree_id = merged_doc.add_object(dictionary! { "Names" => names });
+
+        // Chrome doesn't write a `/Names` dictionary, but keep any other trees in one
+        let catalog = merged_doc
+            .catalog_mut()
+            .map_err(|e| anyhow!("Failed to find the catalog: {}", e))?;
+        match catalog.get(b"Names") {
+            Ok(Object::Reference(names_id)) => {
+                let names_id = *names_id;
+                merged_doc
+                    .get_dictionary_mut(names_id)
+                    .map_err(|e| anyhow!("Failed to read the catalog's name dictionary: {}", e))?
+                    .set("EmbeddedFiles", tree_id);
+            }
+            Ok(Object::Dictionary(_)) => {
+                if let Ok(Object::Dictionary(names)) = catalog.get_mut(b"Names") {
+                    names.set("EmbeddedFiles", tree_id);
+                }
+            }
+            _ => catalog.set("Names", dictionary! { "EmbeddedFiles" => tree_id }),
+        }
+
+        debug!("Attached {} HTML sources", sources.len());
+        Ok(())
+    }
+
+    /// The first title `title_sources` finds for a content document.
+    ///
+    /// The downloader prints the heading or breadcrumb the chain picks as the
+    /// page's PDF title, so every source but the slug reads that title here.
+    fn chain_title(&self, slug: &str, document: &Document) -> Option<String> {
+        TitleSource::chain(&self.options.title_sources).iter().find_map(|source| match source {
+            TitleSource::Slug => Some(slug.to_string()),
+            _ => document_title(document).map(|title| self.clean_title(title)),
+        })
+    }
+
+    /// Writes an `/Outlines` tree with one bookmark per merged document.
+    ///
+    /// The cover and TOC come first as "Cover" and "Table of Contents", so
+    /// readers can always jump back to the start; Markdown front matter and
+    /// content documents get their `titles` entry or the first title
+    /// `title_sources` finds. Each bookmark points at its document's first page.
+    ///
+    /// A content document's own outline, such as the headings Chrome adds with
+    /// `--output-individual-bookmarks`, is nested under its bookmark. When that
+    /// outline is a single entry with the document's title (the page's `h1`),
+    /// its children take its place so the title isn't listed twice. Pages the
+    /// site's sidebar nests (`nav_depths`) follow after them, as bookmarks of
+    /// their own.
+    fn add_outline(&self, merged_doc: &mut Document, section_starts: &[Option<ObjectId>], section_outlines: Vec<Vec<OutlineNode>>) -> Result<()> {
+        let entries: Vec<(usize, OutlineNode)> = self
+            .documents
+            .iter()
+            .zip(section_starts)
+            .zip(section_outlines)
+            .enumerate()
+            .filter_map(|(i, (((filename, document), start), mut children))| {
+                let (title, depth) = if i < self.front_matter {
+                    children.clear();
+                    (front_matter_title(filename, document), 0)
+                } else {
+                    let slug = filename_title(filename);
+                    let depth = self.options.nav_depths.get(&slug).copied().unwrap_or(0);
+                    let from_filename = self.options.bookmarks_from_filenames.then(|| filename_bookmark(&slug));
+                    let title = self
+                        .options
+                        .titles
+                        .get(&slug)
+                        .cloned()
+                        .or(from_filename)
+                        .or_else(|| self.chain_title(&slug, document))
+                        .unwrap_or(slug);
+                    (title, depth)
+                };
+                if children.len() == 1 && children[0].title == title {
+                    children = children.remove(0).children;
+                }
+                start.map(|page| (depth, OutlineNode { title, page, view: Vec::new(), children }))
+            })
+            .collect();
+        let bookmarks = entries.len();
+        let entries = nest_by_depth(entries);
+
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        let outlines_id = merged_doc.new_object_id();
+        let (first, last, visible) = write_outline_items(merged_doc, outlines_id, &entries, self.options.outline_collapsed);
+
+        merged_doc.objects.insert(outlines_id, Object::Dictionary(dictionary! {
+            "Type" => "Outlines",
+            "First" => first,
+            "Last" => last,
+            "Count" => visible,
+        }));
+
+        let catalog = merged_doc
+            .catalog_mut()
+            .map_err(|e| anyhow!("Failed to access PDF catalog: {}", e))?;
+        catalog.set("Outlines", outlines_id);
+        catalog.set("PageMode", "UseOutlines");
+
+        debug!("Added {} bookmarks", bookmarks);
+        Ok(())
+    }
+}
+
+/// Puts every node under the closest earlier one with a smaller depth, as the
+/// last of its children; nodes with none such are the top level
+fn nest_by_depth(nodes: Vec<(usize, OutlineNode)>) -> Vec<OutlineNode> {
+    let mut roots = Vec::new();
+    let mut open: Vec<(usize, OutlineNode)> = Vec::new();
+    for (depth, node) in nodes {
+        close_nodes(&mut open, &mut roots, depth);
+        open.push((depth, node));
+    }
+    close_nodes(&mut open, &mut roots, 0);
+    roots
+}
+
+/// Ends the open nodes at `depth` or deeper, each becoming a child of the one
+/// below it on the stack
+fn close_nodes(open: &mut Vec<(usize, OutlineNode)>, roots: &mut Vec<OutlineNode>, depth: usize) {
+    while open.last().is_some_and(|(open_depth, _)| *open_depth >= depth) {
+        let Some((_, node)) = open.pop() else {
+            break;
+        };
+        match open.last_mut() {
+            Some((_, parent)) => parent.children.push(node),
+            None => roots.push(node),
+        }
+    }
+}
+
+/// Writes `nodes` as the children of the outline item (or root) `parent_id`.
+///
+/// Returns the first and last item and how many items are visible below the
+/// parent. An item's `/Count` is that number for its own children, negated
+/// when `collapsed` so readers open it closed; top-level items always show.
+fn write_outline_items(doc: &mut Document, parent_id: ObjectId, nodes: &[OutlineNode], collapsed: bool) -> (ObjectId, ObjectId, i64) {
+    let item_ids: Vec<ObjectId> = nodes.iter().map(|_| doc.new_object_id()).collect();
+    let mut visible = 0;
+
+    for (i, node) in nodes.iter().enumerate() {
+        let mut item = dictionary! {
+            "Title" => text_string(&node.title),
+            "Parent" => parent_id,
+        };
+        let view = if node.view.is_empty() { vec!["Fit".into()] } else { node.view.clone() };
+        item.set("Dest", [vec![Object::Reference(node.page)], view].concat());
+        if i > 0 {
+            item.set("Prev", item_ids[i - 1]);
+        }
+        if let Some(next) = item_ids.get(i + 1) {
+            item.set("Next", *next);
+        }
+
+        visible += 1;
+        if !node.children.is_empty() {
+            let (first, last, below) = write_outline_items(doc, item_ids[i], &node.children, collapsed);
+            item.set("First", first);
+            item.set("Last", last);
+            if collapsed {
+                item.set("Count", -below);
+            } else {
+                item.set("Count", below);
+                visible += below;
+            }
+        }
+        doc.objects.insert(item_ids[i], Object::Dictionary(item));
+    }
+
+    (item_ids[0], item_ids[item_ids.len() - 1], visible)
+}
+
+/// Sort key putting page files in the order they were numbered: the number
+/// before the first `_` as an integer, then the file name.
+///
+/// Page numbers grow past two digits on long books, and `100_x.pdf` has to come
+/// after `11_x.pdf` rather than between `10_x.pdf` and `11_x.pdf`. Names without
+/// a numeric prefix come after all the numbered ones, by name.
+///
+/// ```
+/// use book2pdf::natural_sort_key;
+/// use std::path::PathBuf;
+///
+/// let mut files: Vec<PathBuf> = ["100_x.pdf", "errata.pdf", "11_y.pdf", "02_z.pdf"].iter().map(PathBuf::from).collect();
+/// files.sort_by_key(|path| natural_sort_key(path));
+/// assert_eq!(files, ["02_z.pdf", "11_y.pdf", "100_x.pdf", "errata.pdf"].map(PathBuf::from));
+/// ```
+pub fn natural_sort_key(path: &Path) -> (bool, Option<u64>, String) {
+    let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    let number: Option<u64> = name.split_once('_').and_then(|(number, _)| number.parse().ok());
+    (number.is_none(), number, name)
+}
+
+/// Reads a `--titles` file: one `slug = Title` (or `URL = Title`) per line.
+///
+/// Blank lines and lines starting with `#` are ignored.
+pub async fn read_titles_file(path: &Path) -> Result<HashMap<String, String>> {
+    let data = fs::read_to_string(path)
+        .await
+        .map_err(|e| anyhow!("Failed to read titles file {}: {}", path.display(), e))?;
+
+    let mut titles = HashMap::new();
+    for (number, line) in data.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        // URLs can contain '=' in their query, so a spaced separator wins
+        let (key, title) = line
+            .split_once(" = ")
+            .or_else(|| line.split_once('='))
+            .ok_or_else(|| anyhow!("Invalid line {} in titles file {}: expected 'slug = Title'", number + 1, path.display()))?;
+        let (key, title) = (key.trim(), title.trim());
+        if key.is_empty() || title.is_empty() {
+            return Err(anyhow!("Invalid line {} in titles file {}: expected 'slug = Title'", number + 1, path.display()));
+        }
+        titles.insert(key.to_string(), title.to_string());
+    }
+
+    Ok(titles)
+}
+
+/// Writes `<output>.sha256` in the format `sha256sum -c` reads
+async fn write_checksum_file(output: &Path, sha256: &str) -> Result<()> {
+    let mut sidecar = output.as_os_str().to_owned();
+    sidecar.push(".sha256");
+    let sidecar = PathBuf::from(sidecar);
+
+    let name = output.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    write_atomic(&sidecar, format!("{}  {}\n", sha256, name))
+        .await
+        .map_err(|e| anyhow!("Failed to write checksum file {}: {}", sidecar.display(), e))
+}
+
+/// Removes what makes two merges of the same pages differ: the dates and
+/// XMP packet Chrome stamps on every PDF, and the random file `/ID`.
+///
+/// With `SOURCE_DATE_EPOCH` set, both info dates are pinned to that time
+/// instead of being left out. Objects only the left-out documents referenced
+/// (their info dictionaries) are pruned and the rest renumbered, otherwise
+/// their dates would still end up in the file.
+fn make_deterministic(doc: &mut Document) {
+    doc.trailer.remove(b"ID");
+
+    if let Ok(catalog) = doc.catalog_mut() {
+        catalog.remove(b"Metadata");
+    }
+
+    let fixed_date = source_date_epoch().map(|time| time.format("D:%Y%m%d%H%M%SZ").to_string());
+
+    let info_id = match doc.trailer.get(b"Info") {
+        Ok(Object::Reference(id)) => Some(*id),
+        Ok(_) => None,
+        Err(_) if fixed_date.is_some() => {
+            let id = doc.add_object(dictionary! {});
+            doc.trailer.set("Info", id);
+            Some(id)
+        }
+        Err(_) => None,
+    };
+    let info = match info_id {
+        Some(id) => doc.get_dictionary_mut(id).ok(),
+        None => doc.trailer.get_mut(b"Info").and_then(Object::as_dict_mut).ok(),
+    };
+    if let Some(info) = info {
+        match &fixed_date {
+            Some(date) => {
+                info.set("CreationDate", Object::string_literal(date.as_str()));
+                info.set("ModDate", Object::string_literal(date.as_str()));
+            }
+            None => {
+                info.remove(b"CreationDate");
+                info.remove(b"ModDate");
+            }
+        }
+    }
+
+    doc.prune_objects();
+    doc.renumber_objects();
+}
+
+/// The build time reproducible-build tooling passes in `SOURCE_DATE_EPOCH`
+fn source_date_epoch() -> Option<chrono::DateTime<chrono::Utc>> {
+    let value = std::env::var("SOURCE_DATE_EPOCH").ok()?;
+    match value.trim().parse::<i64>().ok().and_then(|secs| chrono::DateTime::from_timestamp(secs, 0)) {
+        Some(time) => Some(time),
+        None => {
+            warn!("Ignoring invalid SOURCE_DATE_EPOCH '{}'", value);
+            None
+        }
+    }
+}
+
+/// A parsed input document
+struct LoadedPdf {
+    filename: String,
+    document: Document,
+    /// Only loaded after its cross-reference table was rebuilt
+    repaired: bool,
+    /// The `.html` file next to it, when sources are attached
+    source: Option<Vec<u8>>,
+}
+
+/// Reads and parses a PDF; parsing is CPU-bound and runs on the blocking pool.
+async fn load_pdf(path: PathBuf, lenient: bool, attach_source: bool) -> Result<LoadedPdf> {
+    let data = fs::read(&path)
+        .await
+        .map_err(|e| anyhow!("Failed to read PDF file {}: {}", path.display(), e))?;
+
+    let mut source = None;
+    if attach_source {
+        source = fs::read(path.with_extension("html")).await.ok();
+        // Front matter has no source, so a missing file isn't worth a warning
+        if source.is_none() {
+            debug!("No HTML source next to {}", path.display());
+        }
+    }
+
+    let filename = path.file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("unknown.pdf")
+        .to_string();
+
+    tokio::task::spawn_blocking(move || {
+        let (document, repaired) = match Document::load_mem(&data) {
+            Ok(document) => (document, false),
+            Err(e) if lenient => {
+                let repaired = pdf_repair::rebuild_xref(&data)
+                    .and_then(|repaired| Document::load_mem(&repaired).ok())
+                    .filter(|document| !document.get_pages().is_empty())
+                    .ok_or_else(|| anyhow!("Failed to parse PDF file {} even in lenient mode: {}", path.display(), e))?;
+
+                warn!("{} failed strict parsing ({}), loaded in lenient mode - its pages may be imperfect",
+                      path.display(), e);
+                (repaired, true)
+            }
+            Err(e) => return Err(anyhow!("Failed to parse PDF file {}: {}", path.display(), e)),
+        };
+
+        debug!("Loaded PDF with {} pages from {}", document.get_pages().len(), path.display());
+        Ok(LoadedPdf { filename, document, repaired, source })
+    })
+    .await
+    .map_err(|e| anyhow!("Failed to load PDF file: {}", e))?
+}
+
+/// Structure tree of one of the merged documents
+struct TaggedPart {
+    root: ObjectId,
+    /// Object numbers the document occupies in the merged file
+    objects: RangeInclusive<u32>,
+}
+
+fn struct_tree_root(doc: &Document) -> Option<ObjectId> {
+    doc.catalog().ok()?.get(b"StructTreeRoot").and_then(Object::as_reference).ok()
+}
+
+/// Hangs the structure trees of all tagged documents under one new root.
+///
+/// Every document numbers its marked-content parents from 0, so each one's
+/// `/ParentTree` keys, and the `/StructParents` and `/StructParent` entries that
+/// point into it, are shifted past the keys of the documents before it.
+fn merge_structure_trees(doc: &mut Document, parts: &[TaggedPart]) {
+    if parts.is_empty() {
+        debug!("None of the merged PDFs is tagged");
+        return;
+    }
+
+    let root_id = doc.new_object_id();
+    let mut kids = Vec::new();
+    let mut parents = Vec::new();
+    let mut role_map = lopdf::Dictionary::new();
+    let mut next_key = 0;
+
+    for part in parts {
+        let Ok(root) = doc.get_dictionary(part.root).cloned() else {
+            continue;
+        };
+
+        let part_kids = match root.get(b"K") {
+            Ok(Object::Array(part_kids)) => part_kids.clone(),
+            Ok(kid) => vec![kid.clone()],
+            Err(_) => Vec::new(),
+        };
+        for kid in &part_kids {
+            if let Ok(element) = kid.as_reference().and_then(|id| doc.get_dictionary_mut(id)) {
+                element.set("P", root_id);
+            }
+        }
+        kids.extend(part_kids);
+
+        let part_roles = root.get(b"RoleMap").and_then(|roles| doc.dereference(roles)).and_then(|(_, roles)| roles.as_dict());
+        if let Ok(part_roles) = part_roles {
+            for (role, standard) in part_roles.iter() {
+                role_map.set(role.clone(), standard.clone());
+            }
+        }
+
+        let mut entries = Vec::new();
+        if let Ok(tree) = root.get(b"ParentTree") {
+            collect_number_tree(doc, tree, &mut entries, 0);
+        }
+        let offset = next_key;
+        let used = entries.iter().map(|(key, _)| key + 1).max().unwrap_or(0);
+        next_key += root.get(b"ParentTreeNextKey").and_then(Object::as_i64).unwrap_or(0).max(used);
+        parents.extend(entries.into_iter().map(|(key, value)| (key + offset, value)));
+
+        if offset > 0 {
+            for id in part.objects.clone() {
+                let dict = match doc.objects.get_mut(&(id, 0)) {
+                    Some(Object::Dictionary(dict)) => dict,
+                    Some(Object::Stream(stream)) => &mut stream.dict,
+                    _ => continue,
+                };
+                for key in [b"StructParents".as_slice(), b"StructParent"] {
+                    if let Ok(key_value) = dict.get(key).and_then(Object::as_i64) {
+                        dict.set(key, key_value + offset);
+                    }
+                }
+            }
+        }
+    }
+
+    parents.sort_by_key(|(key, _)| *key);
+    let nums: Vec<Object> = parents
+        .into_iter()
+        .flat_map(|(key, value)| [Object::Integer(key), value])
+        .collect();
+    let parent_tree_id = doc.add_object(dictionary! { "Nums" => nums });
+
+    let mut root = dictionary! {
+        "Type" => "StructTreeRoot",
+        "K" => kids,
+        "ParentTree" => parent_tree_id,
+        "ParentTreeNextKey" => next_key,
+    };
+    if !role_map.is_empty() {
+        root.set("RoleMap", role_map);
+    }
+    doc.objects.insert(root_id, Object::Dictionary(root));
+
+    if let Ok(catalog) = doc.catalog_mut() {
+        catalog.set("StructTreeRoot", root_id);
+        catalog.set("MarkInfo", dictionary! { "Marked" => true });
+    }
+    debug!("Merged the structure trees of {} PDFs", parts.len());
+}
+
+/// Key/value pairs of a number tree, in no particular order
+fn collect_number_tree(doc: &Document, node: &Object, entries: &mut Vec<(i64, Object)>, depth: usize) {
+    if depth > MAX_TREE_DEPTH {
+        return;
+    }
+    let Ok(node) = doc.dereference(node).and_then(|(_, node)| node.as_dict()) else {
+        return;
+    };
+
+    if let Ok(nums) = node.get(b"Nums").and_then(Object::as_array) {
+        for pair in nums.chunks_exact(2) {
+            if let Ok(key) = pair[0].as_i64() {
+                entries.push((key, pair[1].clone()));
+            }
+        }
+    }
+    if let Ok(kids) = node.get(b"Kids").and_then(Object::as_array) {
+        for kid in kids {
+            collect_number_tree(doc, kid, entries, depth + 1);
+        }
+    }
+}
+
+/// Cover, Markdown front matter and nav pages written by the downloader.
+/// Only its exact names count: a content page at `/cover` or `/guide/nav`, or
+/// a user's `book_cover.pdf`, is content.
+fn is_front_matter_file(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| {
+        let name = name.to_string_lossy();
+        name == COVER_FILE || name == NAV_FILE || front_matter_name(&name).is_some()
+    })
+}
+
+/// `preface` for `01_front_02-preface.pdf`. Content pages are numbered from 02,
+/// so one whose slug happens to start with `front_` doesn't match.
+fn front_matter_name(filename: &str) -> Option<&str> {
+    let rest = filename.strip_prefix("01_front_")?.strip_suffix(".pdf")?;
+    let (number, name) = rest.split_once('-')?;
+    number.chars().all(|c| c.is_ascii_digit()).then_some(name)
+}
+
+/// Moves every page directly under `pages_id`.
+///
+/// The merge flattens all page trees into one `/Kids` array, so attributes a page
+/// used to inherit from its old ancestors (`/MediaBox`, `/Resources`, ...) are
+/// copied onto the page itself before it is re-parented. `pages_id` is then
+/// cleared of them: it was the first document's root, and pages of mixed sizes
+/// must not fall back to that document's defaults. A page without any
+/// `/MediaBox` gets US Letter, the size readers assume for it, explicitly.
+pub(crate) fn flatten_page_tree(doc: &mut Document, pages_id: ObjectId, page_ids: &[ObjectId]) {
+    for &page_id in page_ids {
+        let Ok(page) = doc.get_dictionary(page_id) else {
+            continue;
+        };
+
+        let mut inherited: Vec<(&[u8], Object)> = Vec::new();
+        let mut parent = page.get(b"Parent").and_then(Object::as_reference).ok();
+        let mut depth = 0;
+
+        while let Some(parent_id) = parent.filter(|_| depth < MAX_TREE_DEPTH) {
+            let Ok(node) = doc.get_dictionary(parent_id) else {
+                break;
+            };
+            for key in INHERITABLE_ATTRIBUTES {
+                let already_set = page.has(key) || inherited.iter().any(|(k, _)| *k == key);
+                if let (false, Ok(value)) = (already_set, node.get(key)) {
+                    inherited.push((key, value.clone()));
+                }
+            }
+            parent = node.get(b"Parent").and_then(Object::as_reference).ok();
+            depth += 1;
+        }
+
+        if let Ok(page) = doc.get_dictionary_mut(page_id) {
+            for (key, value) in inherited {
+                page.set(key, value);
+            }
+            if !page.has(b"MediaBox") {
+                warn!("Page {:?} has no MediaBox, giving it US Letter size", page_id);
+                let (width, height) = PageSize::Letter.dimensions();
+                page.set("MediaBox", vec![0.into(), 0.into(), width.into(), height.into()]);
+            }
+            page.set("Parent", Object::Reference(pages_id));
+        }
+    }
+
+    if let Ok(root) = doc.get_dictionary_mut(pages_id) {
+        for key in INHERITABLE_ATTRIBUTES {
+            root.remove(key);
+        }
+    }
+}
+
+/// Gives every page the same `/MediaBox`, scaling its content down (or up) to
+/// fit and centering it on the new sheet. Link annotations are moved along.
+fn normalize_page_sizes(doc: &mut Document, size: PageSize) -> Result<()> {
+    let (width, height) = size.dimensions();
+    let mut resized = 0;
+
+    for page_id in doc.get_pages().into_values() {
+        let Some([x0, y0, x1, y1]) = page_box(doc, page_id) else {
+            warn!("Page {:?} has no usable MediaBox, leaving its size unchanged", page_id);
+            continue;
+        };
+
+        let (page_width, page_height) = (x1 - x0, y1 - y0);
+        if (page_width - width).abs() < 0.5 && (page_height - height).abs() < 0.5 && x0 == 0.0 && y0 == 0.0 {
+            continue;
+        }
+
+        let scale = (width / page_width).min(height / page_height);
+        let tx = (width - page_width * scale) / 2.0 - x0 * scale;
+        let ty = (height - page_height * scale) / 2.0 - y0 * scale;
+
+        // Wrap the existing content streams in a transform
+        let transform = format!("q {} 0 0 {} {} {} cm\n", scale, scale, tx, ty);
+        let prefix_id = doc.add_object(Stream::new(dictionary! {}, transform.into_bytes()));
+        let suffix_id = doc.add_object(Stream::new(dictionary! {}, b"\nQ".to_vec()));
+
+        let annotations = doc
+            .get_dictionary(page_id)
+            .and_then(|page| page.get(b"Annots"))
+            .and_then(|annots| doc.dereference(annots))
+            .and_then(|(_, annots)| annots.as_array())
+            .map(|annots| annots.iter().filter_map(|a| a.as_reference().ok()).collect::<Vec<_>>())
+            .unwrap_or_default();
+        for annotation_id in annotations {
+            if let Ok(annotation) = doc.get_dictionary_mut(annotation_id) {
+                transform_rect(annotation, scale, tx, ty);
+            }
+        }
+
+        let page = doc
+            .get_dictionary_mut(page_id)
+            .map_err(|e| anyhow!("Failed to access page {:?}: {}", page_id, e))?;
+
+        let mut contents = vec![Object::Reference(prefix_id)];
+        match page.get(b"Contents") {
+            Ok(Object::Array(streams)) => contents.extend(streams.iter().cloned()),
+            Ok(stream) => contents.push(stream.clone()),
+            Err(_) => {}
+        }
+        contents.push(Object::Reference(suffix_id));
+
+        page.set("Contents", contents);
+        page.set("MediaBox", vec![0.into(), 0.into(), width.into(), height.into()]);
+        page.remove(b"CropBox");
+        if let Ok(Object::Array(annots)) = page.get_mut(b"Annots") {
+            for annotation in annots.iter_mut() {
+                if let Object::Dictionary(annotation) = annotation {
+                    transform_rect(annotation, scale, tx, ty);
+                }
+            }
+        }
+        resized += 1;
+    }
+
+    debug!("Resized {} pages to {} ({}x{} pt)", resized, size, width, height);
+    Ok(())
+}
+
+/// The page's `/MediaBox` as `[x0, y0, x1, y1]`, if it is a valid rectangle
+fn page_box(doc: &Document, page_id: ObjectId) -> Option<[f32; 4]> {
+    let media_box = doc.get_dictionary(page_id).ok()?.get(b"MediaBox").ok()?;
+    let (_, media_box) = doc.dereference(media_box).ok()?;
+    let values = media_box
+        .as_array()
+        .ok()?
+        .iter()
+        .map(|value| doc.dereference(value).ok()?.1.as_float().ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    let [x0, y0, x1, y1] = <[f32; 4]>::try_from(values).ok()?;
+    let rect = [x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)];
+    (rect[2] > rect[0] && rect[3] > rect[1]).then_some(rect)
+}
+
+fn transform_rect(annotation: &mut lopdf::Dictionary, scale: f32, tx: f32, ty: f32) {
+    let Ok(Object::Array(rect)) = annotation.get_mut(b"Rect") else {
+        return;
+    };
+    for (i, value) in rect.iter_mut().enumerate() {
+        if let Ok(v) = value.as_float() {
+            let offset = if i % 2 == 0 { tx } else { ty };
+            *value = Object::Real(v * scale + offset);
+        }
+    }
+}
+
+/// Bookmark title of a cover or nav page
+fn front_matter_title(filename: &str, document: &Document) -> String {
+    if filename == COVER_FILE {
+        "Cover".to_string()
+    } else if filename == NAV_FILE {
+        "Table of Contents".to_string()
+    } else if let Some(name) = front_matter_name(filename) {
+        // Rendered from Markdown, titled after its first heading
+        document_title(document).unwrap_or_else(|| name.to_string())
+    } else {
+        filename_title(filename)
+    }
+}
+
+/// The `/Title` from the document's info dictionary (Chrome copies the page's `<title>`)
+fn document_title(document: &Document) -> Option<String> {
+    let info = document.trailer.get(b"Info").ok()?;
+    let (_, info) = document.dereference(info).ok()?;
+    let title = decode_text_string(info.as_dict().ok()?.get(b"Title").ok()?.as_str().ok()?);
+    let title = title.trim();
+    (!title.is_empty()).then(|| title.to_string())
+}
+
+/// Decodes a PDF text string: UTF-16BE when it starts with a byte order mark, Latin-1 otherwise
+pub(crate) fn decode_text_string(bytes: &[u8]) -> String {
+    match bytes {
+        [0xFE, 0xFF, utf16 @ ..] => String::from_utf16_lossy(
+            &utf16.chunks_exact(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect::<Vec<_>>(),
+        ),
+        bytes => bytes.iter().map(|&b| b as char).collect(),
+    }
+}
+
+/// `02_getting-started.pdf` -> `getting-started`
+pub(crate) fn filename_title(filename: &str) -> String {
+    let stem = filename.strip_suffix(".pdf").unwrap_or(filename);
+    match stem.split_once('_') {
+        Some((number, rest)) if number.chars().all(|c| c.is_ascii_digit()) => rest.to_string(),
+        _ => stem.to_string(),
+    }
+}
+
+/// `getting-started_guide` as "Getting Started Guide": separators become spaces
+/// and each word gets a capital, leaving the rest of it alone (so "API" stays)
+fn filename_bookmark(slug: &str) -> String {
+    let words: Vec<String> = slug
+        .split(['-', '_', ' '])
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars.next().map_or_else(String::new, |first| first.to_uppercase().chain(chars).collect())
+        })
+        .collect();
+
+    match words.join(" ") {
+        title if title.is_empty() => slug.to_string(),
+        title => title,
+    }
+}
+
+/// A PDF text string: literal for ASCII, UTF-16BE with a byte order mark otherwise
+fn text_string(text: &str) -> Object {
+    if text.is_ascii() {
+        Object::String(text.as_bytes().to_vec(), StringFormat::Literal)
+    } else {
+        let mut bytes = vec![0xFE, 0xFF];
+        bytes.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+        Object::String(bytes, StringFormat::Hexadecimal)
+    }
 }
 
 impl Default for PdfMerger {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::Dictionary;
+
+    /// An empty directory for one test's files
+    fn scratch_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("book2pdf-{}-{}", test, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// A PDF with one empty page per entry, given that entry's size as its own
+    /// `/MediaBox`; `None` leaves the page without one
+    fn pdf_with_pages(sizes: &[Option<(i64, i64)>]) -> Document {
+        let mut doc = Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+
+        let kids: Vec<Object> = sizes
+            .iter()
+            .map(|size| {
+                let contents = doc.add_object(Stream::new(dictionary! {}, b"BT ET".to_vec()));
+                let mut page = dictionary! {
+                    "Type" => "Page",
+                    "Parent" => pages_id,
+                    "Contents" => contents,
+                };
+                if let Some((width, height)) = size {
+                    page.set("MediaBox", vec![0.into(), 0.into(), (*width).into(), (*height).into()]);
+                }
+                doc.add_object(page).into()
+            })
+            .collect();
+
+        doc.objects.insert(pages_id, Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Count" => kids.len() as i64,
+            "Kids" => kids,
+        }));
+        let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.trailer.set("Root", catalog_id);
+        doc
+    }
+
+    /// Saves `doc` as `name` in `dir`
+    fn write_pdf(dir: &Path, name: &str, mut doc: Document) -> PathBuf {
+        let path = dir.join(name);
+        doc.save(&path).unwrap();
+        path
+    }
+
+    /// Writes `files` to a scratch directory, merges them in that order and
+    /// returns the output file
+    async fn merged_bytes(test: &str, files: Vec<(&str, Document)>, options: MergeOptions) -> Vec<u8> {
+        let dir = scratch_dir(test);
+        let paths: Vec<PathBuf> = files.into_iter().map(|(name, doc)| write_pdf(&dir, name, doc)).collect();
+        let output = dir.join("merged.pdf");
+        PdfMerger::merge_files(&paths, &output, options).await.unwrap();
+        std::fs::read(&output).unwrap()
+    }
+
+    /// [`merged_bytes`], parsed
+    async fn merge(test: &str, files: Vec<(&str, Document)>, options: MergeOptions) -> Document {
+        Document::load_mem(&merged_bytes(test, files, options).await).expect("the output parses as a PDF")
+    }
+
+    /// Held by the tests that read or set `SOURCE_DATE_EPOCH`, which is
+    /// process-wide
+    static SOURCE_DATE_EPOCH_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+    /// A few chapters of one to three pages, as files to merge
+    fn chapters() -> Vec<(String, Document)> {
+        (0..12)
+            .map(|chapter| {
+                let pages = vec![Some((612, 792)); chapter % 3 + 1];
+                (format!("{:02}_chapter-{}.pdf", chapter + 2, chapter + 1), pdf_with_pages(&pages))
+            })
+            .collect()
+    }
+
+    /// The dictionary the catalog's `key` entry refers to
+    fn catalog_dictionary<'a>(doc: &'a Document, key: &[u8]) -> &'a Dictionary {
+        let id = doc.catalog().unwrap().get(key).and_then(Object::as_reference).unwrap();
+        doc.get_dictionary(id).unwrap()
+    }
+
+    /// Every page's own `/MediaBox`, in page order
+    fn media_boxes(doc: &Document) -> Vec<[f32; 4]> {
+        doc.get_pages()
+            .values()
+            .map(|&page_id| {
+                let page = doc.get_dictionary(page_id).unwrap();
+                let media_box = page.get(b"MediaBox").and_then(Object::as_array).expect("the page has its own MediaBox");
+                let corners: Vec<f32> = media_box.iter().map(|value| value.as_float().unwrap()).collect();
+                corners.try_into().unwrap()
+            })
+            .collect()
+    }
+
+    /// Compares boxes up to the rounding of reals written to the file
+    fn assert_same_box(found: [f32; 4], expected: [f32; 4]) {
+        assert!(found.iter().zip(expected).all(|(a, b)| (a - b).abs() < 0.01), "{:?} != {:?}", found, expected);
+    }
+
+    /// The outline item `item_id` and its first child, if it has children
+    fn outline_item(doc: &Document, item_id: ObjectId) -> (&Dictionary, Option<ObjectId>) {
+        let item = doc.get_dictionary(item_id).unwrap();
+        (item, item.get(b"First").and_then(Object::as_reference).ok())
+    }
+
+    /// `doc` saved as `name` in `dir`, with its `startxref` pointing past the
+    /// end of the file, as a truncated download or a bad edit leaves it
+    fn write_pdf_with_broken_xref(dir: &Path, name: &str, doc: Document) -> PathBuf {
+        let path = write_pdf(dir, name, doc);
+        let data = std::fs::read(&path).unwrap();
+        let keyword = data.windows(9).rposition(|window| window == b"startxref").unwrap();
+        let mut broken = data[..keyword].to_vec();
+        broken.extend_from_slice(b"startxref\n99999999\n%%EOF\n");
+        std::fs::write(&path, broken).unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn repairs_a_broken_xref_in_lenient_mode() {
+        let dir = scratch_dir("lenient-repair");
+        let broken = write_pdf_with_broken_xref(&dir, "02_intro.pdf", pdf_with_pages(&[Some((612, 792)); 3]));
+        let output = dir.join("merged.pdf");
+
+        let options = MergeOptions { lenient: true, ..Default::default() };
+        let result = PdfMerger::merge_files(&[broken], &output, options).await.unwrap();
+
+        assert_eq!(result.page_count, 3);
+        assert_eq!(result.lenient_loads, ["02_intro.pdf"]);
+        assert_eq!(Document::load(&output).unwrap().get_pages().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn skips_a_broken_xref_without_lenient_mode() {
+        let dir = scratch_dir("strict-broken-xref");
+        let broken = write_pdf_with_broken_xref(&dir, "02_intro.pdf", pdf_with_pages(&[Some((612, 792)); 3]));
+        let intact = write_pdf(&dir, "03_setup.pdf", pdf_with_pages(&[Some((612, 792))]));
+        let output = dir.join("merged.pdf");
+
+        let result = PdfMerger::merge_files(&[broken.clone(), intact], &output, MergeOptions::default()).await.unwrap();
+
+        assert_eq!(result.page_count, 1);
+        assert_eq!(result.skipped.len(), 1);
+        assert_eq!(result.skipped[0].0, broken);
+        assert!(result.lenient_loads.is_empty());
+    }
+
+    /// `names` as paths, for the tests of orders and inserts
+    fn paths(names: &[&str]) -> Vec<PathBuf> {
+        names.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn arranges_pages_in_natural_reverse_and_custom_order() {
+        let files = paths(&["02_a.pdf", "03_b.pdf", "04_c.pdf"]);
+
+        assert_eq!(MergeOrder::Natural.arrange(&files).unwrap(), files);
+        assert_eq!(MergeOrder::Reverse.arrange(&files).unwrap(), paths(&["04_c.pdf", "03_b.pdf", "02_a.pdf"]));
+        assert_eq!(MergeOrder::Custom(vec![2, 3, 1]).arrange(&files).unwrap(), paths(&["03_b.pdf", "04_c.pdf", "02_a.pdf"]));
+        assert_eq!("3,1,2".parse::<MergeOrder>().unwrap().arrange(&files).unwrap(), paths(&["04_c.pdf", "02_a.pdf", "03_b.pdf"]));
+    }
+
+    #[test]
+    fn rejects_custom_orders_that_dont_list_every_file_once() {
+        let files = paths(&["02_a.pdf", "03_b.pdf"]);
+
+        let invalid = [
+            vec![0, 1],    // positions are 1-based
+            vec![1, 3],    // there is no third file
+            vec![1, 1, 2], // the first file twice
+            vec![2, 2],    // the second twice, the first never
+            vec![2],       // the first is missing
+            vec![],
+        ];
+        for order in invalid {
+            assert!(MergeOrder::Custom(order.clone()).arrange(&files).is_err(), "{:?} was accepted", order);
+        }
+    }
+
+    #[test]
+    fn splices_inserts_at_their_positions() {
+        let files = paths(&["02_a.pdf", "03_b.pdf"]);
+        let insert = |position: usize, path: &str| PageInsert { position, path: PathBuf::from(path) };
+
+        // Files for the same position keep their order; one past the last appends
+        let inserts = [insert(2, "errata.pdf"), insert(1, "intro.pdf"), insert(2, "notes.pdf"), insert(3, "index.pdf")];
+        assert_eq!(
+            PageInsert::apply(&inserts, &files).unwrap(),
+            paths(&["intro.pdf", "02_a.pdf", "errata.pdf", "notes.pdf", "03_b.pdf", "index.pdf"])
+        );
+        assert_eq!(PageInsert::apply(&[], &files).unwrap(), files);
+
+        assert!(PageInsert::apply(&[insert(4, "late.pdf")], &files).is_err());
+        assert!(PageInsert::apply(&[insert(0, "early.pdf")], &files).is_err());
+    }
+
+    #[test]
+    fn counts_only_the_downloaders_own_front_matter() {
+        let paths = |names: &[&str]| -> Vec<PathBuf> { names.iter().map(|name| PathBuf::from("pages").join(name)).collect() };
+
+        let downloaded = paths(&["01_cover.pdf", "01_front_01-preface.pdf", "01_nav.pdf", "02_intro.pdf"]);
+        assert_eq!(PdfMerger::front_matter_count(&downloaded), 3);
+
+        // Content pages whose slugs end in cover or nav, and a user's own cover
+        for first in ["02_cover.pdf", "02_nav.pdf", "02_guide_nav.pdf", "book_cover.pdf"] {
+            assert_eq!(PdfMerger::front_matter_count(&paths(&[first, "03_setup.pdf"])), 0, "{}", first);
+        }
+    }
+
+    #[tokio::test]
+    async fn writes_identical_bytes_when_deterministic() {
+        let _env = SOURCE_DATE_EPOCH_LOCK.lock().await;
+
+        for epoch in [None, Some("1700000000")] {
+            match epoch {
+                Some(epoch) => std::env::set_var("SOURCE_DATE_EPOCH", epoch),
+                None => std::env::remove_var("SOURCE_DATE_EPOCH"),
+            }
+            let mut outputs = Vec::new();
+            for run in 0..2 {
+                // A second apart, so dates left in the file would differ
+                if run > 0 {
+                    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+                }
+                let files = chapters();
+                let files = files.iter().map(|(name, doc)| (name.as_str(), doc.clone())).collect();
+                let options = MergeOptions { deterministic: true, bookmarks: true, ..Default::default() };
+                outputs.push(merged_bytes(&format!("deterministic-{}-{}", epoch.is_some(), run), files, options).await);
+            }
+            assert!(outputs[0] == outputs[1], "two merges of the same files differ (SOURCE_DATE_EPOCH {:?})", epoch);
+
+            let merged = Document::load_mem(&outputs[0]).unwrap();
+            let info = merged.trailer.get(b"Info").and_then(Object::as_reference).and_then(|id| merged.get_dictionary(id)).ok();
+            let date = |key: &[u8]| info.and_then(|info| info.get(key).and_then(Object::as_str).ok()).map(<[u8]>::to_vec);
+            let expected = epoch.map(|_| b"D:20231114221320Z".to_vec());
+            assert_eq!(date(b"CreationDate"), expected);
+            assert_eq!(date(b"ModDate"), expected);
+        }
+        std::env::remove_var("SOURCE_DATE_EPOCH");
+    }
+
+    #[tokio::test]
+    async fn writes_identical_bytes_whatever_the_load_threads() {
+        let _env = SOURCE_DATE_EPOCH_LOCK.lock().await;
+
+        let mut outputs = Vec::new();
+        for threads in [1, 8] {
+            let files = chapters();
+            let files = files.iter().map(|(name, doc)| (name.as_str(), doc.clone())).collect();
+            let options = MergeOptions { deterministic: true, load_threads: Some(threads), ..Default::default() };
+            outputs.push(merged_bytes(&format!("load-threads-{}", threads), files, options).await);
+        }
+        assert!(outputs[0] == outputs[1], "merging with 1 and 8 load threads gave different files");
+    }
+
+    #[test]
+    fn sorts_page_files_by_number_then_unnumbered_by_name() {
+        let mut expected: Vec<PathBuf> = (0..150).map(|number| PathBuf::from(format!("{:02}_page-{}.pdf", number, number))).collect();
+        expected.extend(["appendix.pdf", "errata.pdf"].map(PathBuf::from));
+
+        let mut files = expected.clone();
+        fastrand::Rng::with_seed(7).shuffle(&mut files);
+        files.sort_by_key(|path| natural_sort_key(path));
+        assert_eq!(files, expected);
+    }
+
+    #[tokio::test]
+    async fn saves_a_single_document() {
+        let dir = scratch_dir("single-document");
+        let input = write_pdf(&dir, "01_intro.pdf", pdf_with_pages(&[Some((612, 792)); 3]));
+        let output = dir.join("merged.pdf");
+
+        let mut merger = PdfMerger::new();
+        merger.add_pdf(&input).await.unwrap();
+        merger.save(&output).await.unwrap();
+
+        let merged = Document::load(&output).expect("the output parses as a PDF");
+        assert_eq!(merged.get_pages().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn rejects_a_merge_that_lost_pages() {
+        let dir = scratch_dir("validate-output");
+        let input = write_pdf(&dir, "02_intro.pdf", pdf_with_pages(&[Some((612, 792)); 3]));
+        let output = dir.join("merged.pdf");
+
+        let mut merger = PdfMerger::new();
+        merger.add_pdf(&input).await.unwrap();
+
+        let mut short = Vec::new();
+        pdf_with_pages(&[Some((612, 792)); 2]).save_to(&mut short).unwrap();
+        let error = merger.validate_output(&output, &short).unwrap_err();
+        assert!(error.to_string().contains("has 2 pages but its sources have 3"), "{}", error);
+
+        let mut complete = Vec::new();
+        pdf_with_pages(&[Some((612, 792)); 3]).save_to(&mut complete).unwrap();
+        merger.validate_output(&output, &complete).unwrap();
+    }
+
+    #[tokio::test]
+    async fn labels_front_matter_in_roman_and_content_from_one() {
+        let letter = Some((612, 792));
+        let files = vec![
+            ("01_cover.pdf", pdf_with_pages(&[letter])),
+            ("01_nav.pdf", pdf_with_pages(&[letter, letter])),
+            ("02_intro.pdf", pdf_with_pages(&[letter, letter])),
+            ("03_setup.pdf", pdf_with_pages(&[letter])),
+        ];
+        let options = MergeOptions { page_labels: true, ..Default::default() };
+        let merged = merge("page-labels", files, options).await;
+
+        let nums = catalog_dictionary(&merged, b"PageLabels").get(b"Nums").and_then(Object::as_array).unwrap();
+        assert_eq!(nums.len(), 4);
+
+        // Pages 0-2 (cover and TOC) in lowercase roman numerals
+        assert_eq!(nums[0].as_i64().unwrap(), 0);
+        let front_matter = nums[1].as_dict().unwrap();
+        assert_eq!(front_matter.get(b"S").and_then(Object::as_name).unwrap(), b"r");
+
+        // The content in decimal, restarting at 1 (no /St means 1)
+        assert_eq!(nums[2].as_i64().unwrap(), 3);
+        let content = nums[3].as_dict().unwrap();
+        assert_eq!(content.get(b"S").and_then(Object::as_name).unwrap(), b"D");
+        assert!(content.get(b"St").map_or(true, |start| start.as_i64().unwrap() == 1));
+    }
+
+    #[tokio::test]
+    async fn gives_pages_of_mixed_sizes_one_uniform_size() {
+        let files = vec![
+            ("02_intro.pdf", pdf_with_pages(&[Some((300, 400))])),
+            ("03_setup.pdf", pdf_with_pages(&[Some((612, 3000)), Some((842, 595))])),
+        ];
+        let options = MergeOptions { uniform_page_size: Some(PageSize::A4), ..Default::default() };
+        let merged = merge("uniform-page-size", files, options).await;
+
+        let (width, height) = PageSize::A4.dimensions();
+        let boxes = media_boxes(&merged);
+        assert_eq!(boxes.len(), 3);
+        for media_box in boxes {
+            assert_same_box(media_box, [0.0, 0.0, width, height]);
+        }
+    }
+
+    #[tokio::test]
+    async fn keeps_each_page_size_on_the_page() {
+        // The landscape page only inherits its size from its /Pages node
+        let mut inherited = pdf_with_pages(&[None]);
+        let pages_id = inherited.catalog().unwrap().get(b"Pages").and_then(Object::as_reference).unwrap();
+        inherited
+            .get_dictionary_mut(pages_id)
+            .unwrap()
+            .set("MediaBox", vec![0.into(), 0.into(), 842.into(), 595.into()]);
+
+        let files = vec![
+            ("02_intro.pdf", pdf_with_pages(&[Some((300, 400)), Some((612, 3000))])),
+            ("03_setup.pdf", inherited),
+            ("04_faq.pdf", pdf_with_pages(&[None])),
+        ];
+        let merged = merge("per-page-size", files, MergeOptions::default()).await;
+
+        let boxes = media_boxes(&merged);
+        assert_eq!(boxes.len(), 4);
+        assert_same_box(boxes[0], [0.0, 0.0, 300.0, 400.0]);
+        assert_same_box(boxes[1], [0.0, 0.0, 612.0, 3000.0]);
+        assert_same_box(boxes[2], [0.0, 0.0, 842.0, 595.0]);
+        // A page with no size anywhere is given US Letter
+        assert_same_box(boxes[3], [0.0, 0.0, 612.0, 792.0]);
+
+        // Nothing is left on the root for a page to inherit
+        let root = catalog_dictionary(&merged, b"Pages");
+        let mut keys: Vec<&[u8]> = root.iter().map(|(key, _)| key.as_slice()).collect();
+        keys.sort();
+        assert_eq!(keys, [b"Count".as_slice(), b"Kids", b"Type"]);
+    }
+
+    #[tokio::test]
+    async fn keeps_link_annotations() {
+        for compress in [false, true] {
+            let mut setup = pdf_with_pages(&[Some((612, 792))]);
+            let page_id = setup.page_iter().next().unwrap();
+            let external = setup.add_object(dictionary! {
+                "Type" => "Annot",
+                "Subtype" => "Link",
+                "Rect" => vec![72.into(), 700.into(), 300.into(), 720.into()],
+                "A" => dictionary! { "S" => "URI", "URI" => Object::string_literal("https://docs.example.com/api") },
+            });
+            let internal = setup.add_object(dictionary! {
+                "Type" => "Annot",
+                "Subtype" => "Link",
+                "Rect" => vec![72.into(), 650.into(), 300.into(), 670.into()],
+                "Dest" => vec![page_id.into(), "Fit".into()],
+            });
+            setup.get_dictionary_mut(page_id).unwrap().set("Annots", vec![external.into(), internal.into()]);
+
+            let files = vec![
+                ("02_intro.pdf", pdf_with_pages(&[Some((612, 792))])),
+                ("03_setup.pdf", setup),
+            ];
+            let options = MergeOptions { compress, ..Default::default() };
+            let merged = merge(&format!("link-annotations-{}", compress), files, options).await;
+
+            let merged_page_id = merged.page_iter().nth(1).unwrap();
+            let annots = merged.get_dictionary(merged_page_id).unwrap().get(b"Annots").and_then(Object::as_array).unwrap();
+            assert_eq!(annots.len(), 2);
+            let external = merged.get_dictionary(annots[0].as_reference().unwrap()).unwrap();
+            let internal = merged.get_dictionary(annots[1].as_reference().unwrap()).unwrap();
+
+            assert_eq!(external.get(b"Subtype").and_then(Object::as_name).unwrap(), b"Link");
+            let uri = external.get(b"A").and_then(Object::as_dict).and_then(|action| action.get(b"URI")).unwrap();
+            assert_eq!(uri.as_str().unwrap(), b"https://docs.example.com/api");
+
+            // Renumbering has to keep the destination on the same page
+            let dest = internal.get(b"Dest").and_then(Object::as_array).unwrap();
+            assert_eq!(dest[0].as_reference().unwrap(), merged_page_id);
+        }
+    }
+
+    #[tokio::test]
+    async fn signs_outline_counts_by_whether_items_open() {
+        // guide > install > linux, then faq back at the top level
+        let nav_depths = HashMap::from([
+            ("guide".to_string(), 0),
+            ("install".to_string(), 1),
+            ("linux".to_string(), 2),
+            ("faq".to_string(), 0),
+        ]);
+
+        for collapsed in [false, true] {
+            let letter = Some((612, 792));
+            let files = vec![
+                ("02_guide.pdf", pdf_with_pages(&[letter])),
+                ("03_install.pdf", pdf_with_pages(&[letter])),
+                ("04_linux.pdf", pdf_with_pages(&[letter])),
+                ("05_faq.pdf", pdf_with_pages(&[letter])),
+            ];
+            let options = MergeOptions {
+                bookmarks: true,
+                outline_collapsed: collapsed,
+                nav_depths: nav_depths.clone(),
+                ..Default::default()
+            };
+            let merged = merge(&format!("outline-counts-{}", collapsed), files, options).await;
+
+            let outlines = catalog_dictionary(&merged, b"Outlines");
+            let guide_id = outlines.get(b"First").and_then(Object::as_reference).unwrap();
+            let (guide, install_id) = outline_item(&merged, guide_id);
+            let (install, linux_id) = outline_item(&merged, install_id.unwrap());
+            let (linux, _) = outline_item(&merged, linux_id.unwrap());
+
+            let count = |item: &Dictionary| item.get(b"Count").and_then(Object::as_i64).unwrap();
+            if collapsed {
+                // Closed items count their direct children, negated; only the
+                // two top-level items show
+                assert_eq!(count(outlines), 2);
+                assert_eq!(count(guide), -1);
+                assert_eq!(count(install), -1);
+            } else {
+                // Open items count everything visible below them
+                assert_eq!(count(outlines), 4);
+                assert_eq!(count(guide), 2);
+                assert_eq!(count(install), 1);
+            }
+            assert!(linux.get(b"Count").is_err(), "an item without children has no /Count");
+        }
+    }
+
+    #[tokio::test]
+    async fn titles_bookmarks_after_file_names() {
+        let mut titled = pdf_with_pages(&[Some((612, 792))]);
+        let info_id = titled.add_object(dictionary! { "Title" => Object::string_literal("Untitled document") });
+        titled.trailer.set("Info", info_id);
+
+        let files = vec![
+            ("02_intro.pdf", pdf_with_pages(&[Some((612, 792))])),
+            ("03_getting-started.pdf", titled),
+        ];
+        let options = MergeOptions { bookmarks: true, bookmarks_from_filenames: true, ..Default::default() };
+        let merged = merge("bookmarks-from-filenames", files, options).await;
+
+        let outlines = catalog_dictionary(&merged, b"Outlines");
+        let last_id = outlines.get(b"Last").and_then(Object::as_reference).unwrap();
+        let (last, _) = outline_item(&merged, last_id);
+        let title = last.get(b"Title").and_then(Object::as_str).unwrap();
+        assert_eq!(String::from_utf8_lossy(title), "Getting Started");
+    }
+}
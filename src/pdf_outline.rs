@@ -0,0 +1,191 @@
+use anyhow::{anyhow, Result};
+use lopdf::{Document, Object, ObjectId};
+use std::collections::HashSet;
+
+use crate::pdf_merger::decode_text_string;
+
+/// A bookmark and the bookmarks nested under it
+pub(crate) struct OutlineNode {
+    pub title: String,
+    pub page: ObjectId,
+    /// How the page is shown after the jump, the rest of a destination array
+    /// (`/XYZ left top zoom` for headings); `/Fit` when empty
+    pub view: Vec<Object>,
+    pub children: Vec<OutlineNode>,
+}
+
+/// Cuts a PDF's outline off below `max_depth` levels and returns the rewritten file.
+///
+/// Chrome's `generateDocumentOutline` nests a bookmark for every heading, `h1`
+/// through `h6`; the deep levels are mostly noise in a page's outline. Chrome
+/// nests by heading level, so on a page without an `h1` the limit is relative
+/// to its top-level headings. Files without an outline come back unchanged.
+pub(crate) fn limit_outline_depth(data: &[u8], max_depth: usize) -> Result<Vec<u8>> {
+    let mut doc = Document::load_mem(data).map_err(|e| anyhow!("Failed to parse PDF: {}", e))?;
+
+    let Some(root_id) = doc
+        .catalog()
+        .ok()
+        .and_then(|catalog| catalog.get(b"Outlines").and_then(Object::as_reference).ok())
+    else {
+        return Ok(data.to_vec());
+    };
+
+    let visible = prune_items(&mut doc, root_id, 0, max_depth, &mut HashSet::new());
+    if let Ok(root) = doc.get_dictionary_mut(root_id) {
+        root.set("Count", visible);
+    }
+    doc.prune_objects();
+
+    let mut pruned = Vec::new();
+    doc.save_to(&mut pruned)
+        .map_err(|e| anyhow!("Failed to serialize PDF: {}", e))?;
+    Ok(pruned)
+}
+
+/// Drops the children of `parent_id`'s items that sit deeper than `max_depth`,
+/// fixing up `/Count` on the way, and returns how many items stay visible below it
+fn prune_items(doc: &mut Document, parent_id: ObjectId, depth: usize, max_depth: usize, visited: &mut HashSet<ObjectId>) -> i64 {
+    let first = doc
+        .get_dictionary(parent_id)
+        .ok()
+        .and_then(|parent| parent.get(b"First").and_then(Object::as_reference).ok());
+
+    let mut visible = 0;
+    let mut item = first;
+    while let Some(item_id) = item.filter(|id| visited.insert(*id)) {
+        let Ok(dict) = doc.get_dictionary(item_id) else {
+            break;
+        };
+        let next = dict.get(b"Next").and_then(Object::as_reference).ok();
+        let open = dict.get(b"Count").and_then(Object::as_i64).map_or(true, |count| count >= 0);
+        visible += 1;
+
+        if depth + 1 >= max_depth {
+            if let Ok(dict) = doc.get_dictionary_mut(item_id) {
+                for key in [b"First".as_slice(), b"Last", b"Count"] {
+                    dict.remove(key);
+                }
+            }
+        } else {
+            let below = prune_items(doc, item_id, depth + 1, max_depth, visited);
+            if let Ok(dict) = doc.get_dictionary_mut(item_id) {
+                if below == 0 {
+                    dict.remove(b"Count");
+                } else if open {
+                    dict.set("Count", below);
+                    visible += below;
+                } else {
+                    dict.set("Count", -below);
+                }
+            }
+        }
+        item = next;
+    }
+
+    visible
+}
+
+/// A PDF's outline as bookmarks, with the nesting kept.
+///
+/// Destinations are read from `/Dest` or a `/GoTo` action, given directly or by
+/// name through the catalog's `/Dests` or its `/Names` tree. An item whose
+/// destination isn't a page of `doc` is dropped and its children take its
+/// place. Files without an outline have no bookmarks.
+pub(crate) fn read_outline(doc: &Document) -> Vec<OutlineNode> {
+    let Some(root_id) = doc
+        .catalog()
+        .ok()
+        .and_then(|catalog| catalog.get(b"Outlines").and_then(Object::as_reference).ok())
+    else {
+        return Vec::new();
+    };
+
+    let pages: HashSet<ObjectId> = doc.get_pages().into_values().collect();
+    read_items(doc, root_id, &pages, &mut HashSet::new())
+}
+
+fn read_items(doc: &Document, parent_id: ObjectId, pages: &HashSet<ObjectId>, visited: &mut HashSet<ObjectId>) -> Vec<OutlineNode> {
+    let mut nodes = Vec::new();
+    let mut item = doc
+        .get_dictionary(parent_id)
+        .ok()
+        .and_then(|parent| parent.get(b"First").and_then(Object::as_reference).ok());
+
+    while let Some(item_id) = item.filter(|id| visited.insert(*id)) {
+        let Ok(dict) = doc.get_dictionary(item_id) else {
+            break;
+        };
+        item = dict.get(b"Next").and_then(Object::as_reference).ok();
+
+        let children = read_items(doc, item_id, pages, visited);
+        let title = dict
+            .get(b"Title")
+            .and_then(Object::as_str)
+            .map(|title| decode_text_string(title).trim().to_string())
+            .unwrap_or_default();
+        let dest = dict.get(b"Dest").ok().or_else(|| {
+            let action = doc.dereference(dict.get(b"A").ok()?).ok()?.1.as_dict().ok()?;
+            (action.get(b"S").and_then(Object::as_name).ok()? == b"GoTo").then(|| action.get(b"D").ok())?
+        });
+
+        match dest.and_then(|dest| resolve_dest(doc, dest)).filter(|(page, _)| pages.contains(page)) {
+            Some((page, view)) => nodes.push(OutlineNode { title, page, view, children }),
+            None => nodes.extend(children),
+        }
+    }
+
+    nodes
+}
+
+/// The page and view of a destination, following names and references
+fn resolve_dest(doc: &Document, dest: &Object) -> Option<(ObjectId, Vec<Object>)> {
+    let (_, dest) = doc.dereference(dest).ok()?;
+    match dest {
+        Object::Array(items) => {
+            let (page, view) = items.split_first()?;
+            Some((page.as_reference().ok()?, view.to_vec()))
+        }
+        // Named destinations may be wrapped in a dictionary with the array under /D
+        Object::Dictionary(dict) => resolve_dest(doc, dict.get(b"D").ok()?),
+        Object::Name(name) | Object::String(name, _) => resolve_dest(doc, named_dest(doc, name)?),
+        _ => None,
+    }
+}
+
+/// Looks `name` up in the catalog's `/Dests` dictionary (PDF 1.1) or the `/Dests`
+/// name tree under `/Names`
+fn named_dest<'a>(doc: &'a Document, name: &[u8]) -> Option<&'a Object> {
+    let catalog = doc.catalog().ok()?;
+    if let Some(dests) = catalog.get(b"Dests").ok().and_then(|dests| doc.dereference(dests).ok()) {
+        if let Ok(dest) = dests.1.as_dict().and_then(|dests| dests.get(name)) {
+            return Some(dest);
+        }
+    }
+
+    let names = doc.dereference(catalog.get(b"Names").ok()?).ok()?.1.as_dict().ok()?;
+    let tree = doc.dereference(names.get(b"Dests").ok()?).ok()?.1;
+    find_in_name_tree(doc, tree, name, &mut HashSet::new())
+}
+
+fn find_in_name_tree<'a>(doc: &'a Document, node: &'a Object, name: &[u8], visited: &mut HashSet<ObjectId>) -> Option<&'a Object> {
+    let node = node.as_dict().ok()?;
+    if let Ok(names) = node.get(b"Names").and_then(Object::as_array) {
+        let found = names
+            .chunks_exact(2)
+            .find(|pair| pair[0].as_str().is_ok_and(|key| key == name))
+            .map(|pair| &pair[1]);
+        if found.is_some() {
+            return found;
+        }
+    }
+
+    let kids = node.get(b"Kids").and_then(Object::as_array).ok()?;
+    kids.iter().find_map(|kid| {
+        let kid_id = kid.as_reference().ok()?;
+        if !visited.insert(kid_id) {
+            return None;
+        }
+        find_in_name_tree(doc, doc.get_object(kid_id).ok()?, name, visited)
+    })
+}
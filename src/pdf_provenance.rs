@@ -0,0 +1,83 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use lopdf::{dictionary, Document, Stream};
+
+/// Namespace of the XMP properties only book2pdf writes
+const BOOK2PDF_NS: &str = "https://github.com/0xheartcode/book2pdf/ns/1.0/";
+
+/// Where a merged PDF's pages came from
+pub(crate) struct Provenance<'a> {
+    /// Site the pages were captured from
+    pub source_url: Option<&'a str>,
+    /// Page URLs (or file names, for PDFs merged from disk) in book order
+    pub sources: &'a [String],
+    /// Left out when unknown, e.g. for deterministic output without `SOURCE_DATE_EPOCH`
+    pub exported_at: Option<DateTime<Utc>>,
+}
+
+/// Sets the catalog's `/Metadata` to an XMP packet recording `provenance`.
+///
+/// The site goes in `dc:source`, the export time in `xmp:CreateDate` and
+/// `xmp:MetadataDate`, the book2pdf version in `xmp:CreatorTool` and the pages
+/// in an ordered `book2pdf:Sources` list. Any packet already there (Chrome's,
+/// from the first page) is replaced. The stream stays uncompressed, as XMP
+/// readers that scan the file for packets expect.
+pub(crate) fn embed(doc: &mut Document, provenance: &Provenance) -> Result<()> {
+    let stream = Stream::new(
+        dictionary! { "Type" => "Metadata", "Subtype" => "XML" },
+        xmp_packet(provenance).into_bytes(),
+    )
+    .with_compression(false);
+    let metadata_id = doc.add_object(stream);
+
+    doc.catalog_mut()
+        .map_err(|e| anyhow!("Failed to find the catalog: {}", e))?
+        .set("Metadata", metadata_id);
+    Ok(())
+}
+
+fn xmp_packet(provenance: &Provenance) -> String {
+    let mut properties = String::new();
+    if let Some(url) = provenance.source_url {
+        properties.push_str(&format!("   <dc:source>{}</dc:source>\n", escape(url)));
+    }
+    if let Some(time) = provenance.exported_at {
+        let time = time.format("%Y-%m-%dT%H:%M:%SZ");
+        properties.push_str(&format!("   <xmp:CreateDate>{}</xmp:CreateDate>\n", time));
+        properties.push_str(&format!("   <xmp:MetadataDate>{}</xmp:MetadataDate>\n", time));
+    }
+    properties.push_str(&format!("   <xmp:CreatorTool>book2pdf {}</xmp:CreatorTool>\n", env!("CARGO_PKG_VERSION")));
+    if !provenance.sources.is_empty() {
+        properties.push_str("   <book2pdf:Sources>\n    <rdf:Seq>\n");
+        for source in provenance.sources {
+            properties.push_str(&format!("     <rdf:li>{}</rdf:li>\n", escape(source)));
+        }
+        properties.push_str("    </rdf:Seq>\n   </book2pdf:Sources>\n");
+    }
+
+    format!(
+        concat!(
+            "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n",
+            "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n",
+            " <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n",
+            "  <rdf:Description rdf:about=\"\"\n",
+            "    xmlns:dc=\"http://purl.org/dc/elements/1.1/\"\n",
+            "    xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\"\n",
+            "    xmlns:book2pdf=\"{}\">\n",
+            "{}",
+            "  </rdf:Description>\n",
+            " </rdf:RDF>\n",
+            "</x:xmpmeta>\n",
+            "<?xpacket end=\"w\"?>",
+        ),
+        BOOK2PDF_NS, properties
+    )
+}
+
+/// `text` with the characters XML reserves replaced by entities
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
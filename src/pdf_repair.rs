@@ -0,0 +1,140 @@
+use std::collections::BTreeMap;
+
+/// Rebuilds the cross-reference table of a damaged PDF from its object headers.
+///
+/// Scans the file for `N G obj` headers, keeps the last definition of every
+/// object, locates the catalog and appends a fresh xref table, trailer and
+/// `startxref`. Returns `None` when no catalog can be found.
+pub(crate) fn rebuild_xref(data: &[u8]) -> Option<Vec<u8>> {
+    let objects = scan_objects(data);
+    let root = find_reference(data, b"/Root").or_else(|| find_catalog(data, &objects))?;
+    let info = find_reference(data, b"/Info");
+
+    let size = objects.keys().next_back().map_or(1, |max| max + 1);
+
+    let mut repaired = data.to_vec();
+    repaired.extend_from_slice(b"\n");
+    let xref_offset = repaired.len();
+
+    repaired.extend_from_slice(format!("xref\n0 {}\n", size).as_bytes());
+    for id in 0..size {
+        let entry = match objects.get(&id) {
+            Some((offset, generation)) => format!("{:010} {:05} n \n", offset, generation),
+            None => "0000000000 65535 f \n".to_string(),
+        };
+        repaired.extend_from_slice(entry.as_bytes());
+    }
+
+    let mut trailer = format!("trailer\n<< /Size {} /Root {} {} R", size, root.0, root.1);
+    if let Some((id, generation)) = info {
+        trailer.push_str(&format!(" /Info {} {} R", id, generation));
+    }
+    trailer.push_str(&format!(" >>\nstartxref\n{}\n%%EOF\n", xref_offset));
+    repaired.extend_from_slice(trailer.as_bytes());
+
+    Some(repaired)
+}
+
+/// Object number -> (offset, generation) of every `N G obj` header, last one wins
+fn scan_objects(data: &[u8]) -> BTreeMap<u32, (usize, u16)> {
+    let mut objects = BTreeMap::new();
+    let mut pos = 0;
+
+    while let Some(found) = find(&data[pos..], b"obj") {
+        let keyword = pos + found;
+        pos = keyword + 3;
+
+        // "endobj" and names like "/objfoo" are not headers
+        if data.get(pos).is_some_and(|c| c.is_ascii_alphanumeric()) {
+            continue;
+        }
+        if let Some((start, id, generation)) = parse_header_before(data, keyword) {
+            objects.insert(id, (start, generation));
+        }
+    }
+
+    objects
+}
+
+/// Parses `N G` right before the `obj` keyword at `keyword`, returning where `N` starts
+fn parse_header_before(data: &[u8], keyword: usize) -> Option<(usize, u32, u16)> {
+    let mut i = keyword;
+
+    let generation_end = skip_whitespace_back(data, i)?;
+    i = skip_digits_back(data, generation_end);
+    let generation = parse_number(&data[i..generation_end])?;
+
+    let id_end = skip_whitespace_back(data, i)?;
+    i = skip_digits_back(data, id_end);
+    let id = parse_number(&data[i..id_end])?;
+
+    // The object number has to start a token
+    if i > 0 && !data[i - 1].is_ascii_whitespace() {
+        return None;
+    }
+
+    Some((i, id as u32, u16::try_from(generation).ok()?))
+}
+
+/// Finds the last `key N G R` reference in the file (trailers and xref streams)
+fn find_reference(data: &[u8], key: &[u8]) -> Option<(u32, u16)> {
+    let mut pos = data.len();
+
+    while let Some(found) = rfind(&data[..pos], key) {
+        pos = found;
+        let mut tokens = data[found + key.len()..]
+            .split(|c| c.is_ascii_whitespace())
+            .filter(|token| !token.is_empty());
+
+        let id = tokens.next().and_then(parse_number);
+        let generation = tokens.next().and_then(parse_number);
+        let is_reference = tokens.next().is_some_and(|token| token.starts_with(b"R"));
+
+        if let (Some(id), Some(generation), true) = (id, generation, is_reference) {
+            return Some((id as u32, u16::try_from(generation).ok()?));
+        }
+    }
+
+    None
+}
+
+/// Falls back to the object whose body declares `/Type /Catalog`
+fn find_catalog(data: &[u8], objects: &BTreeMap<u32, (usize, u16)>) -> Option<(u32, u16)> {
+    objects.iter().find_map(|(id, (offset, generation))| {
+        let body = &data[*offset..];
+        let end = find(body, b"endobj").unwrap_or(body.len());
+        let body = &body[..end];
+        let is_catalog = find(body, b"/Catalog").is_some() && find(body, b"/Type").is_some();
+        is_catalog.then_some((*id, *generation))
+    })
+}
+
+fn skip_whitespace_back(data: &[u8], mut i: usize) -> Option<usize> {
+    let end = i;
+    while i > 0 && data[i - 1].is_ascii_whitespace() {
+        i -= 1;
+    }
+    (i < end).then_some(i)
+}
+
+fn skip_digits_back(data: &[u8], mut i: usize) -> usize {
+    while i > 0 && data[i - 1].is_ascii_digit() {
+        i -= 1;
+    }
+    i
+}
+
+fn parse_number(digits: &[u8]) -> Option<u64> {
+    if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    std::str::from_utf8(digits).ok()?.parse().ok()
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).rposition(|window| window == needle)
+}
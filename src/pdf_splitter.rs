@@ -0,0 +1,295 @@
+use anyhow::{anyhow, Result};
+use lopdf::{Document, Object, ObjectId};
+use slug::slugify;
+use std::collections::{HashMap, HashSet};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tracing::{debug, info, warn};
+
+use crate::atomic_write::write_atomic;
+use crate::pdf_merger::{decode_text_string, flatten_page_tree};
+
+/// Guards against cyclic outline and name trees in malformed files
+const MAX_TREE_DEPTH: usize = 32;
+
+/// How `PdfSplitter::split_file` cuts a document into parts
+#[derive(Debug, Clone, PartialEq)]
+pub enum Split {
+    /// One file per top-level bookmark, named after it
+    Bookmarks,
+    /// One file per page range (1-based, inclusive)
+    Ranges(Vec<RangeInclusive<usize>>),
+    /// Consecutive files of this many pages
+    Count(usize),
+}
+
+/// A run of pages that becomes one output file
+#[derive(Debug, Clone)]
+struct Section {
+    title: String,
+    /// 1-based page numbers, inclusive
+    pages: RangeInclusive<usize>,
+}
+
+/// Splits a PDF into several files, the inverse of `PdfMerger`.
+pub struct PdfSplitter {
+    document: Document,
+    /// Page object ids in page order
+    page_ids: Vec<ObjectId>,
+}
+
+impl PdfSplitter {
+    pub async fn load(path: &Path) -> Result<Self> {
+        let data = fs::read(path)
+            .await
+            .map_err(|e| anyhow!("Failed to read PDF file {}: {}", path.display(), e))?;
+        let document = Document::load_mem(&data)
+            .map_err(|e| anyhow!("Failed to parse PDF file {}: {}", path.display(), e))?;
+        let page_ids = document.get_pages().into_values().collect();
+
+        Ok(Self { document, page_ids })
+    }
+
+    /// Splits `input` into `out_dir/NN_<title>.pdf` files and returns their paths in order.
+    pub async fn split_file(input: &Path, out_dir: &Path, split: &Split) -> Result<Vec<PathBuf>> {
+        let splitter = Self::load(input).await?;
+        let sections = splitter.sections(split)?;
+
+        fs::create_dir_all(out_dir)
+            .await
+            .map_err(|e| anyhow!("Failed to create directory {}: {}", out_dir.display(), e))?;
+
+        let mut written = Vec::with_capacity(sections.len());
+        for (i, section) in sections.iter().enumerate() {
+            let slug = match slugify(&section.title) {
+                slug if slug.is_empty() => "section".to_string(),
+                slug => slug,
+            };
+            let path = out_dir.join(format!("{:02}_{}.pdf", i + 1, slug));
+
+            info!("Writing pages {}-{} ({}) to {}",
+                  section.pages.start(), section.pages.end(), section.title, path.display());
+            splitter.save_section(section, &path).await?;
+            written.push(path);
+        }
+
+        Ok(written)
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.page_ids.len()
+    }
+
+    fn sections(&self, split: &Split) -> Result<Vec<Section>> {
+        let page_count = self.page_count();
+        if page_count == 0 {
+            return Err(anyhow!("The PDF has no pages"));
+        }
+
+        let sections = match split {
+            Split::Bookmarks => self.bookmark_sections()?,
+            Split::Ranges(ranges) => {
+                for range in ranges {
+                    if *range.start() == 0 || range.start() > range.end() || *range.end() > page_count {
+                        return Err(anyhow!("Page range {}-{} is outside the document's {} pages",
+                                           range.start(), range.end(), page_count));
+                    }
+                }
+                ranges
+                    .iter()
+                    .map(|range| Section { title: range_title(range), pages: range.clone() })
+                    .collect()
+            }
+            Split::Count(0) => return Err(anyhow!("Page count per file must be at least 1")),
+            Split::Count(count) => (1..=page_count)
+                .step_by(*count)
+                .map(|start| {
+                    let pages = start..=(start + count - 1).min(page_count);
+                    Section { title: range_title(&pages), pages }
+                })
+                .collect(),
+        };
+
+        Ok(sections)
+    }
+
+    /// One section per top-level bookmark, running until the next one starts.
+    /// Pages before the first bookmark become their own section.
+    fn bookmark_sections(&self) -> Result<Vec<Section>> {
+        let page_numbers: HashMap<ObjectId, usize> = self
+            .page_ids
+            .iter()
+            .enumerate()
+            .map(|(i, id)| (*id, i + 1))
+            .collect();
+
+        let mut starts: Vec<(usize, String)> = self
+            .top_level_bookmarks()
+            .into_iter()
+            .filter_map(|(title, page_id)| {
+                let page = page_numbers.get(&page_id).copied();
+                if page.is_none() {
+                    warn!("Bookmark \"{}\" doesn't point at a page, ignoring it", title);
+                }
+                page.map(|page| (page, title))
+            })
+            .collect();
+
+        if starts.is_empty() {
+            return Err(anyhow!("The PDF has no bookmarks to split by, use ranges or a page count instead"));
+        }
+
+        // Readers list bookmarks in outline order, which needn't be page order;
+        // for a page shared by several bookmarks the first one names the section
+        starts.sort_by_key(|(page, _)| *page);
+        starts.dedup_by_key(|(page, _)| *page);
+        if starts[0].0 > 1 {
+            starts.insert(0, (1, "Front matter".to_string()));
+        }
+
+        let ends = starts.iter().skip(1).map(|(page, _)| page - 1).chain([self.page_count()]);
+        Ok(starts
+            .iter()
+            .zip(ends)
+            .map(|((start, title), end)| Section { title: title.clone(), pages: *start..=end })
+            .collect())
+    }
+
+    /// Title and target page of every top-level outline item, in outline order
+    fn top_level_bookmarks(&self) -> Vec<(String, ObjectId)> {
+        let doc = &self.document;
+        let first = doc
+            .catalog()
+            .ok()
+            .and_then(|catalog| catalog.get(b"Outlines").ok())
+            .and_then(|outlines| doc.dereference(outlines).ok())
+            .and_then(|(_, outlines)| outlines.as_dict().ok())
+            .and_then(|outlines| outlines.get(b"First").and_then(Object::as_reference).ok());
+
+        let mut bookmarks = Vec::new();
+        let mut visited = HashSet::new();
+        let mut item = first;
+        while let Some(item_id) = item.filter(|id| visited.insert(*id)) {
+            let Ok(dict) = doc.get_dictionary(item_id) else {
+                break;
+            };
+            let title = dict
+                .get(b"Title")
+                .and_then(Object::as_str)
+                .map(decode_text_string)
+                .unwrap_or_default();
+
+            match self.bookmark_page(dict) {
+                Some(page_id) => bookmarks.push((title.trim().to_string(), page_id)),
+                None => debug!("Bookmark \"{}\" has no page destination", title),
+            }
+            item = dict.get(b"Next").and_then(Object::as_reference).ok();
+        }
+
+        bookmarks
+    }
+
+    /// Page an outline item jumps to, through `/Dest` or a `/GoTo` action
+    fn bookmark_page(&self, item: &lopdf::Dictionary) -> Option<ObjectId> {
+        let doc = &self.document;
+        let dest = match item.get(b"Dest") {
+            Ok(dest) => dest,
+            Err(_) => {
+                let action = doc.dereference(item.get(b"A").ok()?).ok()?.1.as_dict().ok()?;
+                if action.get(b"S").and_then(Object::as_name).ok()? != b"GoTo" {
+                    return None;
+                }
+                action.get(b"D").ok()?
+            }
+        };
+        self.destination_page(dest, 0)
+    }
+
+    fn destination_page(&self, dest: &Object, depth: usize) -> Option<ObjectId> {
+        let doc = &self.document;
+        if depth > MAX_TREE_DEPTH {
+            return None;
+        }
+
+        match doc.dereference(dest).ok()?.1 {
+            Object::Array(array) => array.first()?.as_reference().ok(),
+            // A named destination's value is the array or a dictionary holding it
+            Object::Dictionary(dict) => self.destination_page(dict.get(b"D").ok()?, depth + 1),
+            Object::Name(name) => {
+                let dests = doc.catalog().ok()?.get(b"Dests").ok()?;
+                let dests = doc.dereference(dests).ok()?.1.as_dict().ok()?;
+                self.destination_page(dests.get(name).ok()?, depth + 1)
+            }
+            Object::String(name, _) => {
+                let names = doc.catalog().ok()?.get(b"Names").ok()?;
+                let names = doc.dereference(names).ok()?.1.as_dict().ok()?;
+                let dest = self.find_in_name_tree(names.get(b"Dests").ok()?, name, 0)?;
+                self.destination_page(dest, depth + 1)
+            }
+            _ => None,
+        }
+    }
+
+    fn find_in_name_tree<'a>(&'a self, node: &'a Object, key: &[u8], depth: usize) -> Option<&'a Object> {
+        let doc = &self.document;
+        if depth > MAX_TREE_DEPTH {
+            return None;
+        }
+        let node = doc.dereference(node).ok()?.1.as_dict().ok()?;
+
+        if let Ok(names) = node.get(b"Names").and_then(Object::as_array) {
+            let found = names
+                .chunks_exact(2)
+                .find(|pair| pair[0].as_str().is_ok_and(|name| name == key));
+            if let Some(pair) = found {
+                return Some(&pair[1]);
+            }
+        }
+
+        let kids = node.get(b"Kids").and_then(Object::as_array).ok()?;
+        kids.iter().find_map(|kid| self.find_in_name_tree(kid, key, depth + 1))
+    }
+
+    async fn save_section(&self, section: &Section, path: &Path) -> Result<()> {
+        let mut doc = self.document.clone();
+        let kept: Vec<ObjectId> = self.page_ids[section.pages.start() - 1..*section.pages.end()].to_vec();
+
+        let pages_id = doc
+            .catalog()
+            .and_then(|catalog| catalog.get(b"Pages"))
+            .and_then(Object::as_reference)
+            .map_err(|e| anyhow!("Failed to find the page tree: {}", e))?;
+
+        // Inherited attributes come along before the other pages are cut loose
+        flatten_page_tree(&mut doc, pages_id, &self.page_ids);
+        if let Ok(pages) = doc.get_dictionary_mut(pages_id) {
+            pages.set("Kids", kept.iter().copied().map(Object::Reference).collect::<Vec<_>>());
+            pages.set("Count", kept.len() as i64);
+        }
+
+        // These index the whole document and would keep every page alive
+        if let Ok(catalog) = doc.catalog_mut() {
+            for key in [b"Outlines".as_slice(), b"PageLabels", b"Dests", b"PageMode"] {
+                catalog.remove(key);
+            }
+        }
+        doc.prune_objects();
+
+        let mut data = Vec::new();
+        doc.save_to(&mut data)
+            .map_err(|e| anyhow!("Failed to serialize {}: {}", path.display(), e))?;
+        write_atomic(path, data)
+            .await
+            .map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))
+    }
+}
+
+/// `pages 3-7`, or `page 3` for a single page
+fn range_title(range: &RangeInclusive<usize>) -> String {
+    if range.start() == range.end() {
+        format!("page {}", range.start())
+    } else {
+        format!("pages {}-{}", range.start(), range.end())
+    }
+}
@@ -0,0 +1,140 @@
+use scraper::{Html, Selector};
+use std::fmt;
+use tracing::debug;
+
+/// Documentation platforms the crawler knows how to collect links from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocPlatform {
+    /// The original GitBook, rendered with react-native-web
+    GitBookLegacy,
+    GitBook,
+    Docusaurus,
+    Mintlify,
+}
+
+/// How well a platform's sites are known to work
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Maturity {
+    /// Used on many sites; issues are bugs
+    Stable,
+    /// Recently added, expect sites that need workarounds
+    Experimental,
+}
+
+/// Something in a page's HTML that identifies its platform
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Marker {
+    /// An element matching this CSS selector
+    Selector(&'static str),
+    /// Elements matching each of these selectors, which are too generic on their own
+    AllOf(&'static [&'static str]),
+    /// An inline script containing this text
+    Script(&'static str),
+}
+
+impl DocPlatform {
+    /// Every platform, in the order detection tries them
+    pub const ALL: [DocPlatform; 4] = [
+        DocPlatform::GitBookLegacy,
+        DocPlatform::GitBook,
+        DocPlatform::Docusaurus,
+        DocPlatform::Mintlify,
+    ];
+
+    pub fn maturity(self) -> Maturity {
+        match self {
+            DocPlatform::GitBookLegacy | DocPlatform::GitBook | DocPlatform::Docusaurus => Maturity::Stable,
+            DocPlatform::Mintlify => Maturity::Experimental,
+        }
+    }
+
+    /// What detection looks for; any one marker is enough
+    pub fn markers(self) -> &'static [Marker] {
+        match self {
+            DocPlatform::GitBookLegacy => &[Marker::Selector("body > .gitbook-root")],
+            DocPlatform::GitBook => &[
+                Marker::Selector("body > div.scroll-nojump"),
+                Marker::Selector("nav[role=\"navigation\"]"),
+                Marker::Selector("a[href*=\"gitbook.io\"]"),
+                Marker::Selector("body[class*=\"theme-\"]"),
+            ],
+            DocPlatform::Docusaurus => &[
+                Marker::Selector("div#__docusaurus"),
+                Marker::Selector("div.docusaurus-root"),
+                Marker::Selector("nav.navbar--fixed-top"),
+                Marker::Selector("div.navbar__logo"),
+                Marker::Selector("script[src*=\"docusaurus\"]"),
+                Marker::Script("docusaurus"),
+                Marker::Script("__DOCUSAURUS__"),
+            ],
+            DocPlatform::Mintlify => &[
+                Marker::Selector("meta[name=\"generator\"][content*=\"Mintlify\" i]"),
+                Marker::Selector(".mintlify"),
+                Marker::Selector("script[src*=\"mintlify\" i]"),
+                Marker::Selector("link[href*=\"mintlify\" i]"),
+                Marker::AllOf(&["#navbar", "#sidebar-content"]),
+                // Covers the `__mintlify` globals and the config it inlines
+                Marker::Script("mintlify"),
+            ],
+        }
+    }
+
+    /// The first platform, in `ALL` order, with a marker in `document`
+    pub fn detect(document: &Html) -> Option<DocPlatform> {
+        Self::ALL.into_iter().find(|platform| {
+            let found = platform.markers().iter().find(|marker| marker.matches(document));
+            if let Some(marker) = found {
+                debug!("Detected {} site from {}", platform, marker);
+            }
+            found.is_some()
+        })
+    }
+}
+
+impl Marker {
+    fn matches(&self, document: &Html) -> bool {
+        let has = |selector: &str| Selector::parse(selector).is_ok_and(|selector| document.select(&selector).next().is_some());
+        match self {
+            Marker::Selector(selector) => has(selector),
+            Marker::AllOf(selectors) => selectors.iter().all(|selector| has(selector)),
+            Marker::Script(text) => {
+                let script_selector = Selector::parse("script").unwrap();
+                document
+                    .select(&script_selector)
+                    .any(|script| script.text().collect::<String>().contains(text))
+            }
+        }
+    }
+}
+
+impl fmt::Display for DocPlatform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DocPlatform::GitBookLegacy => "GitBook (legacy)",
+            DocPlatform::GitBook => "GitBook",
+            DocPlatform::Docusaurus => "Docusaurus",
+            DocPlatform::Mintlify => "Mintlify",
+        };
+        f.write_str(name)
+    }
+}
+
+impl fmt::Display for Maturity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Maturity::Stable => "stable",
+            Maturity::Experimental => "experimental",
+        };
+        f.write_str(name)
+    }
+}
+
+impl fmt::Display for Marker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Marker::Selector(selector) => f.write_str(selector),
+            Marker::AllOf(selectors) => write!(f, "{} together", selectors.join(" and ")),
+            Marker::Script(text) => write!(f, "a <script> containing \"{}\"", text),
+        }
+    }
+}
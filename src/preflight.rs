@@ -0,0 +1,79 @@
+use anyhow::{anyhow, Result};
+use chromiumoxide::detection::{default_executable, DetectionOptions};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+use tracing::{debug, warn};
+
+const INSTALL_HINT: &str = "Install Google Chrome or Chromium (e.g. `sudo apt install chromium`, \
+    `brew install --cask google-chrome`, or https://www.google.com/chrome/), \
+    or point book2pdf at an existing binary with --chrome-path <path> (or the CHROME environment variable)";
+
+static CHROME: OnceLock<Result<PathBuf, String>> = OnceLock::new();
+
+/// Locates a usable Chrome/Chromium executable before the browser is launched.
+///
+/// The check runs once per process; later calls return the cached outcome.
+pub(crate) fn chrome_executable(explicit: Option<&Path>) -> Result<PathBuf> {
+    CHROME
+        .get_or_init(|| check_chrome(explicit))
+        .clone()
+        .map_err(|e| anyhow!(e))
+}
+
+fn check_chrome(explicit: Option<&Path>) -> Result<PathBuf, String> {
+    let executable = match explicit {
+        Some(path) if path.is_file() => path.to_path_buf(),
+        Some(path) => {
+            return Err(format!("Chrome executable '{}' does not exist. {}", path.display(), INSTALL_HINT));
+        }
+        None => default_executable(DetectionOptions::default())
+            .map_err(|_| format!("Could not find Chrome/Chromium. {}", INSTALL_HINT))?,
+    };
+
+    // Chrome on Windows doesn't answer --version, so the probe only runs elsewhere
+    if cfg!(not(windows)) {
+        let output = Command::new(&executable)
+            .arg("--version")
+            .output()
+            .map_err(|e| format!("Found '{}' but it could not be started: {}. {}", executable.display(), e, INSTALL_HINT))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let reason = match stderr.trim() {
+                "" => output.status.to_string(),
+                stderr => stderr.to_string(),
+            };
+            return Err(format!(
+                "'{}' is not a working Chrome/Chromium installation ({}). {}",
+                executable.display(),
+                reason,
+                INSTALL_HINT
+            ));
+        }
+
+        debug!("Using {} ({})", executable.display(), String::from_utf8_lossy(&output.stdout).trim());
+    }
+
+    Ok(executable)
+}
+
+/// Checks a `--user-data-dir` profile before Chrome is pointed at it.
+///
+/// A running Chrome keeps a `SingletonLock` in its profile (`lockfile` on
+/// Windows); launching another instance on it hands the window to the running
+/// one, and the crawl never gets a browser to talk to.
+pub(crate) fn check_profile_dir(path: &Path) -> Result<()> {
+    if !path.is_dir() {
+        return Err(anyhow!("Chrome profile directory '{}' does not exist", path.display()));
+    }
+
+    let in_use = ["SingletonLock", "lockfile"]
+        .iter()
+        .any(|lock| path.join(lock).symlink_metadata().is_ok());
+    if in_use {
+        warn!("The Chrome profile in '{}' looks in use, close every Chrome window using it \
+               before crawling (a lock left by a crash is fine)", path.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,149 @@
+use anyhow::{anyhow, Result};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+/// Time spent in each phase of a page capture, in milliseconds
+#[derive(Debug, Clone, Copy, Default, Serialize, JsonSchema)]
+pub struct PhaseTimings {
+    /// Opening the tab and loading the page
+    pub navigation_ms: u64,
+    /// Preparing the loaded page for print
+    pub render_ms: u64,
+    /// Chrome's PDF generation
+    pub pdf_ms: u64,
+}
+
+/// Outcome of capturing a single documentation page
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct PageReport {
+    pub url: String,
+    pub slug: String,
+    /// HTTP status of the page's main document, when known
+    pub http_status: Option<u16>,
+    /// Size of the generated PDF in bytes
+    pub bytes: Option<u64>,
+    /// Number of pages in the generated PDF
+    pub page_count: Option<usize>,
+    /// Time spent on the last capture attempt, in milliseconds
+    pub duration_ms: u64,
+    /// Phase breakdown of the last successful attempt, or of a last attempt
+    /// that timed out, up to the phase that stalled
+    pub timings: Option<PhaseTimings>,
+    pub attempts: u32,
+    pub success: bool,
+    /// Why the capture was skipped, if it was
+    pub skipped: Option<String>,
+    /// The page never became ready and was captured anyway when the timeout hit
+    pub timed_out: bool,
+    /// The phase that ran out of time on the last attempt (navigation, settle
+    /// or pdf), whether the page was then captured anyway or failed
+    pub stalled_phase: Option<String>,
+    /// Chrome printed the page as an empty or invalid PDF, even when asked again
+    pub empty_pdf: bool,
+    /// Images printed without an `alt` attribute, when capturing an accessible PDF
+    pub missing_alt: Vec<String>,
+    pub error: Option<String>,
+}
+
+/// A discovered link that `--verify-links` dropped before the crawl
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct PrunedLink {
+    pub url: String,
+    pub title: String,
+    pub reason: String,
+}
+
+/// Per-page statistics collected during a crawl
+#[derive(Debug, Clone, Default, Serialize, JsonSchema)]
+pub struct CrawlReport {
+    pub pages: Vec<PageReport>,
+    /// Links dropped by `--verify-links`, which have no page entry
+    pub pruned_links: Vec<PrunedLink>,
+    /// Time spent combining the pages into one PDF, in milliseconds
+    pub merge_ms: Option<u64>,
+    /// Documentation platform the site was detected as
+    pub platform: Option<String>,
+    /// Nav links dropped because they pointed at a page already listed
+    pub duplicate_links: usize,
+    /// The combined PDF, or the directory of the page PDFs when they weren't
+    /// combined; set once the run is finished
+    pub output_path: Option<PathBuf>,
+    /// Number of pages in the combined PDF
+    pub combined_page_count: Option<usize>,
+}
+
+impl CrawlReport {
+    pub fn succeeded(&self) -> usize {
+        self.pages.iter().filter(|p| p.success).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.pages.len() - self.succeeded()
+    }
+
+    /// Pages that succeeded without a capture: unchanged since the previous
+    /// manifest, or reused from an earlier run
+    pub fn skipped(&self) -> usize {
+        self.pages.iter().filter(|p| p.success && p.skipped.is_some()).count()
+    }
+
+    /// Writes the report as CSV when the path ends in `.csv`, as JSON otherwise
+    pub async fn write_to(&self, path: &Path) -> Result<()> {
+        let is_csv = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+
+        let data = if is_csv {
+            self.to_csv()
+        } else {
+            serde_json::to_string_pretty(self)?
+        };
+
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)
+                .await
+                .map_err(|e| anyhow!("Failed to create directory {}: {}", parent.display(), e))?;
+        }
+
+        fs::write(path, data)
+            .await
+            .map_err(|e| anyhow!("Failed to write stats file {}: {}", path.display(), e))
+    }
+
+    fn to_csv(&self) -> String {
+        let mut csv = String::from("url,slug,http_status,bytes,page_count,duration_ms,navigation_ms,render_ms,pdf_ms,attempts,success,skipped,timed_out,empty_pdf,missing_alt,error\n");
+        for page in &self.pages {
+            let fields = [
+                csv_field(&page.url),
+                csv_field(&page.slug),
+                page.http_status.map(|s| s.to_string()).unwrap_or_default(),
+                page.bytes.map(|b| b.to_string()).unwrap_or_default(),
+                page.page_count.map(|c| c.to_string()).unwrap_or_default(),
+                page.duration_ms.to_string(),
+                page.timings.map(|t| t.navigation_ms.to_string()).unwrap_or_default(),
+                page.timings.map(|t| t.render_ms.to_string()).unwrap_or_default(),
+                page.timings.map(|t| t.pdf_ms.to_string()).unwrap_or_default(),
+                page.attempts.to_string(),
+                page.success.to_string(),
+                csv_field(page.skipped.as_deref().unwrap_or("")),
+                page.timed_out.to_string(),
+                page.empty_pdf.to_string(),
+                csv_field(&page.missing_alt.join(" ")),
+                csv_field(page.error.as_deref().unwrap_or("")),
+            ];
+            csv.push_str(&fields.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}